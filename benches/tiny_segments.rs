@@ -0,0 +1,48 @@
+//! Reading speed of files with a header per sample, as written by the File Meta Sink in
+//! unbuffered mode. Run with `cargo bench --bench tiny_segments`.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use gnuradio_meta::core::{AttachedHeader, SampleReadSeek};
+use gnuradio_meta::header::{DataType, HeaderBuilder, SeekPreserve};
+use gnuradio_meta::writer::write_from_iter;
+
+const NUM_SAMPLES: u64 = 200_000;
+
+fn main() {
+    let template = HeaderBuilder::new()
+        .dtype(DataType::Float)
+        .cplx(false)
+        .num_samples(1)
+        .build();
+    let mut file = Vec::new();
+    write_from_iter(&mut file, &template, (0..NUM_SAMPLES).map(|i| i as f32)).unwrap();
+
+    let mut reader = AttachedHeader::from_bytes(&file);
+    let mut buf = vec![0.0f32; 4096];
+    for pass in ["cold", "warm"] {
+        reader
+            .seek(std::io::SeekFrom::Start(0), SeekPreserve::None)
+            .unwrap_or(0);
+        let start = Instant::now();
+        let mut total = 0;
+        loop {
+            let n = reader.read_samples(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            total += n;
+            black_box(&buf);
+        }
+        let elapsed = start.elapsed();
+        assert_eq!(total, NUM_SAMPLES);
+        println!(
+            "{} read of {} 1-sample segments: {:?} ({:.1} ns/segment)",
+            pass,
+            NUM_SAMPLES,
+            elapsed,
+            elapsed.as_nanos() as f64 / NUM_SAMPLES as f64
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! Builds headers from arbitrary header and extra dicts, which must never panic, only error.
+//! Run with `cargo fuzz run fuzz_from_tags`.
+#![no_main]
+
+use gnuradio_meta::header::Header;
+use gnuradio_meta::pmt::{ParseOptions, Tag, parse_with};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Tolerant, so that more inputs reach from_tags
+    let opts = ParseOptions {
+        tolerant: true,
+        ..Default::default()
+    };
+    let mut reader = data;
+    let Ok(tag) = parse_with(&mut reader, &opts) else {
+        return;
+    };
+    let extra = parse_with(&mut reader, &opts).unwrap_or(Tag::Null());
+
+    // Positions near the end of the range, set by the bytes left, reach overflows
+    let byte_in_file = u64::MAX - reader.len() as u64;
+    if let Ok(header) = Header::from_tags(byte_in_file, tag, extra) {
+        let _ = header.get_num_samples();
+        let _ = header.get_sample_time(0);
+        let _ = header.data_file_range();
+    }
+});
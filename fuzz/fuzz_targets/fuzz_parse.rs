@@ -0,0 +1,19 @@
+//! Parses arbitrary bytes as PMTs, which must never panic. Run with `cargo fuzz run fuzz_parse`.
+#![no_main]
+
+use gnuradio_meta::pmt::{ParseOptions, parse, parse_maybe_eof, parse_with};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parse(&mut &data[..]);
+
+    // Headers are parsed one after another until EOF
+    let mut reader = data;
+    while let Ok(Some(_)) = parse_maybe_eof(&mut reader) {}
+
+    let tolerant = ParseOptions {
+        tolerant: true,
+        ..Default::default()
+    };
+    let _ = parse_with(&mut &data[..], &tolerant);
+});
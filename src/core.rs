@@ -1,51 +1,189 @@
+use bytemuck::Pod;
 use num_complex::Complex;
+use num_traits::Zero;
 use std::collections::BTreeMap;
-use std::io::{Read, Seek, SeekFrom};
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::mem::MaybeUninit;
+use std::path::Path;
 
-use crate::header::{Header, InvalidHeaderError, SeekPreserve};
-use crate::pmt::{Timestamp, parse, parse_maybe_eof};
+use crate::header::{
+    ConversionScaling, ConversionStats, DataType, Endianness, FloatSample, Format, Header,
+    HeaderBuilder, InvalidHeaderError, RxTimeMode, SampleType, SeekPreserve, TimeGapSeek,
+};
+use crate::index::FileIndex;
+use crate::pmt::{Tag, Timestamp, parse, parse_maybe_eof};
 use thiserror::Error;
 
+/// Key of a header in the store: the byte in the binary file the header starts at, either because
+/// it's stored there, or because the first byte of its segment is there, and then the position
+/// of the header in the file it's stored in. Empty segments of dettached files share their byte
+/// with the next segment, so the latter tells them apart.
+type StoreKey = (u64, u64);
+
 #[derive(Default)]
 pub struct HeaderStorage {
-    /// Maps a byte in the binary file to the header that starts at that byte, either
-    /// because it's stored there, or because the first byte of that header's segment is there.
-    store: BTreeMap<u64, Header>,
+    /// Headers loaded, in file order
+    store: BTreeMap<StoreKey, Header>,
+    /// How rx_time of the headers is to be interpreted
+    rx_time_mode: RxTimeMode,
+    /// What seeking to a time between segments does
+    time_gap_seek: TimeGapSeek,
+    /// Segment after a time gap being filled by read_gap_filled, and zeros left to fill
+    gap_fill: Option<(u64, u64)>,
+    /// If true, extra dicts are not parsed and stored as Null
+    skip_extra_dict: bool,
+    /// Byte of the first sample read in the last call to read, if any sample was read
+    last_read_start: Option<u64>,
+    /// Byte order of the samples
+    endianness: Endianness,
+    /// Keys of the extra dict whose values are (sample offset, value) pairs
+    offset_tag_keys: Vec<String>,
+    /// How read_conv converts integers to floating point
+    conversion_scaling: ConversionScaling,
+    /// Correction added to rx_time of every header
+    time_offset: Timestamp,
+    /// Most headers loaded by a single lookup, DEFAULT_MAX_HEADERS_TO_SCAN if None
+    max_headers_to_scan: Option<u64>,
+    /// Most headers kept in store, all of them if None
+    max_cached_headers: Option<usize>,
+    /// Number of headers dropped from the start of store, which can't be loaded again
+    evicted: usize,
 }
 
+/// Most headers loaded by a single lookup by default, see HeaderReader::set_max_headers_to_scan
+pub const DEFAULT_MAX_HEADERS_TO_SCAN: u64 = 100_000_000;
+
 impl HeaderStorage {
     /// Gets the header applicable to a byte in the binary file (byte) or None if not loaded.
     /// Assumes all previous headers have already been loaded!
     fn get_header_for_byte(&self, byte: u64) -> Option<&Header> {
         use std::ops::Bound::*;
-        if self.store.len() == 0 {
-            return None;
-        }
-        // mut because of use of peek
-        let mut last = self.store.range((Unbounded, Excluded(byte))).peekable();
-        let last_or_first = if last.peek().is_none() {
-            self.store
-                .iter()
-                .next()
-                .expect("Checked before that self.store.len() != 0")
-                .1
-        } else {
-            last.peek().expect("Just checked on the if").1
-        };
+        // The last header starting at or before byte is the only candidate, as segments may be
+        // of any size (GNU Radio allows changing the header interval, and retunes cut segments short)
+        let (_, candidate) = self
+            .store
+            .range((Unbounded, Included((byte, u64::MAX))))
+            .next_back()?;
 
-        // If last_or_first is present, it may not be correct just yet, as it could be a too early header
-        let max_byte = last_or_first.abs_pos + last_or_first.bytes;
+        // The candidate may not be correct just yet, as it could be a too early header
+        let max_byte = candidate.abs_pos + candidate.bytes;
         if byte < max_byte {
-            Some(last_or_first)
+            Some(candidate)
         } else {
             None
         }
     }
 
-    fn add_header_for_byte(&mut self, byte: u64, header: Header) {
+    /// Storage with all headers of index already loaded. The index is trusted, see validate.
+    fn from_index(index: &FileIndex) -> HeaderStorage {
+        let mut storage = HeaderStorage::default();
+        for header in index.headers() {
+            // Same keys as load_next_header is called with
+            let byte = if header.attached {
+                header.pos_in_file
+            } else {
+                header.abs_pos
+            };
+            storage.store.insert((byte, header.pos_in_file), header);
+        }
+        storage
+    }
+
+    /// Checks that no loaded segment (header or data, if attached) starts before the data of the
+    /// previous one ends, which happens in corrupt files, for example if a sink crashed and was
+    /// restarted mid-file. The byte lookup would otherwise silently return wrong data.
+    pub fn validate(&self) -> Result<(), MetaFileError> {
+        let mut prev_end = None;
+        for (byte, header) in self {
+            check_overlap(prev_end, byte)?;
+            prev_end = Some(header.abs_pos + header.bytes);
+        }
+        Ok(())
+    }
+
+    /// Number of loaded headers
+    pub fn len(&self) -> usize {
+        self.store.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.store.is_empty()
+    }
+
+    /// First byte whose header may be looked up, as headers before it were evicted
+    fn first_retained_byte(&self) -> u64 {
+        match self.store.first_key_value() {
+            Some((&(byte, _), _)) if self.evicted > 0 => byte,
+            _ => 0,
+        }
+    }
+
+    /// Drops the oldest headers over max_cached_headers, but never that of the segment of the
+    /// last read sample or later ones, so the reader can continue from it. If nothing has been
+    /// read, the last max_cached_headers loaded are kept.
+    fn evict_over_max(&mut self) {
+        let Some(max) = self.max_cached_headers else {
+            return;
+        };
+        let current = self.last_read_start;
+        self.evict_while(|len, header| {
+            len > max && current.is_none_or(|byte| header.abs_pos + header.bytes <= byte)
+        });
+    }
+
+    /// Drops headers from the start while evict is true for them, always keeping the last one,
+    /// as the next header to load is found from it
+    fn evict_while(&mut self, mut evict: impl FnMut(usize, &Header) -> bool) {
+        while self.store.len() > 1 {
+            let len = self.store.len();
+            let first = self.store.first_entry().unwrap();
+            if !evict(len, first.get()) {
+                break;
+            }
+            first.remove();
+            self.evicted += 1;
+        }
+    }
+
+    fn add_header_for_byte(&mut self, byte: u64, header: Header) -> Result<(), MetaFileError> {
         // Check that all headers previous to this one have been loaded, or none
         // previous to it have been loaded, so the indexing logic works
-        self.store.insert(byte, header);
+        let key = (byte, header.pos_in_file);
+        let prev = self.store.range(..key).next_back();
+        check_overlap(prev.map(|(_, h)| h.abs_pos + h.bytes), byte)?;
+        let first_sample = prev.map_or(0, |(_, h)| h.sample_index_range().end);
+        let header = Header {
+            first_sample,
+            ..header.with_corrected_rx_time(self.time_offset)
+        };
+        self.store.insert(key, header);
+        self.evict_over_max();
+        Ok(())
+    }
+}
+
+/// Iterates the loaded headers in file order, along with the byte they are stored for: the
+/// position of the header if attached, or of the first byte of the segment if dettached
+impl<'a> IntoIterator for &'a HeaderStorage {
+    type Item = (u64, &'a Header);
+    type IntoIter = std::iter::Map<
+        std::collections::btree_map::Iter<'a, StoreKey, Header>,
+        fn((&'a StoreKey, &'a Header)) -> (u64, &'a Header),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.store.iter().map(|(&(byte, _), header)| (byte, header))
+    }
+}
+
+fn check_overlap(prev_end: Option<u64>, next_start: u64) -> Result<(), MetaFileError> {
+    match prev_end {
+        Some(prev_end) if next_start < prev_end => Err(MetaFileError::OverlappingSegments {
+            prev_end,
+            next_start,
+        }),
+        _ => Ok(()),
     }
 }
 
@@ -57,13 +195,272 @@ pub enum MetaFileError {
     ParseError(#[from] crate::pmt::ParseError),
     #[error("Invalid header error")]
     InvalidHeaderError(#[from] InvalidHeaderError),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(&'static str),
+    #[error("Samples are not aligned in memory as required for zero-copy access")]
+    Unaligned,
+    #[error("Expected samples of type {expected}, but segment is of type {found}")]
+    WrongSampleType {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("Type {0} can't hold samples, as its size doesn't match its sample format")]
+    UnsupportedSampleType(&'static str),
+    #[error("Seek target is not compatible with the current segment")]
+    IncompatibleSeek,
+    #[error("Time is not within any segment of the file")]
+    TimeOutOfRange,
+    #[error("Loaded more than {0} headers in a single lookup, likely corrupt file")]
+    ScanLimitExceeded(u64),
+    #[error("Header was evicted from the cache, see HeaderReader::set_max_cached_headers")]
+    EvictedHeader,
+    #[error("Invalid or missing field {0} in file index")]
+    InvalidIndex(&'static str),
+    #[error(
+        "Segment starting at byte {next_start} overlaps the previous one, ending at {prev_end}"
+    )]
+    OverlappingSegments { prev_end: u64, next_start: u64 },
+}
+
+/// An entry of the extra dict of a segment, located at the sample it applies to
+#[derive(Clone, Debug, PartialEq)]
+pub struct StreamTag {
+    /// Global index of the sample the tag applies to, counting from the start of the file
+    pub offset: u64,
+    pub key: String,
+    pub value: Tag,
+}
+
+/// Gets the stream tags of the extra dict of header, whose segment starts at global sample
+/// first_sample. Tags apply to the first sample of the segment, except:
+/// - Those with a key in offset_keys and a (sample offset, value) pair as value, which apply to
+///   the given sample of the segment instead.
+/// - Serialized GNU Radio tags (see gr_tag), which apply to the absolute sample given by their
+///   own offset, and take their own key.
+fn stream_tags(header: &Header, first_sample: u64, offset_keys: &[String]) -> Vec<StreamTag> {
+    let Tag::Dict(dict) = header.extra_dict.as_ref() else {
+        return Vec::new();
+    };
+    dict.iter()
+        .map(|(key, value)| {
+            if let Some((offset, tag_key, value)) = gr_tag(value) {
+                return StreamTag {
+                    offset,
+                    key: tag_key.unwrap_or(key).clone(),
+                    value: value.clone(),
+                };
+            }
+
+            let offset_pair = match value {
+                Tag::Pair(offset, value) if offset_keys.contains(key) => match offset.as_ref() {
+                    Tag::UInt64(u) => Some((*u, value.as_ref())),
+                    Tag::Int32(i) if *i >= 0 => Some((*i as u64, value.as_ref())),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let (offset, value) = offset_pair.unwrap_or((0, value));
+            StreamTag {
+                offset: first_sample + offset,
+                key: key.clone(),
+                value: value.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Recognizes a GNU Radio tag (gr::tag_t) serialized either as a dict with `offset`, `key` and
+/// `value` (and usually `srcid`) entries, or as a (key, value, srcid, offset) tuple. Returns its
+/// absolute sample offset, its key if it's a symbol, and its value.
+fn gr_tag(tag: &Tag) -> Option<(u64, Option<&String>, &Tag)> {
+    let (key, value, offset) = match tag {
+        Tag::Dict(_) => (tag.get("key")?, tag.get("value")?, tag.get("offset")?),
+        Tag::Tuple(items) => match items.as_slice() {
+            [key @ Tag::Symbol(_), value, _srcid, offset] => (key, value, offset),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let key = match key {
+        Tag::Symbol(s) => Some(s),
+        _ => None,
+    };
+    Some((offset.get_u64()?, key, value))
 }
 
+#[non_exhaustive]
 pub struct SampleMeta {
     /// Sample rate of the data read
-    samp_rate: f64,
+    pub samp_rate: f64,
     /// Reception time of the first sample read
-    rx_time: Timestamp,
+    pub rx_time: Timestamp,
+    /// Center frequency of the data read, from the `rx_freq` tag of the extra dict if present
+    pub center_freq: Option<f64>,
+    /// Bandwidth of the data read, from the `bandwidth` tag of the extra dict if present
+    pub bandwidth: Option<f64>,
+}
+
+/// Why a read stopped before reading more samples
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The buffer was filled
+    BufferFull,
+    /// The end of the file was reached
+    Eof,
+    /// The next segment has a different format
+    FormatChange,
+    /// The next segment has a different sample rate
+    SampleRateChange,
+    /// The next segment doesn't continue in time the previous one
+    TimeDiscontinuity,
+    /// The segment to read can't be read into the target type
+    NotDirectlyReadable,
+}
+
+/// Iterator over the headers of a file, see HeaderReader::segments
+pub struct Segments<'a, R: HeaderReader> {
+    reader: &'a mut R,
+    /// Lowest key of the store the next header may have, or None once done
+    next_key: Option<StoreKey>,
+    /// Index in the file of the next header
+    index: usize,
+}
+
+impl<R: HeaderReader> Iterator for Segments<'_, R> {
+    type Item = Result<Header, MetaFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next_key = self.next_key?;
+        if self.index < self.reader.get_header_storage().evicted {
+            self.next_key = None;
+            return Some(Err(MetaFileError::EvictedHeader));
+        }
+        // Headers are loaded left-to-right, so the next one is either loaded or the next to load
+        let loaded = self
+            .reader
+            .get_header_storage()
+            .store
+            .range(next_key..)
+            .next()
+            .map(|(&key, header)| (key, header.clone()));
+        let found = match loaded {
+            Some(v) => Ok(Some(v)),
+            None => {
+                let first_byte = self.reader.get_first_byte_of_next_header_to_read();
+                self.reader.load_next_header(first_byte).and_then(|header| {
+                    let Some(header) = header else {
+                        return Ok(None);
+                    };
+                    let storage = self.reader.get_header_storage_mut();
+                    storage.add_header_for_byte(first_byte, header)?;
+                    // The stored header, as storing it completes it, which is the last one
+                    Ok(storage
+                        .store
+                        .last_key_value()
+                        .map(|(&key, h)| (key, h.clone())))
+                })
+            }
+        };
+
+        match found {
+            Ok(Some(((byte, pos), header))) => {
+                self.next_key = Some((byte, pos + 1));
+                self.index += 1;
+                Some(Ok(header))
+            }
+            Ok(None) => {
+                self.next_key = None;
+                None
+            }
+            Err(e) => {
+                self.next_key = None;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Iterator over blocks of samples of a file, see SampleReadSeek::iter_blocks
+pub struct Blocks<'a, R: SampleReadSeek, T> {
+    reader: &'a mut R,
+    block_size: usize,
+    done: bool,
+    _samples: std::marker::PhantomData<T>,
+}
+
+impl<R: SampleReadSeek, T: SampleType + Default> Iterator for Blocks<'_, R, T> {
+    type Item = Result<Vec<T>, MetaFileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut block = vec![T::default(); self.block_size];
+        match self.reader.read_run(&mut block) {
+            Ok((num_read, reason)) => {
+                // A short block is the last one, as reading stopped
+                self.done = reason != StopReason::BufferFull;
+                block.truncate(num_read as usize);
+                (num_read > 0).then_some(Ok(block))
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Where a segment of the file landed in the output of SampleReadSeek::flatten_timeline
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentMap {
+    /// Global index of the first sample of the segment in the file
+    pub source_first_sample: u64,
+    /// Index of the first sample of the segment in the flattened buffer
+    pub first_sample: u64,
+    pub num_samples: u64,
+    /// Reception time of the first sample of the segment, as given by its header
+    pub rx_time: Timestamp,
+    /// Time of the first sample of the segment in the flattened timeline, which starts at the
+    /// rx_time of the first segment and has no gaps
+    pub timeline_time: Timestamp,
+}
+
+/// Overview of the consistency of the segments of a file, see HeaderReader::profile. Empty
+/// segments are ignored.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileProfile {
+    /// All segments have the same format
+    pub uniform_format: bool,
+    /// All segments have the same sample rate
+    pub uniform_rate: bool,
+    /// Every segment continues in time the previous one
+    pub gapless: bool,
+    /// Format of most samples of the file, None if it has no samples
+    pub format: Option<Format>,
+    /// Sample rate of most samples of the file, None if it has no samples
+    pub samp_rate: Option<f64>,
+}
+
+/// Segment of the file at a given time, see HeaderReader::segment_at_time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SegmentLocation {
+    /// Index of the segment containing the time or, if in a gap, of the nearest segment
+    pub index: usize,
+    /// If the time is in a gap between segments, index of the segment before the gap
+    pub in_gap_after: Option<usize>,
+}
+
+/// Statistics of the samples of a file, see SampleReadSeek::stats. Complex samples contribute
+/// their magnitude. If count is 0, all other fields are NaN.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SampleStats {
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    /// Root mean square
+    pub rms: f64,
 }
 
 /// This trait allows accessing headers for both attached and dettached files using a common interface.
@@ -85,28 +482,36 @@ pub trait HeaderReader {
             Some(v) => v,
         };
 
-        // TODO: bytes may be wrong!
-
-        last.get().abs_pos + last.get().bytes + 1
+        // Segments are contiguous, so the next header (or data, if dettached) starts right after
+        last.get().abs_pos + last.get().bytes
     }
 
     fn get_header_for_byte(&mut self, byte: u64) -> Result<Option<Header>, MetaFileError> {
         if let Some(v) = self.get_header_storage().get_header_for_byte(byte) {
             return Ok(Some(v.clone()));
         }
+        if byte < self.get_header_storage().first_retained_byte() {
+            return Err(MetaFileError::EvictedHeader);
+        }
 
         // Not loaded! We need to get the first byte of the header, as 'byte' may be at any point
         // in the segment. Note that headers are always loaded "left-to-right", so this may load
         // a whole bunch of headers.
+        let max_headers = self.get_max_headers_to_scan();
+        let mut scanned = 0;
         loop {
             let first_byte = self.get_first_byte_of_next_header_to_read();
             if first_byte > byte {
                 // It should have already been loaded
                 return Ok(self.get_header_storage().get_header_for_byte(byte).cloned());
             }
+            if scanned == max_headers {
+                return Err(MetaFileError::ScanLimitExceeded(max_headers));
+            }
+            scanned += 1;
             if let Some(v) = self.load_next_header(first_byte)? {
                 self.get_header_storage_mut()
-                    .add_header_for_byte(first_byte, v);
+                    .add_header_for_byte(first_byte, v)?;
             } else {
                 // We reached EOF...
                 break;
@@ -116,311 +521,4758 @@ pub trait HeaderReader {
         // ...out of bounds byte
         Ok(None)
     }
-}
 
-fn read_raw<T>(reader: &mut impl Read, target: &mut [T]) -> Result<u64, MetaFileError> {
-    todo!();
-}
+    /// Loads the headers of every segment overlapping bytes byte_start..byte_end of the binary
+    /// file (including the inline headers if attached) in a single sequential pass, so later
+    /// random accesses within the range don't need to load any. Headers after the range are not
+    /// loaded, but those before it are, as headers are always loaded left-to-right.
+    /// Returns the number of segments overlapping the range.
+    fn preload_headers_for_range(
+        &mut self,
+        byte_start: u64,
+        byte_end: u64,
+    ) -> Result<usize, MetaFileError> {
+        if byte_start >= byte_end {
+            return Ok(0);
+        }
+        self.get_header_for_byte(byte_end - 1)?;
 
-/// Similar to Rust's Read + Seek, but obtaining individual samples instead of bytes,
-/// and with radio specific functionality (for example, you are guaranteed to never
-/// get streams with different sample rates, or with time jumps, if you use read())
-///
-/// For maximum performance, it's recommended to only ever read forward such that all
-/// disk access is sequential. This should yield maximum speed on most systems.
-pub trait SampleReadSeek {
-    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader;
-    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek);
+        Ok(self
+            .get_header_storage()
+            .store
+            .values()
+            .filter(|h| {
+                h.abs_pos - h.header_byte_len() < byte_end && h.abs_pos + h.bytes > byte_start
+            })
+            .count())
+    }
 
-    /// Gets the header that the last read sample belonged to, or None if no samples have been read
-    /// yet, or a seek has been performed.
-    fn get_last_read_header(&mut self) -> Result<Option<Header>, MetaFileError> {
-        let pos = self.get_sample_reader_mut().stream_position()?;
-        self.get_header_reader_mut().get_header_for_byte(pos)
+    /// Iterates the headers of all segments of the file in order, loading them as needed. Stops
+    /// after the first error, which is EvictedHeader if the next header was evicted (see
+    /// HeaderReader::set_max_cached_headers).
+    fn segments(&mut self) -> Segments<'_, Self>
+    where
+        Self: Sized,
+    {
+        Segments {
+            reader: self,
+            next_key: Some((0, 0)),
+            index: 0,
+        }
     }
 
-    fn get_last_read_rx_time(&mut self) -> Option<Timestamp> {
-        todo!("Implement");
-        /*
-        let offset = self.get_last_read_offset_in_header()?;
-        let header = self.get_last_read_header()?;
-        header.get_sample_time(offset);
-        */
+    /// Writes every field of every header in the file to out, including the entries of the extra
+    /// dicts, one per line, for debugging
+    fn dump_tags<W: Write>(&mut self, out: &mut W) -> Result<(), MetaFileError>
+    where
+        Self: Sized,
+    {
+        for (i, header) in self.segments().enumerate() {
+            let header = header?;
+            writeln!(out, "segment {}:", i)?;
+            writeln!(out, "  rx_rate: {}", header.samp_rate)?;
+            writeln!(out, "  rx_time: {:.9}", header.rx_time.as_secs_f64())?;
+            writeln!(out, "  size: {}", header.size)?;
+            writeln!(out, "  type: {}", header.dtype.type_string(header.cplx))?;
+            writeln!(out, "  cplx: {}", header.cplx)?;
+            writeln!(out, "  strt: {}", header.strt)?;
+            writeln!(out, "  bytes: {}", header.bytes)?;
+            match header.extra_dict.as_ref() {
+                Tag::Null() => {}
+                Tag::Dict(dict) => {
+                    for (key, value) in dict {
+                        writeln!(out, "  extra {}: {}", key, value)?;
+                    }
+                }
+                other => writeln!(out, "  extra: {}", other)?,
+            }
+        }
+        Ok(())
     }
 
-    #[doc(hidden)]
-    fn get_last_and_applicable_header(
-        &mut self,
-    ) -> Result<Option<(Header, Header)>, MetaFileError> {
-        let last_header = match self.get_last_read_header()? {
-            None => return Ok(None), // EOF of empty file
-            Some(v) => v,
-        };
+    /// Finds the first segment whose extra dict contains key, returning the global index of its
+    /// first sample and the value of the tag. Loads headers up to that segment.
+    fn find_first_tag(&mut self, key: &str) -> Result<Option<(u64, &Tag)>, MetaFileError>
+    where
+        Self: Sized,
+    {
+        for header in self.segments() {
+            if header?.extra_dict.get(key).is_some() {
+                break;
+            }
+        }
+        Ok(self
+            .get_header_storage()
+            .store
+            .values()
+            .find_map(|h| Some((h.first_sample, h.extra_dict.get(key)?))))
+    }
 
-        let appl_header = if last_header.abs_pos + last_header.bytes
-            <= self.get_sample_reader_mut().stream_position()?
-        {
-            // We finished the last segment, seek to next one
-            self.get_sample_reader_mut().seek(SeekFrom::Current(1))?;
-            let cur_pos = self.get_sample_reader_mut().stream_position()?;
-            let out = match self.get_header_reader_mut().get_header_for_byte(cur_pos)? {
-                None => return Ok(None), // EOF achieved
-                Some(v) => v,
-            };
-            self.get_sample_reader_mut()
-                .seek(SeekFrom::Start(out.abs_pos))?;
-            out
-        } else {
-            // We keep reading from last segment
-            last_header.clone()
-        };
+    /// Same as find_first_tag, but returning every segment whose extra dict contains key, in
+    /// file order. Loads all headers.
+    fn find_all_tags(&mut self, key: &str) -> Result<Vec<(u64, &Tag)>, MetaFileError> {
+        self.load_remaining_headers()?;
+        if self.get_header_storage().evicted > 0 {
+            return Err(MetaFileError::EvictedHeader);
+        }
+        Ok(self
+            .get_header_storage()
+            .store
+            .values()
+            .filter_map(|h| Some((h.first_sample, h.extra_dict.get(key)?)))
+            .collect())
+    }
 
-        Ok(Some((last_header, appl_header)))
+    /// Loads every header in the file, returning them in file order. Errors with EvictedHeader
+    /// if any was evicted, see HeaderReader::set_max_cached_headers.
+    fn load_all_headers(&mut self) -> Result<Vec<Header>, MetaFileError> {
+        self.load_remaining_headers()?;
+        if self.get_header_storage().evicted > 0 {
+            return Err(MetaFileError::EvictedHeader);
+        }
+        Ok(self.get_header_storage().store.values().cloned().collect())
     }
 
-    /// Fills buf from left to right, at most filling it completely. It will stop reading samples
-    /// if the next read would imply reading from:
-    /// - A segment with type not directly readable to T
-    /// - A segment with different sample rate from the one used in previous calls to read
-    /// - A segment with a jump in time with respect to the last sample of the previous segment
-    ///
-    /// Returns the number of samples actually read into buf.
-    /// This function will never perform conversion, so it's the fastest possible as it
-    /// will simply copy from the source file to the destination array.
-    ///
-    /// If an error is returned, the buffer may have been modified!
-    fn read_samples<T: 'static>(&mut self, buf: &mut [T]) -> Result<u64, MetaFileError> {
-        let mut num_read: u64 = 0;
+    /// Loads every header not yet loaded, up to the end of the file, without returning them.
+    fn load_remaining_headers(&mut self) -> Result<(), MetaFileError> {
+        loop {
+            let first_byte = self.get_first_byte_of_next_header_to_read();
+            match self.load_next_header(first_byte)? {
+                Some(v) => self
+                    .get_header_storage_mut()
+                    .add_header_for_byte(first_byte, v)?,
+                None => return Ok(()),
+            }
+        }
+    }
 
-        while num_read < buf.len() as u64 {
-            let (last_header, appl_header) = match self.get_last_and_applicable_header()? {
-                Some(v) => v,
-                None => break, // EOF or empty file
-            };
+    /// Loads every header in the file, and returns the number of samples in it.
+    fn get_total_samples(&mut self) -> Result<u64, MetaFileError> {
+        self.load_remaining_headers()?;
+        Ok(self
+            .get_header_storage()
+            .store
+            .values()
+            .next_back()
+            .map_or(0, |h| h.sample_index_range().end))
+    }
 
-            if !appl_header.dtype.reads_directly_to::<T>(appl_header.cplx) {
-                break; // Not directly readable to T, stop reading
-            }
+    /// Loads every header in the file and maps f over them, in file order
+    fn map_segments<R>(&mut self, f: impl FnMut(&Header) -> R) -> Result<Vec<R>, MetaFileError> {
+        Ok(self.load_all_headers()?.iter().map(f).collect())
+    }
 
-            if appl_header != last_header {
-                if !appl_header.is_compatible_with(&last_header, SeekPreserve::All) {
-                    break; // Something is different about the new header, stop reading
-                }
+    /// Loads every header in the file, and returns its table of contents, which can be used to
+    /// open the file again without scanning its headers (see AttachedHeader::with_index and
+    /// DettachedHeader::with_index).
+    fn build_index(&mut self) -> Result<FileIndex, MetaFileError> {
+        // The index stores the times of the file, without the time offset
+        let offset = Timestamp::ZERO - self.get_time_offset();
+        let headers: Vec<Header> = self
+            .load_all_headers()?
+            .iter()
+            .map(|h| h.with_corrected_rx_time(offset))
+            .collect();
+        let attached = headers.first().is_none_or(|h| h.attached);
+        Ok(FileIndex::from_headers(attached, &headers))
+    }
 
-                if !appl_header.is_continuation_of(&last_header) {
-                    break; // The segment had a time discontinuity, stop reading
-                }
+    /// Returns the global index of the first sample after every time discontinuity of the file
+    /// (as given by Header::is_continuation_of), and the gap before it (see Header::gap_to).
+    /// Empty segments are ignored.
+    fn dropouts(&mut self) -> Result<Vec<(u64, Timestamp)>, MetaFileError> {
+        let mut out = Vec::new();
+        let mut previous: Option<Header> = None;
+        let mut first_sample = 0;
+        for header in self.load_all_headers()? {
+            if header.get_num_samples() == 0 {
+                continue;
+            }
+            if let Some(previous) = &previous
+                && !header.is_continuation_of(previous)
+            {
+                out.push((first_sample, header.gap_to(previous)));
             }
+            first_sample += header.get_num_samples();
+            previous = Some(header);
+        }
+        Ok(out)
+    }
 
-            let buff_remain = buf.len() as u64 - num_read;
+    /// Checks whether the file is a single run of samples of the same format and sample rate
+    /// without gaps, as most files are, or a more complex one. Loads all headers.
+    fn profile(&mut self) -> Result<FileProfile, MetaFileError> {
+        let mut profile = FileProfile {
+            uniform_format: true,
+            uniform_rate: true,
+            gapless: true,
+            format: None,
+            samp_rate: None,
+        };
+        // Number of samples of each format and sample rate
+        let mut formats: Vec<(Format, u64)> = Vec::new();
+        let mut rates: Vec<(Header, u64)> = Vec::new();
+        let mut previous: Option<Header> = None;
+        for header in self.load_all_headers()? {
+            let num_samples = header.get_num_samples();
+            if num_samples == 0 {
+                continue;
+            }
+            if let Some(previous) = &previous {
+                profile.uniform_format &= header.format() == previous.format();
+                profile.uniform_rate &= header.same_samp_rate(previous);
+                profile.gapless &= header.is_continuation_of(previous);
+            }
 
-            let cur_sample =
-                appl_header.get_sample_pos_of_byte(self.get_sample_reader_mut().stream_position()?);
-            let samps_remain = appl_header.get_num_samples() - cur_sample;
+            match formats.iter_mut().find(|(f, _)| *f == header.format()) {
+                Some((_, count)) => *count += num_samples,
+                None => formats.push((header.format(), num_samples)),
+            }
+            match rates.iter_mut().find(|(h, _)| h.same_samp_rate(&header)) {
+                Some((_, count)) => *count += num_samples,
+                None => rates.push((header.clone(), num_samples)),
+            }
+            previous = Some(header);
+        }
 
-            let to_read = buff_remain.min(samps_remain);
-            let start = num_read as usize;
-            let end = start + to_read as usize;
+        // max_by_key returns the last maximum, so reverse to prefer the first one in the file
+        profile.format = formats
+            .iter()
+            .rev()
+            .max_by_key(|(_, n)| *n)
+            .map(|(f, _)| *f);
+        profile.samp_rate = rates
+            .iter()
+            .rev()
+            .max_by_key(|(_, n)| *n)
+            .map(|(h, _)| h.samp_rate);
+        Ok(profile)
+    }
 
-            num_read += read_raw(self.get_sample_reader_mut(), &mut buf[start..end])?;
+    /// Gets the header of the idx-th segment of the file (starting from 0), loading headers up to
+    /// it if needed, or None if the file has fewer segments.
+    fn get_header_at_index(&mut self, idx: usize) -> Result<Option<Header>, MetaFileError> {
+        // Headers are always loaded left-to-right, so the store holds the first segments (but
+        // those evicted)
+        let evicted = self.get_header_storage().evicted;
+        if idx < evicted {
+            return Err(MetaFileError::EvictedHeader);
+        }
+        while self.get_header_storage().store.len() + evicted <= idx {
+            let first_byte = self.get_first_byte_of_next_header_to_read();
+            match self.load_next_header(first_byte)? {
+                Some(v) => self
+                    .get_header_storage_mut()
+                    .add_header_for_byte(first_byte, v)?,
+                None => return Ok(None),
+            }
         }
 
-        Ok(num_read)
+        Ok(self
+            .get_header_storage()
+            .store
+            .values()
+            .nth(idx - evicted)
+            .cloned())
     }
 
-    /// Fills buf from left to right, at most filling it completely. It will stop reading samples
-    /// if the next read would imply reading from:
-    /// - A segment with type inconvertible to T
-    /// - A segment with different sample rate from the one used in previous calls to read
-    /// - A segment with a jump in time with respect to the last sample of the previous segment
-    ///
-    /// Returns the number of samples actually read into buf.
-    /// This function may convert if neccesary, and is thus expected to be slightly slower
-    /// than read.
-    fn read_conv<T>(&mut self, buf: &mut [T]) -> Result<u64, MetaFileError> {
-        todo!("Implement");
+    /// Finds the segment whose time span contains t, in the same time base as the rx_time of
+    /// headers. If t falls in a gap between segments, the nearest of the two is returned, along
+    /// with the index of the segment before the gap. Indices are as in get_header_at_index, but
+    /// empty segments are never returned. Errors with TimeOutOfRange if t is before the first
+    /// sample or after the last one of the file, or EvictedHeader if t is before the first
+    /// segment kept (see set_max_cached_headers). Loads all headers.
+    fn segment_at_time(&mut self, t: Timestamp) -> Result<SegmentLocation, MetaFileError> {
+        self.load_remaining_headers()?;
+        let storage = self.get_header_storage();
+        // Index and end time of the last non empty segment before t
+        let mut prev: Option<(usize, Timestamp)> = None;
+        for (index, header) in (storage.evicted..).zip(storage.store.values()) {
+            let num_samples = header.get_num_samples();
+            if num_samples == 0 {
+                continue;
+            }
+
+            if t < header.rx_time {
+                let (prev_index, prev_end) = match prev {
+                    Some(v) => v,
+                    None if storage.evicted > 0 => return Err(MetaFileError::EvictedHeader),
+                    None => return Err(MetaFileError::TimeOutOfRange),
+                };
+                let nearest = if t - prev_end < header.rx_time - t {
+                    prev_index
+                } else {
+                    index
+                };
+                return Ok(SegmentLocation {
+                    index: nearest,
+                    in_gap_after: Some(prev_index),
+                });
+            }
+
+            let end = header.get_sample_time(num_samples as i64);
+            if t < end {
+                return Ok(SegmentLocation {
+                    index,
+                    in_gap_after: None,
+                });
+            }
+            prev = Some((index, end));
+        }
+        Err(MetaFileError::TimeOutOfRange)
     }
 
-    /// Returns metadata that applies to all samples read in the previous call to read.
-    fn get_last_read_meta(&self) -> Option<SampleMeta> {
-        todo!("Implement");
+    /// Returns the global index of the first sample and the sample rate of every run of
+    /// consecutive segments with the same sample rate. More than one entry means the sample
+    /// rate changed mid-file (for example, because of a retune). Empty segments are skipped, and
+    /// rates are compared with Header::same_samp_rate.
+    fn sample_rate_segments(&mut self) -> Result<Vec<(u64, f64)>, MetaFileError> {
+        let mut out = Vec::new();
+        let mut first_sample = 0;
+        let mut prev: Option<Header> = None;
+        for header in self.load_all_headers()? {
+            if header.bytes == 0 {
+                continue;
+            }
+            if prev
+                .as_ref()
+                .is_none_or(|prev| !prev.same_samp_rate(&header))
+            {
+                out.push((first_sample, header.samp_rate));
+            }
+            first_sample += header.get_num_samples();
+            prev = Some(header);
+        }
+        Ok(out)
     }
 
-    /// Seeks within the file, preserving certain qualities of the current segment as
-    /// given in preserve. Returns the current position in samples from the start of the file, or
-    /// errors if the seek could not be performed, leaving the position unmodified.
-    fn seek(&mut self, pos: SeekFrom, preserve: SeekPreserve) -> Result<u64, MetaFileError> {
-        todo!("Implement");
+    /// Returns the segments whose extra dict differs from that of the previous segment, as given
+    /// by Header::extra_dict_fingerprint. The first segment is always included.
+    fn segments_with_tag_changes(&mut self) -> Result<Vec<Header>, MetaFileError> {
+        let mut last_fingerprint = None;
+        let mut out = Vec::new();
+        for header in self.load_all_headers()? {
+            let fingerprint = header.extra_dict_fingerprint();
+            if last_fingerprint != Some(fingerprint) {
+                out.push(header);
+            }
+            last_fingerprint = Some(fingerprint);
+        }
+        Ok(out)
     }
 
-    /// Same as seek, but moving to segment start samples, and pos given in segments.
-    /// Returns the current position in samples from the start of the file, or errors if the
-    /// seek could not be performed, leaving the position unmodified.
-    fn seek_segment(
-        &mut self,
-        pos_seg: SeekFrom,
-        preserve: SeekPreserve,
-    ) -> Result<u64, MetaFileError> {
-        todo!("Implement");
+    fn get_rx_time_mode(&self) -> RxTimeMode {
+        self.get_header_storage().rx_time_mode
     }
 
-    /// Seeks the next segment which has a format that can be converted to `T`, returning the
-    /// number of segments skipped, erroring if no such segment can be found.
-    fn seek_valid_segment(&mut self) -> Result<u64, MetaFileError> {
-        todo!("Implement");
+    /// Sets how rx_time of the headers is interpreted, Relative by default as per the GNU Radio docs
+    fn set_rx_time_mode(&mut self, mode: RxTimeMode) {
+        self.get_header_storage_mut().rx_time_mode = mode;
     }
-}
 
-pub struct AttachedHeader<T: Read + Seek> {
-    header_storage: HeaderStorage,
-    file: T,
-}
+    fn get_skip_extra_dict(&self) -> bool {
+        self.get_header_storage().skip_extra_dict
+    }
 
-impl<T: Read + Seek> AttachedHeader<T> {
-    fn new(file: T) -> AttachedHeader<T> {
-        AttachedHeader {
-            header_storage: Default::default(),
-            file,
-        }
+    /// If set, the extra dict of headers loaded from now on is not parsed and is stored as
+    /// Tag::Null(), which speeds up scanning files with many segments when only the core fields
+    /// are needed.
+    fn set_skip_extra_dict(&mut self, skip: bool) {
+        self.get_header_storage_mut().skip_extra_dict = skip;
     }
-}
 
-impl<T: Read + Seek> HeaderReader for AttachedHeader<T> {
-    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
-        &mut self.header_storage
+    fn get_endianness(&self) -> Endianness {
+        self.get_header_storage().endianness
     }
-    fn get_header_storage(&self) -> &HeaderStorage {
-        &self.header_storage
+
+    /// Sets the byte order of the samples in the file, native by default as GNU Radio writes
+    /// samples in the byte order of the host that recorded them
+    fn set_endianness(&mut self, endianness: Endianness) {
+        self.get_header_storage_mut().endianness = endianness;
     }
 
-    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
-        let old_pos = self.file.stream_position()?;
-        self.file.seek(SeekFrom::Start(start_byte))?;
+    fn get_conversion_scaling(&self) -> ConversionScaling {
+        self.get_header_storage().conversion_scaling
+    }
 
-        // header_file seek is always at the next header, so we can simply
-        let header_tag = match parse_maybe_eof(&mut self.file) {
-            Ok(Some(v)) => v,
-            Ok(None) => return Ok(None),
-            Err(e) => return Err(MetaFileError::ParseError(e)),
-        };
-        let extra = parse(&mut self.file)?;
-        let header = Header::from_tags(start_byte, header_tag, extra)?;
-        self.file.seek(SeekFrom::Start(old_pos))?;
+    /// Sets how read_conv converts integer samples to floating point, keeping their value (Raw)
+    /// by default as GNU Radio does
+    fn set_conversion_scaling(&mut self, scaling: ConversionScaling) {
+        self.get_header_storage_mut().conversion_scaling = scaling;
+    }
 
-        Ok(Some(header))
+    fn get_offset_tag_keys(&self) -> &[String] {
+        &self.get_header_storage().offset_tag_keys
     }
-}
 
-impl<T: Read + Seek> SampleReadSeek for AttachedHeader<T> {
-    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
-        self
+    /// Sets the keys of the extra dict whose values are (sample offset, value) pairs, which
+    /// some flowgraphs use for tags applying to a given sample of the segment instead of its
+    /// start. None by default.
+    fn set_offset_tag_keys(&mut self, keys: Vec<String>) {
+        self.get_header_storage_mut().offset_tag_keys = keys;
     }
 
-    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
-        &mut self.file
+    fn get_time_offset(&self) -> Timestamp {
+        self.get_header_storage().time_offset
     }
-}
 
-pub struct DettachedHeader<B: Read + Seek, H: Read + Seek> {
-    header_storage: HeaderStorage,
-    header_file: B,
-    binary_file: H,
-}
+    /// Sets a correction, which may be negative, added to rx_time of all headers (and thus to
+    /// all times given by the reader), for example to account for a known offset of the clock
+    /// that timestamped the samples. Zero by default.
+    fn set_time_offset(&mut self, offset: Timestamp) {
+        let storage = self.get_header_storage_mut();
+        let delta = offset - storage.time_offset;
+        for header in storage.store.values_mut() {
+            header.rx_time = header.rx_time + delta;
+        }
+        storage.time_offset = offset;
+    }
 
-impl<B: Read + Seek, H: Read + Seek> HeaderReader for DettachedHeader<B, H> {
-    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
-        &mut self.header_storage
+    fn get_time_gap_seek(&self) -> TimeGapSeek {
+        self.get_header_storage().time_gap_seek
     }
 
+    /// Sets what seeking to a time between segments does, erroring by default
+    fn set_time_gap_seek(&mut self, gap: TimeGapSeek) {
+        self.get_header_storage_mut().time_gap_seek = gap;
+    }
+
+    fn get_max_headers_to_scan(&self) -> u64 {
+        self.get_header_storage()
+            .max_headers_to_scan
+            .unwrap_or(DEFAULT_MAX_HEADERS_TO_SCAN)
+    }
+
+    /// Sets the most headers a single lookup of the header of a byte (which all reads and
+    /// seeks do) may load before erroring with ScanLimitExceeded, so corrupt files with huge
+    /// numbers of empty segments fail instead of hanging. DEFAULT_MAX_HEADERS_TO_SCAN by default.
+    fn set_max_headers_to_scan(&mut self, max: u64) {
+        self.get_header_storage_mut().max_headers_to_scan = Some(max);
+    }
+
+    fn get_max_cached_headers(&self) -> Option<usize> {
+        self.get_header_storage().max_cached_headers
+    }
+
+    /// Sets the most headers kept loaded, evicting those earliest in the file once exceeded, so
+    /// files with huge numbers of segments can be streamed through in bounded memory. Headers
+    /// from that of the first sample of the last read on are never evicted, so more may be kept
+    /// while loading ahead of it (for example, seeking to the end loads all headers). If nothing
+    /// has been read, the last headers loaded are kept.
+    ///
+    /// Evicted headers can't be loaded again, as the header file of dettached files may not be
+    /// seekable, so looking them up (for example, seeking back to their segments) errors with
+    /// EvictedHeader, as do methods needing every header (load_all_headers, stats, build_index,
+    /// ...) once any was evicted. All headers are kept if None, the default.
+    fn set_max_cached_headers(&mut self, max: Option<usize>) {
+        let storage = self.get_header_storage_mut();
+        storage.max_cached_headers = max;
+        storage.evict_over_max();
+    }
+
+    /// Evicts the loaded headers of segments ending at or before byte of the binary file, but
+    /// the last loaded one, see set_max_cached_headers.
+    fn clear_cache_before(&mut self, byte: u64) {
+        self.get_header_storage_mut()
+            .evict_while(|_, header| header.abs_pos + header.bytes <= byte);
+    }
+
+    /// Guesses how rx_time is to be interpreted: if the first header's seconds are a plausible
+    /// modern UNIX epoch (after 2001), it's AbsoluteUnix, otherwise Relative. The guess is not
+    /// applied, use set_rx_time_mode for that.
+    fn detect_rx_time_mode(&mut self) -> Result<RxTimeMode, MetaFileError> {
+        Ok(match self.get_header_for_byte(0)? {
+            Some(first) if first.rx_time.as_secs_f64() > 1e9 => RxTimeMode::AbsoluteUnix,
+            _ => RxTimeMode::Relative,
+        })
+    }
+
+    /// Time of sample at offset `sample` of header's segment, relative to the first sample of the
+    /// file regardless of the rx_time mode.
+    fn relative_sample_time(
+        &mut self,
+        header: &Header,
+        sample: i64,
+    ) -> Result<Timestamp, MetaFileError> {
+        let time = header.get_sample_time(sample);
+        Ok(match self.get_rx_time_mode() {
+            RxTimeMode::Relative => time,
+            RxTimeMode::AbsoluteUnix => match self.get_header_for_byte(0)? {
+                Some(first) => time - first.rx_time,
+                None => time,
+            },
+        })
+    }
+
+    /// UNIX time of sample at offset `sample` of header's segment, or None if rx_time is relative
+    /// and thus the absolute time is unknown.
+    fn absolute_sample_time(&self, header: &Header, sample: i64) -> Option<Timestamp> {
+        match self.get_rx_time_mode() {
+            RxTimeMode::Relative => None,
+            RxTimeMode::AbsoluteUnix => Some(header.get_sample_time(sample)),
+        }
+    }
+
+    /// Gets the header applicable to the sample with global index `sample`, counting from the
+    /// start of the file, or None if out of bounds. Segments may have differing item sizes.
+    fn get_header_for_sample(&mut self, sample: u64) -> Result<Option<Header>, MetaFileError> {
+        Ok(self.locate_sample(sample)?.map(|(header, _)| header))
+    }
+
+    /// Gets the position in the binary file of the first byte of the sample with global index
+    /// `sample`, counting from the start of the file, or None if out of bounds. Useful to hand
+    /// the location of samples to other tools.
+    fn byte_offset_of_sample(&mut self, sample: u64) -> Result<Option<u64>, MetaFileError> {
+        Ok(self
+            .locate_sample(sample)?
+            .map(|(header, offset)| header.abs_pos + offset * header.size as u64))
+    }
+
+    /// Gets the header applicable to the sample with global index `sample`, and the offset of
+    /// the sample within its segment, or None if out of bounds.
+    #[doc(hidden)]
+    fn locate_sample(&mut self, sample: u64) -> Result<Option<(Header, u64)>, MetaFileError> {
+        // Load headers up to the one containing sample, if not loaded yet
+        let max_headers = self.get_max_headers_to_scan();
+        let mut scanned = 0;
+        while self
+            .get_header_storage()
+            .store
+            .values()
+            .next_back()
+            .is_none_or(|h| h.sample_index_range().end <= sample)
+        {
+            if scanned == max_headers {
+                return Err(MetaFileError::ScanLimitExceeded(max_headers));
+            }
+            scanned += 1;
+            let first_byte = self.get_first_byte_of_next_header_to_read();
+            match self.load_next_header(first_byte)? {
+                Some(v) => self
+                    .get_header_storage_mut()
+                    .add_header_for_byte(first_byte, v)?,
+                None => return Ok(None),
+            }
+        }
+
+        // The first segment ending after sample contains it, as empty ones end where they start
+        let header = self
+            .get_header_storage()
+            .store
+            .values()
+            .find(|h| h.sample_index_range().end > sample)
+            .unwrap();
+        if sample < header.first_sample {
+            return Err(MetaFileError::EvictedHeader);
+        }
+        Ok(Some((header.clone(), sample - header.first_sample)))
+    }
+}
+
+/// Errors if samples can never be read into T, as its size is not that of its format (which may
+/// only happen for types outside of this crate implementing SampleType)
+fn check_sample_type<T: SampleType>() -> Result<(), MetaFileError> {
+    if std::mem::size_of::<T>() != T::byte_width() {
+        return Err(MetaFileError::UnsupportedSampleType(
+            std::any::type_name::<T>(),
+        ));
+    }
+    Ok(())
+}
+
+/// Reads exactly target.len() samples stored with the given endianness, returning the number of
+/// samples read
+fn read_raw<T: SampleType>(
+    reader: &mut impl Read,
+    target: &mut [T],
+    endianness: Endianness,
+) -> Result<u64, MetaFileError> {
+    // SAFETY: SampleType guarantees T is plain old data with no padding, so any bytes are valid
+    let bytes = unsafe {
+        std::slice::from_raw_parts_mut(
+            target.as_mut_ptr() as *mut u8,
+            std::mem::size_of_val(target),
+        )
+    };
+    reader.read_exact(bytes)?;
+    endianness.convert_in_place(bytes, T::DTYPE.bit_width() as usize / 8);
+    Ok(target.len() as u64)
+}
+
+/// Similar to Rust's Read + Seek, but obtaining individual samples instead of bytes,
+/// and with radio specific functionality (for example, you are guaranteed to never
+/// get streams with different sample rates, or with time jumps, if you use read())
+///
+/// For maximum performance, it's recommended to only ever read forward such that all
+/// disk access is sequential. This should yield maximum speed on most systems.
+pub trait SampleReadSeek {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader;
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek);
+
+    /// Gets the header that the last read sample belonged to, or None if no samples have been read
+    /// yet, or the reader is not positioned right after a sample.
+    fn get_last_read_header(&mut self) -> Result<Option<Header>, MetaFileError> {
+        let pos = self.get_sample_reader_mut().stream_position()?;
+        if pos == 0 {
+            return Ok(None);
+        }
+        let header = self.get_header_reader_mut().get_header_for_byte(pos - 1)?;
+        // In attached files, the byte may belong to the header itself
+        Ok(header.filter(|h| pos > h.abs_pos))
+    }
+
+    /// Gets the sample offset of the read position within the segment of the last read sample,
+    /// which is the number of samples of said segment read up to now. None if no samples have
+    /// been read yet.
+    fn get_last_read_offset_in_header(&mut self) -> Result<Option<u64>, MetaFileError> {
+        let header = match self.get_last_read_header()? {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let pos = self.get_sample_reader_mut().stream_position()?;
+        Ok(Some(header.get_sample_pos_of_byte(pos)))
+    }
+
+    /// Gets the global index of the next sample to read, counting from the start of the file,
+    /// or the number of samples of the file if at its end.
+    fn position_samples(&mut self) -> Result<u64, MetaFileError> {
+        let pos = self.get_sample_reader_mut().stream_position()?;
+        let header = match self.get_last_read_header()? {
+            Some(v) => v,
+            None => match self.get_header_reader_mut().get_header_for_byte(pos)? {
+                Some(v) => v,
+                None => return Ok(0), // Empty file
+            },
+        };
+        // In attached files, the position may be on top of the header
+        Ok(header.first_sample + header.get_sample_pos_of_byte(pos.max(header.abs_pos)))
+    }
+
+    /// Gets the number of samples left in the current segment, so reads may be sized to end
+    /// exactly at its end. The current segment is that of the last read sample, so 0 is
+    /// returned right at the boundary between segments (and at the end of the file).
+    fn samples_remaining_in_segment(&mut self) -> Result<u64, MetaFileError> {
+        let header = match self.get_last_read_header()? {
+            Some(v) => v,
+            None => {
+                let pos = self.get_sample_reader_mut().stream_position()?;
+                match self.get_header_reader_mut().get_header_for_byte(pos)? {
+                    Some(v) => v,
+                    None => return Ok(0),
+                }
+            }
+        };
+        let pos = self.get_sample_reader_mut().stream_position()?;
+        // In attached files, the position may be on top of the header
+        let offset = header.get_sample_pos_of_byte(pos.max(header.abs_pos));
+        Ok(header.get_num_samples() - offset)
+    }
+
+    /// Gets the timestamp of the next sample to read, or None if at the end of the file.
+    fn position_time(&mut self) -> Result<Option<Timestamp>, MetaFileError> {
+        let sample = self.position_samples()?;
+        Ok(self
+            .get_header_reader_mut()
+            .locate_sample(sample)?
+            .map(|(header, offset)| header.get_sample_time(offset as i64)))
+    }
+
+    /// Gets the timestamp of the last read sample, or None if no samples have been read yet.
+    fn get_last_read_rx_time(&mut self) -> Result<Option<Timestamp>, MetaFileError> {
+        let offset = match self.get_last_read_offset_in_header()? {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let header = self.get_last_read_header()?.unwrap();
+        Ok(Some(header.get_sample_time(offset as i64 - 1)))
+    }
+
+    /// Returns the header of the last read sample (if any) and the header of the next sample to
+    /// read, positioning the reader at said sample. Returns None on EOF.
+    #[doc(hidden)]
+    fn get_last_and_applicable_header(
+        &mut self,
+    ) -> Result<Option<(Option<Header>, Header)>, MetaFileError> {
+        let last_header = self.get_last_read_header()?;
+
+        let mut pos = self.get_sample_reader_mut().stream_position()?;
+        loop {
+            let appl_header = match self.get_header_reader_mut().get_header_for_byte(pos)? {
+                None => return Ok(None), // EOF or empty file
+                Some(v) => v,
+            };
+
+            if pos < appl_header.abs_pos {
+                // We are on top of an attached header, skip to its data
+                pos = appl_header.abs_pos;
+                self.get_sample_reader_mut().seek(SeekFrom::Start(pos))?;
+            }
+
+            // Empty segments (for example, emitted on a retune) have no samples, so the next
+            // sample to read is in the following segment
+            if appl_header.bytes > 0 {
+                return Ok(Some((last_header, appl_header)));
+            }
+        }
+    }
+
+    /// Fills buf from left to right, at most filling it completely. It will stop reading samples
+    /// if the next read would imply reading from:
+    /// - A segment with type not directly readable to T
+    /// - A segment with different sample rate from the one used in previous calls to read
+    /// - A segment with a jump in time with respect to the last sample of the previous segment
+    ///
+    /// Returns the number of samples actually read into buf.
+    /// This function will never perform conversion, so it's the fastest possible as it
+    /// will simply copy from the source file to the destination array.
+    ///
+    /// If an error is returned, the buffer may have been modified!
+    fn read_samples<T: SampleType>(&mut self, buf: &mut [T]) -> Result<u64, MetaFileError> {
+        self.read_samples_with(buf, |_| {})
+    }
+
+    /// Same as read_samples, but calling on_new_segment with the header of every segment the
+    /// reader enters while reading, before any of its samples are read. This includes the first
+    /// segment of the file, but not the segment of the current position if reading continues
+    /// within it.
+    fn read_samples_with<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+        on_new_segment: impl FnMut(&Header),
+    ) -> Result<u64, MetaFileError> {
+        Ok(self.read_run_with(buf, on_new_segment)?.0)
+    }
+
+    /// Iterates over blocks of block_size samples, read as read_samples does. The iterator ends
+    /// where reading stops, after yielding the samples before it as a shorter block.
+    fn iter_blocks<T: SampleType + Default>(&mut self, block_size: usize) -> Blocks<'_, Self, T>
+    where
+        Self: Sized,
+    {
+        assert!(block_size > 0, "blocks must contain samples");
+        Blocks {
+            reader: self,
+            block_size,
+            done: false,
+            _samples: std::marker::PhantomData,
+        }
+    }
+
+    /// Same as read_samples, but reading into a buffer which needs not be initialized, saving
+    /// the cost of zeroing it. The first returned number of samples of buf are initialized.
+    ///
+    /// # Safety
+    /// Samples are read directly into buf, so the underlying reader (see get_sample_reader_mut)
+    /// must not read from the buffers it's given, only write to them. std's File and Cursor,
+    /// for example, don't read from them.
+    unsafe fn read_samples_uninit<T: SampleType>(
+        &mut self,
+        buf: &mut [MaybeUninit<T>],
+    ) -> Result<u64, MetaFileError> {
+        // SAFETY: reading only writes whole samples to buf, as guaranteed by the caller
+        let buf = unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut T, buf.len()) };
+        self.read_samples(buf)
+    }
+
+    /// Same as read_samples_uninit, returning the samples read
+    ///
+    /// # Safety
+    /// Same as read_samples_uninit
+    unsafe fn read_samples_uninit_slice<'a, T: SampleType>(
+        &mut self,
+        buf: &'a mut [MaybeUninit<T>],
+    ) -> Result<&'a [T], MetaFileError> {
+        // SAFETY: forwarded from the caller
+        let num_read = unsafe { self.read_samples_uninit(buf)? } as usize;
+        // SAFETY: the first num_read samples were written by the read
+        Ok(unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const T, num_read) })
+    }
+
+    /// Same as read_samples, but also returning the header of the first sample read (None if
+    /// nothing was read), which saves looking it up again after reading.
+    fn read_samples_meta<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+    ) -> Result<(u64, Option<Header>), MetaFileError> {
+        let (num_read, _, header) = self.read_run_with(buf, |_| {})?;
+        Ok((num_read, header))
+    }
+
+    /// Same as read_samples_meta, but starting a new run if read_samples would read nothing:
+    /// segments not directly readable into T are skipped, and the next one is read even if it
+    /// doesn't continue the last sample read (after a time gap, or a change of sample rate).
+    /// Reading then stops as read_samples does, so nothing is read only at the end of the file.
+    fn read_samples_resume<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+    ) -> Result<(u64, Option<Header>), MetaFileError> {
+        loop {
+            let (num_read, header) = self.read_samples_meta(buf)?;
+            if num_read > 0 || buf.is_empty() {
+                return Ok((num_read, header));
+            }
+
+            let (_, header) = match self.get_last_and_applicable_header()? {
+                Some(v) => v,
+                None => return Ok((0, None)),
+            };
+            if !T::reads_directly_from(header.dtype, header.cplx) {
+                self.get_sample_reader_mut()
+                    .seek(SeekFrom::Start(header.abs_pos + header.bytes))?;
+                continue;
+            }
+
+            // The position can't tell apart the new run from the segment before it, so its first
+            // sample is read directly
+            let start = self.get_sample_reader_mut().stream_position()?;
+            let endianness = self.get_header_reader_mut().get_endianness();
+            read_raw(self.get_sample_reader_mut(), &mut buf[..1], endianness)?;
+            let (num_read, _, _) = self.read_run_with(&mut buf[1..], |_| {})?;
+            self.set_last_read_start(Some(start));
+            return Ok((num_read + 1, Some(header)));
+        }
+    }
+
+    /// Same as read_samples, but also returning why reading stopped. Note that BufferFull is
+    /// returned if buf was filled, even if the next read would stop for other reasons.
+    fn read_run<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+    ) -> Result<(u64, StopReason), MetaFileError> {
+        let (num_read, reason, _) = self.read_run_with(buf, |_| {})?;
+        Ok((num_read, reason))
+    }
+
+    /// Reads as read_run, also returning the header of the first sample read
+    #[doc(hidden)]
+    fn read_run_with<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+        mut on_new_segment: impl FnMut(&Header),
+    ) -> Result<(u64, StopReason, Option<Header>), MetaFileError> {
+        check_sample_type::<T>()?;
+        let mut num_read: u64 = 0;
+        let mut first_header = None;
+        self.set_last_read_start(None);
+        let mut segment = self.get_last_read_header()?.map(|h| h.abs_pos);
+
+        while num_read < buf.len() as u64 {
+            let readable = |h: &Header| T::reads_directly_from(h.dtype, h.cplx);
+            let appl_header = match self.next_readable_header(readable, SeekPreserve::All)? {
+                Ok(v) => v,
+                Err(reason) => return Ok((num_read, reason, first_header)),
+            };
+            if num_read == 0 {
+                let start = self.get_sample_reader_mut().stream_position()?;
+                self.set_last_read_start(Some(start));
+                first_header = Some(appl_header.clone());
+            }
+            if segment != Some(appl_header.abs_pos) {
+                segment = Some(appl_header.abs_pos);
+                on_new_segment(&appl_header);
+            }
+
+            // Samples not read yet, stored contiguously from the current position
+            let mut pending = self.samples_to_read(&appl_header, buf.len() as u64 - num_read)?;
+            let endianness = self.get_header_reader_mut().get_endianness();
+
+            // Fast path for files with many small segments: the segments that follow in the file
+            // and can be read along with the current one are read without looking up the
+            // position again, in a single read if their data is contiguous (dettached files)
+            let mut header = appl_header;
+            loop {
+                let remaining = buf.len() as u64 - num_read - pending;
+                if remaining == 0 {
+                    break;
+                }
+                // The current segment is read up to its end, where the next one starts
+                let end = header.abs_pos + header.bytes;
+                let next = match self.get_header_reader_mut().get_header_for_byte(end)? {
+                    Some(v) => v,
+                    None => break,
+                };
+                if next.bytes == 0
+                    || !readable(&next)
+                    || !next.is_compatible_with(&header, SeekPreserve::All)
+                    || !next.is_continuation_of(&header)
+                {
+                    // Left for the general path, which also knows why reading stops
+                    break;
+                }
+
+                if next.abs_pos != end {
+                    let start = num_read as usize;
+                    let reader = self.get_sample_reader_mut();
+                    num_read += read_raw(
+                        reader,
+                        &mut buf[start..start + pending as usize],
+                        endianness,
+                    )?;
+                    pending = 0;
+                    // Skip the inline header
+                    reader.seek(SeekFrom::Start(next.abs_pos))?;
+                }
+                segment = Some(next.abs_pos);
+                on_new_segment(&next);
+                pending += next.get_num_samples().min(remaining);
+                header = next;
+            }
+
+            let start = num_read as usize;
+            num_read += read_raw(
+                self.get_sample_reader_mut(),
+                &mut buf[start..start + pending as usize],
+                endianness,
+            )?;
+        }
+
+        Ok((num_read, StopReason::BufferFull, first_header))
+    }
+
+    /// Advances the position by up to n samples, stopping as read_samples does (except that any
+    /// sample type is accepted), but seeking instead of reading the samples. Returns the number
+    /// of samples actually skipped.
+    fn skip_samples(&mut self, n: u64) -> Result<u64, MetaFileError> {
+        let mut skipped = 0;
+        while skipped < n {
+            let appl_header = match self.next_readable_header(|_| true, SeekPreserve::All)? {
+                Ok(v) => v,
+                Err(_) => break,
+            };
+
+            let to_skip = self.samples_to_read(&appl_header, n - skipped)?;
+            let bytes = to_skip * appl_header.size as u64;
+            self.get_sample_reader_mut()
+                .seek(SeekFrom::Current(bytes as i64))?;
+            skipped += to_skip;
+        }
+        Ok(skipped)
+    }
+
+    /// Fills buf with every factor-th sample, starting at the current position, seeking over the
+    /// samples in between instead of reading them. Stops as read_samples does, and also once the
+    /// next sample to take is past the end of the current segment, leaving the position at its
+    /// end (so decimation starts over at the first sample of the next segment).
+    /// Returns the number of samples read into buf.
+    fn read_decimated<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+        factor: u64,
+    ) -> Result<u64, MetaFileError> {
+        if factor == 0 {
+            return Err(MetaFileError::InvalidArgument(
+                "decimation factor must not be zero",
+            ));
+        }
+
+        let mut num_read = 0;
+        while num_read < buf.len() {
+            if self.read_run(&mut buf[num_read..num_read + 1])?.0 == 0 {
+                break;
+            }
+            num_read += 1;
+
+            let remaining = self.samples_remaining_in_segment()?;
+            let skip = (factor - 1).min(remaining);
+            let bytes = skip * std::mem::size_of::<T>() as u64;
+            self.get_sample_reader_mut()
+                .seek(SeekFrom::Current(bytes as i64))?;
+            if skip == remaining {
+                break;
+            }
+        }
+        Ok(num_read as u64)
+    }
+
+    /// Same as read_samples, but instead of stopping on a time discontinuity, fills the gap with
+    /// as many zero samples as would have been received during it, and continues reading the next
+    /// segment. If buf fills up in the middle of a gap, the next call continues filling it.
+    /// Still stops on overlapping segments, as these can't be filled.
+    fn read_gap_filled<T: SampleType + Zero>(
+        &mut self,
+        buf: &mut [T],
+    ) -> Result<u64, MetaFileError> {
+        check_sample_type::<T>()?;
+        let mut num_read: u64 = 0;
+        self.set_last_read_start(None);
+
+        while num_read < buf.len() as u64 {
+            let (last_header, appl_header) = match self.get_last_and_applicable_header()? {
+                Some(v) => v,
+                None => break,
+            };
+            let storage = self.get_header_reader_mut().get_header_storage_mut();
+
+            if let Some(last_header) = last_header
+                && appl_header != last_header
+            {
+                if !appl_header.is_compatible_with(&last_header, SeekPreserve::All) {
+                    break;
+                }
+
+                if !appl_header.is_continuation_of(&last_header) {
+                    let zeros = match storage.gap_fill {
+                        Some((pos, zeros)) if pos == appl_header.abs_pos => zeros,
+                        _ => {
+                            let gap = appl_header.gap_to(&last_header).as_secs_f64();
+                            let zeros = (gap * last_header.samp_rate).round();
+                            if zeros < 1.0 {
+                                break; // Overlapping segments
+                            }
+                            zeros as u64
+                        }
+                    };
+
+                    let fill = zeros.min(buf.len() as u64 - num_read);
+                    let start = num_read as usize;
+                    buf[start..start + fill as usize].fill(T::zero());
+                    num_read += fill;
+                    // Once filled, the gap is kept so reading may continue into the segment
+                    storage.gap_fill = Some((appl_header.abs_pos, zeros - fill));
+                    if zeros > fill {
+                        // Stay before the inline header, so the next call still sees the gap
+                        if appl_header.attached {
+                            self.get_sample_reader_mut()
+                                .seek(SeekFrom::Start(appl_header.pos_in_file))?;
+                        }
+                        break;
+                    }
+                }
+            }
+
+            if !T::reads_directly_from(appl_header.dtype, appl_header.cplx) {
+                break;
+            }
+            if num_read == buf.len() as u64 {
+                break;
+            }
+
+            let to_read = self.samples_to_read(&appl_header, buf.len() as u64 - num_read)?;
+            let start = num_read as usize;
+            let end = start + to_read as usize;
+            if self
+                .get_header_reader_mut()
+                .get_header_storage()
+                .last_read_start
+                .is_none()
+            {
+                let pos = self.get_sample_reader_mut().stream_position()?;
+                self.set_last_read_start(Some(pos));
+            }
+            let endianness = self.get_header_reader_mut().get_endianness();
+            num_read += read_raw(
+                self.get_sample_reader_mut(),
+                &mut buf[start..end],
+                endianness,
+            )?;
+            self.get_header_reader_mut()
+                .get_header_storage_mut()
+                .gap_fill = None;
+        }
+
+        Ok(num_read)
+    }
+
+    /// Reads `gr_complex` samples, the most common recording format, as read_samples does.
+    /// Unlike read_samples, errors with MetaFileError::WrongSampleType if the next segment to
+    /// read is not complex float.
+    fn read_iq(&mut self, buf: &mut [Complex<f32>]) -> Result<u64, MetaFileError> {
+        if let Some((_, header)) = self.get_last_and_applicable_header()?
+            && !(header.dtype == DataType::Float && header.cplx)
+        {
+            return Err(MetaFileError::WrongSampleType {
+                expected: DataType::Float.type_string(true),
+                found: header.dtype.type_string(header.cplx),
+            });
+        }
+        self.read_samples(buf)
+    }
+
+    /// Positions the reader at the next sample to read and returns its header, or the reason why
+    /// reading must stop before it: on EOF, if its segment is not compatible with (as given by
+    /// preserve) or a continuation of the segment of the last read sample, or if `readable`
+    /// returns false for its segment.
+    #[doc(hidden)]
+    fn next_readable_header(
+        &mut self,
+        readable: impl Fn(&Header) -> bool,
+        preserve: SeekPreserve,
+    ) -> Result<Result<Header, StopReason>, MetaFileError> {
+        let (last_header, appl_header) = match self.get_last_and_applicable_header()? {
+            Some(v) => v,
+            None => return Ok(Err(StopReason::Eof)), // EOF or empty file
+        };
+
+        if let Some(last_header) = last_header
+            && appl_header != last_header
+        {
+            if !appl_header.is_compatible_with(&last_header, preserve) {
+                // Something is different about the new header, stop reading
+                return Ok(Err(
+                    if preserve.preserves_samplerate() && !appl_header.same_samp_rate(&last_header)
+                    {
+                        StopReason::SampleRateChange
+                    } else {
+                        StopReason::FormatChange
+                    },
+                ));
+            }
+
+            if !appl_header.is_continuation_of(&last_header) {
+                // The segment had a time discontinuity, stop reading
+                return Ok(Err(StopReason::TimeDiscontinuity));
+            }
+        }
+
+        if !readable(&appl_header) {
+            return Ok(Err(StopReason::NotDirectlyReadable));
+        }
+
+        Ok(Ok(appl_header))
+    }
+
+    /// Number of samples that can be read from the current position within header's segment,
+    /// limited to at most max
+    #[doc(hidden)]
+    fn samples_to_read(&mut self, header: &Header, max: u64) -> Result<u64, MetaFileError> {
+        let cur_sample =
+            header.get_sample_pos_of_byte(self.get_sample_reader_mut().stream_position()?);
+        let samps_remain = header.get_num_samples().saturating_sub(cur_sample);
+        Ok(max.min(samps_remain))
+    }
+
+    /// Reads raw sample bytes into buf, never reading past the end of the current segment, and
+    /// skipping the inline headers of attached files. Returns the number of bytes read and
+    /// whether the end of the segment was reached, such that the next read starts a new segment.
+    /// Types, sample rates and timestamps are not checked. Returns (0, false) on EOF.
+    fn read_bytes_until_boundary(
+        &mut self,
+        buf: &mut [u8],
+    ) -> Result<(usize, bool), MetaFileError> {
+        let (_, header) = match self.get_last_and_applicable_header()? {
+            Some(v) => v,
+            None => return Ok((0, false)),
+        };
+
+        let reader = self.get_sample_reader_mut();
+        let pos = reader.stream_position()?;
+        let remain = header.abs_pos + header.bytes - pos;
+        let to_read = remain.min(buf.len() as u64) as usize;
+        reader.read_exact(&mut buf[..to_read])?;
+        Ok((to_read, to_read as u64 == remain))
+    }
+
+    /// Copies the sample data of every segment in the file to out, skipping the inline headers
+    /// of attached files, which is useful to feed tools that expect headerless data.
+    /// Returns the number of bytes copied. The position of the reader is left unmodified.
+    fn copy_payload<W: Write>(&mut self, out: &mut W) -> Result<u64, MetaFileError> {
+        let old_pos = self.get_sample_reader_mut().stream_position()?;
+        let mut copied = 0;
+        for header in self.get_header_reader_mut().load_all_headers()? {
+            let reader = self.get_sample_reader_mut();
+            reader.seek(SeekFrom::Start(header.abs_pos))?;
+            let num = std::io::copy(&mut reader.take(header.bytes), out)?;
+            if num != header.bytes {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+            copied += num;
+        }
+        self.get_sample_reader_mut()
+            .seek(SeekFrom::Start(old_pos))?;
+        Ok(copied)
+    }
+
+    /// Reads the samples of every segment of the file into a single buffer, removing any time
+    /// gaps between them, and returns where each (non empty) segment landed in it. The position
+    /// of the reader is left unmodified.
+    ///
+    /// All segments must be directly readable into T (use read_conv otherwise) and have the same
+    /// sample rate, as the timeline has a single time base.
+    fn flatten_timeline<T: SampleType + Default>(
+        &mut self,
+    ) -> Result<(Vec<T>, Vec<SegmentMap>), MetaFileError> {
+        let headers: Vec<Header> = self
+            .get_header_reader_mut()
+            .load_all_headers()?
+            .into_iter()
+            .filter(|h| h.bytes > 0)
+            .collect();
+        for header in &headers {
+            if !T::reads_directly_from(header.dtype, header.cplx) {
+                return Err(MetaFileError::WrongSampleType {
+                    expected: T::DTYPE.type_string(T::COMPLEX),
+                    found: header.dtype.type_string(header.cplx),
+                });
+            }
+            if !header.same_samp_rate(&headers[0]) {
+                return Err(MetaFileError::InvalidArgument(
+                    "segments of a timeline must have the same sample rate",
+                ));
+            }
+        }
+
+        let total: u64 = headers.iter().map(Header::get_num_samples).sum();
+        let mut out = vec![T::default(); total as usize];
+        let mut map = Vec::with_capacity(headers.len());
+        let old_pos = self.get_sample_reader_mut().stream_position()?;
+        let endianness = self.get_header_reader_mut().get_endianness();
+        let mut first_sample = 0;
+        for header in &headers {
+            let num_samples = header.get_num_samples();
+            let reader = self.get_sample_reader_mut();
+            reader.seek(SeekFrom::Start(header.abs_pos))?;
+            let range = first_sample as usize..(first_sample + num_samples) as usize;
+            read_raw(reader, &mut out[range], endianness)?;
+
+            // Empty segments hold no samples, so indices in the file and buffer are the same
+            map.push(SegmentMap {
+                source_first_sample: first_sample,
+                first_sample,
+                num_samples,
+                rx_time: header.rx_time,
+                timeline_time: headers[0].get_sample_time(first_sample as i64),
+            });
+            first_sample += num_samples;
+        }
+        self.get_sample_reader_mut()
+            .seek(SeekFrom::Start(old_pos))?;
+
+        Ok((out, map))
+    }
+
+    /// Computes the statistics of the samples of every segment of the file directly readable
+    /// into T (others are skipped), streaming them in blocks so the file needs not fit in
+    /// memory. The position of the reader is left unmodified.
+    ///
+    /// Mean and variance are accumulated with Welford's method, so precision is kept even for
+    /// long recordings with a large DC offset.
+    fn stats<T: FloatSample + Default>(&mut self) -> Result<SampleStats, MetaFileError> {
+        check_sample_type::<T>()?;
+        const BLOCK_LEN: u64 = 4096;
+
+        let mut count = 0u64;
+        let (mut min, mut max) = (f64::INFINITY, f64::NEG_INFINITY);
+        // Running mean, and sum of squared differences from it
+        let (mut mean, mut m2) = (0.0, 0.0);
+        let mut block = vec![T::default(); BLOCK_LEN as usize];
+
+        let old_pos = self.get_sample_reader_mut().stream_position()?;
+        let endianness = self.get_header_reader_mut().get_endianness();
+        for header in self.get_header_reader_mut().load_all_headers()? {
+            if !T::reads_directly_from(header.dtype, header.cplx) {
+                continue;
+            }
+            let reader = self.get_sample_reader_mut();
+            reader.seek(SeekFrom::Start(header.abs_pos))?;
+            let mut remaining = header.get_num_samples();
+            while remaining > 0 {
+                let len = remaining.min(BLOCK_LEN) as usize;
+                read_raw(reader, &mut block[..len], endianness)?;
+                remaining -= len as u64;
+                for sample in &block[..len] {
+                    let x = sample.stat_value();
+                    count += 1;
+                    min = min.min(x);
+                    max = max.max(x);
+                    let delta = x - mean;
+                    mean += delta / count as f64;
+                    m2 += delta * (x - mean);
+                }
+            }
+        }
+        self.get_sample_reader_mut()
+            .seek(SeekFrom::Start(old_pos))?;
+
+        if count == 0 {
+            return Ok(SampleStats {
+                count,
+                min: f64::NAN,
+                max: f64::NAN,
+                mean: f64::NAN,
+                rms: f64::NAN,
+            });
+        }
+        // The mean square is the (population) variance plus the squared mean
+        let rms = (m2 / count as f64 + mean * mean).sqrt();
+        Ok(SampleStats {
+            count,
+            min,
+            max,
+            mean,
+            rms,
+        })
+    }
+
+    /// Reads a block of samples of `channels` interleaved channels (as written by GNU Radio
+    /// when multiple streams are muxed into a single file) and scatters them round-robin into
+    /// `out`, which must contain one buffer per channel, all of the same length.
+    ///
+    /// GNU Radio doesn't store the number of channels, so it must be supplied externally.
+    /// Follows the same stopping rules as read_samples, but never reads a partial set of
+    /// channels. Returns the number of samples written to each channel's buffer.
+    fn read_deinterleaved<T: SampleType + Default>(
+        &mut self,
+        channels: usize,
+        out: &mut [Vec<T>],
+    ) -> Result<u64, MetaFileError> {
+        if channels == 0 || out.len() != channels {
+            return Err(MetaFileError::InvalidArgument(
+                "out must contain exactly one buffer per channel",
+            ));
+        }
+        let per_channel = out[0].len();
+        if out.iter().any(|v| v.len() != per_channel) {
+            return Err(MetaFileError::InvalidArgument(
+                "all channel buffers must be of the same length",
+            ));
+        }
+
+        let mut block = vec![T::default(); per_channel * channels];
+        let num_read = self.read_samples(&mut block)? as usize;
+
+        // Leave the partially read set of channels for the next read. Samples read are
+        // consecutive, but may span segments, so this must seek by sample and not by byte.
+        let partial = num_read % channels;
+        if partial != 0 {
+            let end = self.position_samples()?;
+            self.seek(SeekFrom::Start(end - partial as u64), SeekPreserve::None)?;
+        }
+
+        let frames = num_read / channels;
+        for (i, sample) in block[..frames * channels].iter().enumerate() {
+            out[i % channels][i / channels] = *sample;
+        }
+
+        Ok(frames as u64)
+    }
+
+    /// Fills buf from left to right, at most filling it completely. It will stop reading samples
+    /// if the next read would imply reading from:
+    /// - A segment with type inconvertible to T
+    /// - A segment with different sample rate from the one used in previous calls to read
+    /// - A segment with a jump in time with respect to the last sample of the previous segment
+    ///
+    /// Returns the number of samples actually read into buf.
+    /// This function may convert if neccesary, and is thus expected to be slightly slower
+    /// than read.
+    fn read_conv<T: SampleType>(&mut self, buf: &mut [T]) -> Result<u64, MetaFileError> {
+        Ok(self.read_conv_checked(buf)?.0)
+    }
+
+    /// Same as read_conv, but also returns how many values lost precision in the conversion,
+    /// so you may decide whether to trust the results. Only conversions to floating point
+    /// types may be lossy (f64 -> f32, or i32 -> f32), as integers are only ever up-casted.
+    fn read_conv_checked<T: SampleType>(
+        &mut self,
+        buf: &mut [T],
+    ) -> Result<(u64, ConversionStats), MetaFileError> {
+        check_sample_type::<T>()?;
+        let mut num_read: u64 = 0;
+        let mut stats = ConversionStats::default();
+        self.set_last_read_start(None);
+
+        while num_read < buf.len() as u64 {
+            let readable =
+                |h: &Header| h.cplx == T::COMPLEX && h.dtype.converts_to_dtype(&T::DTYPE);
+            let appl_header = match self.next_readable_header(readable, SeekPreserve::SampleRate)? {
+                Ok(v) => v,
+                Err(_) => break,
+            };
+            if num_read == 0 {
+                let start = self.get_sample_reader_mut().stream_position()?;
+                self.set_last_read_start(Some(start));
+            }
+
+            let to_read = self.samples_to_read(&appl_header, buf.len() as u64 - num_read)?;
+            let item_size = appl_header.size as usize;
+            let mut bytes = vec![0; to_read as usize * item_size];
+            self.get_sample_reader_mut().read_exact(&mut bytes)?;
+            let endianness = self.get_header_reader_mut().get_endianness();
+            endianness.convert_in_place(&mut bytes, appl_header.dtype.bit_width() as usize / 8);
+
+            let scaling = self.get_header_reader_mut().get_conversion_scaling();
+            let start = num_read as usize;
+            for (target, src) in buf[start..].iter_mut().zip(bytes.chunks_exact(item_size)) {
+                *target = appl_header
+                    .dtype
+                    .read_from_bytes_scaled(src, scaling, &mut stats);
+            }
+
+            num_read += to_read;
+        }
+
+        Ok((num_read, stats))
+    }
+
+    /// Returns metadata that applies to all samples read in the previous call to read, or None
+    /// if it read no samples.
+    fn get_last_read_meta(&mut self) -> Result<Option<SampleMeta>, MetaFileError> {
+        let byte = match self
+            .get_header_reader_mut()
+            .get_header_storage()
+            .last_read_start
+        {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let header = match self.get_header_reader_mut().get_header_for_byte(byte)? {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+
+        let extra = &header.extra_dict;
+        Ok(Some(SampleMeta {
+            samp_rate: header.samp_rate,
+            rx_time: header.get_sample_time(header.get_sample_pos_of_byte(byte) as i64),
+            center_freq: extra.get("rx_freq").and_then(Tag::get_f64),
+            bandwidth: extra.get("bandwidth").and_then(Tag::get_f64),
+        }))
+    }
+
+    /// Returns the stream tags applying to the samples read in the previous call to read, in
+    /// order of segment, see HeaderReader::set_offset_tag_keys.
+    fn get_last_read_tags(&mut self) -> Result<Vec<StreamTag>, MetaFileError> {
+        let byte = match self
+            .get_header_reader_mut()
+            .get_header_storage()
+            .last_read_start
+        {
+            None => return Ok(Vec::new()),
+            Some(v) => v,
+        };
+        let start = match self.get_header_reader_mut().get_header_for_byte(byte)? {
+            None => return Ok(Vec::new()),
+            Some(header) => header.first_sample + header.get_sample_pos_of_byte(byte),
+        };
+        let end = self.position_samples()?;
+
+        // Headers of the samples read are never evicted, and were loaded to read them
+        let storage = self.get_header_reader_mut().get_header_storage();
+        let mut tags = Vec::new();
+        for header in storage.store.values() {
+            if header.first_sample >= end {
+                break;
+            }
+            tags.extend(
+                stream_tags(header, header.first_sample, &storage.offset_tag_keys)
+                    .into_iter()
+                    .filter(|tag| (start..end).contains(&tag.offset)),
+            );
+        }
+        Ok(tags)
+    }
+
+    /// Remembers the byte of the first sample read by the current read call, or None if it
+    /// hasn't read anything (yet)
+    #[doc(hidden)]
+    fn set_last_read_start(&mut self, start: Option<u64>) {
+        self.get_header_reader_mut()
+            .get_header_storage_mut()
+            .last_read_start = start;
+    }
+
+    /// Seeks within the file, preserving certain qualities of the current segment as
+    /// given in preserve. Returns the current position in samples from the start of the file, or
+    /// errors if the seek could not be performed, leaving the position unmodified.
+    fn seek(&mut self, pos: SeekFrom, preserve: SeekPreserve) -> Result<u64, MetaFileError> {
+        let byte = self.get_sample_reader_mut().stream_position()?;
+        let current = self.get_seek_origin_header()?;
+
+        let headers = self.get_header_reader_mut();
+        let cur_sample = current.first_sample + current.get_sample_pos_of_byte(byte);
+
+        // Only seeking from the end needs every header loaded
+        let target = match pos {
+            SeekFrom::Start(n) => n as i128,
+            SeekFrom::Current(d) => cur_sample as i128 + d as i128,
+            SeekFrom::End(d) => headers.get_total_samples()? as i128 + d as i128,
+        };
+        let target = u64::try_from(target)
+            .map_err(|_| MetaFileError::InvalidArgument("seek out of bounds"))?;
+
+        // Past the last sample there is only the end of the file, right after it
+        let (header, offset) = match headers.locate_sample(target)? {
+            Some(v) => v,
+            None => match target.checked_sub(1) {
+                Some(last) => match headers.locate_sample(last)? {
+                    Some((h, offset)) => (h, offset + 1),
+                    None => return Err(MetaFileError::InvalidArgument("seek out of bounds")),
+                },
+                None => return Err(MetaFileError::InvalidArgument("seek out of bounds")),
+            },
+        };
+
+        let same_segment = header.abs_pos == current.abs_pos;
+        if preserve == SeekPreserve::Segment && !same_segment {
+            return Err(MetaFileError::IncompatibleSeek);
+        }
+        if !header.is_compatible_with(&current, preserve) {
+            return Err(MetaFileError::IncompatibleSeek);
+        }
+
+        let byte = header.abs_pos + offset * header.size as u64;
+        self.get_sample_reader_mut().seek(SeekFrom::Start(byte))?;
+        Ok(target)
+    }
+
+    /// Seeks to sample_offset samples from the start of the current segment, which may be
+    /// negative or past its end if preserve allows leaving the segment (SeekPreserve::Segment
+    /// doesn't). Returns the current position in samples from the start of the file, or errors
+    /// if the seek could not be performed, leaving the position unmodified.
+    fn seek_within_segment(
+        &mut self,
+        sample_offset: i64,
+        preserve: SeekPreserve,
+    ) -> Result<u64, MetaFileError> {
+        let current = self.get_seek_origin_header()?;
+        let target = current.first_sample as i128 + sample_offset as i128;
+        if target < 0 {
+            return Err(MetaFileError::InvalidArgument("seek out of bounds"));
+        }
+        self.seek(SeekFrom::Start(target as u64), preserve)
+    }
+
+    /// Header of the segment seeks are relative to: that of the current position, or of the
+    /// last read sample at the end of the file
+    #[doc(hidden)]
+    fn get_seek_origin_header(&mut self) -> Result<Header, MetaFileError> {
+        let byte = self.get_sample_reader_mut().stream_position()?;
+        match self.get_header_reader_mut().get_header_for_byte(byte)? {
+            Some(v) => Ok(v),
+            None => match self.get_last_read_header()? {
+                Some(v) => Ok(v),
+                None => Err(MetaFileError::InvalidArgument("no segment to seek from")),
+            },
+        }
+    }
+
+    /// Seeks to the sample containing time t, in the same time base as the rx_time of headers,
+    /// preserving qualities of the current segment as in seek. If t is between two segments,
+    /// the behaviour is given by HeaderReader::set_time_gap_seek.
+    /// Returns the current position in samples from the start of the file.
+    fn seek_to_time(&mut self, t: Timestamp, preserve: SeekPreserve) -> Result<u64, MetaFileError> {
+        let headers = self.get_header_reader_mut();
+        let gap = headers.get_time_gap_seek();
+        headers.load_remaining_headers()?;
+
+        let storage = headers.get_header_storage();
+        let mut target = None;
+        let mut first = true;
+        for header in storage.store.values() {
+            let num_samples = header.get_num_samples();
+            if num_samples == 0 {
+                continue;
+            }
+
+            if t < header.rx_time {
+                // t is before this segment, so it's in a gap, or in an evicted segment
+                if first && storage.evicted > 0 {
+                    return Err(MetaFileError::EvictedHeader);
+                }
+                if gap == TimeGapSeek::NextSegment {
+                    target = Some(header.first_sample);
+                }
+                break;
+            }
+
+            if t < header.get_sample_time(num_samples as i64) {
+                // Small tolerance so that floating point errors don't land on the previous sample
+                let offset = (t - header.rx_time).as_secs_f64() * header.samp_rate + 1e-6;
+                let offset = (offset.floor() as u64).min(num_samples - 1);
+                target = Some(header.first_sample + offset);
+                break;
+            }
+            first = false;
+        }
+
+        match target {
+            Some(sample) => self.seek(SeekFrom::Start(sample), preserve),
+            None => Err(MetaFileError::TimeOutOfRange),
+        }
+    }
+
+    /// Seeks to the sample whose time is closest to t, in the same time base as the rx_time of
+    /// headers. Unlike seek_to_time, t may fall between samples or in a gap, but errors with
+    /// TimeOutOfRange (leaving the position unmodified) if the closest sample is more than
+    /// max_gap away from t, so that a target deep in a gap doesn't land on an unrelated segment.
+    /// Returns the current position in samples from the start of the file.
+    fn seek_nearest_time(
+        &mut self,
+        t: Timestamp,
+        max_gap: Timestamp,
+    ) -> Result<u64, MetaFileError> {
+        // Global index of the closest sample so far, and its distance to t
+        let mut nearest: Option<(u64, Timestamp)> = None;
+        let headers = self.get_header_reader_mut();
+        headers.load_remaining_headers()?;
+        let storage = headers.get_header_storage();
+        for header in storage.store.values() {
+            let num_samples = header.get_num_samples();
+            if num_samples == 0 {
+                continue;
+            }
+            // The closest sample may be in an evicted segment
+            if nearest.is_none() && storage.evicted > 0 && t < header.rx_time {
+                return Err(MetaFileError::EvictedHeader);
+            }
+
+            let offset = ((t - header.rx_time).as_secs_f64() * header.samp_rate).round();
+            let offset = (offset.max(0.0) as u64).min(num_samples - 1);
+            let distance = t.abs_diff(header.get_sample_time(offset as i64));
+            if nearest.is_none_or(|(_, best)| distance < best) {
+                nearest = Some((header.first_sample + offset, distance));
+            }
+        }
+
+        match nearest {
+            Some((sample, distance)) if distance <= max_gap => {
+                self.seek(SeekFrom::Start(sample), SeekPreserve::None)
+            }
+            _ => Err(MetaFileError::TimeOutOfRange),
+        }
+    }
+
+    /// Same as seek, but moving to segment start samples, and pos given in segments.
+    /// Returns the current position in samples from the start of the file, or errors if the
+    /// seek could not be performed, leaving the position unmodified.
+    fn seek_segment(
+        &mut self,
+        _pos_seg: SeekFrom,
+        _preserve: SeekPreserve,
+    ) -> Result<u64, MetaFileError> {
+        todo!("Implement");
+    }
+
+    /// Seeks the next segment which has a format that can be converted to `T`, returning the
+    /// number of segments skipped, erroring if no such segment can be found.
+    fn seek_valid_segment(&mut self) -> Result<u64, MetaFileError> {
+        todo!("Implement");
+    }
+}
+
+pub struct AttachedHeader<T: Read + Seek> {
+    header_storage: HeaderStorage,
+    file: T,
+}
+
+impl<T: Read + Seek> AttachedHeader<T> {
+    pub fn new(file: T) -> AttachedHeader<T> {
+        AttachedHeader {
+            header_storage: Default::default(),
+            file,
+        }
+    }
+
+    /// Reader of a file whose headers are all taken from index (which must have been built from
+    /// the same file), instead of being scanned as needed. The index is trusted, but may be
+    /// checked with HeaderStorage::validate. Fails if index is of a dettached file.
+    pub fn with_index(file: T, index: &FileIndex) -> Result<AttachedHeader<T>, MetaFileError> {
+        if !index.attached {
+            return Err(MetaFileError::InvalidIndex("attached"));
+        }
+        Ok(AttachedHeader {
+            header_storage: HeaderStorage::from_index(index),
+            file,
+        })
+    }
+
+    /// Returns the underlying file, positioned wherever the reader left it
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+}
+
+impl<D: AsRef<[u8]>> AttachedHeader<Cursor<D>> {
+    /// Reader of a file already in memory, for example as a Vec<u8> or &[u8]
+    pub fn from_bytes(data: D) -> AttachedHeader<Cursor<D>> {
+        AttachedHeader::new(Cursor::new(data))
+    }
+}
+
+impl<T: Read + Seek> HeaderReader for AttachedHeader<T> {
+    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
+        &mut self.header_storage
+    }
+    fn get_header_storage(&self) -> &HeaderStorage {
+        &self.header_storage
+    }
+
+    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
+        let old_pos = self.file.stream_position()?;
+        self.file.seek(SeekFrom::Start(start_byte))?;
+
+        let header_tag = match parse_maybe_eof(&mut self.file) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                self.file.seek(SeekFrom::Start(old_pos))?;
+                return Ok(None);
+            }
+            Err(e) => return Err(MetaFileError::ParseError(e)),
+        };
+        // The data is found through strt, so there's no need to move past the extra dict
+        let extra = if self.header_storage.skip_extra_dict {
+            Tag::Null()
+        } else {
+            parse(&mut self.file)?
+        };
+        let mut header = Header::from_tags(start_byte, header_tag, extra)?;
+        if header.bytes == 0 {
+            // Either an empty segment, followed by another header or the end of the file, or the
+            // last segment of a file whose sink didn't finalize the header
+            self.file.seek(SeekFrom::Start(header.abs_pos))?;
+            if !matches!(
+                parse_maybe_eof(&mut self.file),
+                Ok(None) | Ok(Some(Tag::Dict(_)))
+            ) {
+                header.bytes = unfinished_segment_bytes(&mut self.file, &header)?;
+            }
+        }
+        self.file.seek(SeekFrom::Start(old_pos))?;
+
+        Ok(Some(header))
+    }
+}
+
+impl<T: Read + Seek> SampleReadSeek for AttachedHeader<T> {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
+        self
+    }
+
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
+        &mut self.file
+    }
+}
+
+/// Reader of an in-memory (or memory mapped) file with attached headers, which allows borrowing
+/// samples directly from memory with read_samples_ref.
+pub struct SliceReader<'a> {
+    inner: AttachedHeader<Cursor<&'a [u8]>>,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(data: &'a [u8]) -> SliceReader<'a> {
+        SliceReader {
+            inner: AttachedHeader::new(Cursor::new(data)),
+        }
+    }
+
+    /// Zero-copy alternative to read_samples: returns a view of the samples from the current
+    /// position up to the end of the segment, following the same stopping rules as read_samples
+    /// (an empty slice is returned if no samples can be read). Samples can't be converted in
+    /// place, so the endianness of the reader must be native. Returns MetaFileError::Unaligned
+    /// if the samples in memory are not aligned for T.
+    pub fn read_samples_ref<T: SampleType + Pod>(&mut self) -> Result<&'a [T], MetaFileError> {
+        if !self.get_endianness().is_native() && T::DTYPE.bit_width() > 8 {
+            return Err(MetaFileError::InvalidArgument(
+                "zero-copy reads require native endianness",
+            ));
+        }
+        self.set_last_read_start(None);
+        let readable = |h: &Header| T::reads_directly_from(h.dtype, h.cplx);
+        let header = match self.next_readable_header(readable, SeekPreserve::All)? {
+            Ok(v) => v,
+            Err(_) => return Ok(&[]),
+        };
+
+        let to_read = self.samples_to_read(&header, u64::MAX)?;
+        let start = self.inner.file.position();
+        self.set_last_read_start(Some(start));
+        let cursor = &mut self.inner.file;
+        let start = start as usize;
+        let end = start + to_read as usize * size_of::<T>();
+        let data: &'a [u8] = cursor.get_ref();
+        let samples =
+            bytemuck::try_cast_slice(&data[start..end]).map_err(|_| MetaFileError::Unaligned)?;
+        cursor.set_position(end as u64);
+        Ok(samples)
+    }
+}
+
+impl HeaderReader for SliceReader<'_> {
+    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
+        self.inner.get_header_storage_mut()
+    }
+    fn get_header_storage(&self) -> &HeaderStorage {
+        self.inner.get_header_storage()
+    }
+
+    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
+        self.inner.load_next_header(start_byte)
+    }
+}
+
+impl SampleReadSeek for SliceReader<'_> {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
+        self
+    }
+
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
+        &mut self.inner.file
+    }
+}
+
+/// Length of the data of the last segment of a file, whose header says it's empty as the sink
+/// didn't finalize it, but extends up to the end of the file. Partial samples are left out.
+fn unfinished_segment_bytes(file: &mut impl Seek, header: &Header) -> std::io::Result<u64> {
+    let old_pos = file.stream_position()?;
+    let len = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(old_pos))?;
+    let size = header.size.max(1) as u64;
+    Ok(len.saturating_sub(header.abs_pos) / size * size)
+}
+
+/// Counts the bytes read through it, to know positions within streams that can't seek. Bytes
+/// in peeked were read from inner ahead of time, and are returned (and counted) first.
+struct CountingReader<'a, R: Read> {
+    inner: &'a mut R,
+    count: &'a mut u64,
+    peeked: &'a mut Option<u8>,
+}
+
+impl<R: Read> CountingReader<'_, R> {
+    /// Returns true if there are no more bytes to read, without consuming any
+    fn at_eof(&mut self) -> std::io::Result<bool> {
+        if self.peeked.is_none() {
+            let mut byte = [0];
+            if self.inner.read(&mut byte)? == 0 {
+                return Ok(true);
+            }
+            *self.peeked = Some(byte[0]);
+        }
+        Ok(false)
+    }
+}
+
+impl<R: Read> Read for CountingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = match self.peeked.take() {
+            Some(byte) => {
+                buf[0] = byte;
+                1
+            }
+            None => self.inner.read(buf)?,
+        };
+        *self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Reader of a file with dettached headers. The header file is only ever read forward, so it
+/// needs not be seekable (it may be a pipe, for example).
+pub struct DettachedHeader<B: Read + Seek, H: Read> {
+    header_storage: HeaderStorage,
+    binary_file: B,
+    header_file: H,
+    /// Bytes read from the header file
+    header_file_pos: u64,
+    /// Position of the next header in the header file
+    next_header_pos: u64,
+    /// Byte read from the header file to check for its end, but not yet consumed
+    peeked: Option<u8>,
+}
+
+impl<B: Read + Seek, H: Read> DettachedHeader<B, H> {
+    /// Positions in the header file (Header::pos_in_file) are counted from the position of
+    /// header_file when given, which must be at the start of a header.
+    pub fn new(binary_file: B, header_file: H) -> DettachedHeader<B, H> {
+        DettachedHeader {
+            header_storage: Default::default(),
+            binary_file,
+            header_file,
+            header_file_pos: 0,
+            next_header_pos: 0,
+            peeked: None,
+        }
+    }
+
+    /// Reader of a file whose headers are all taken from index (which must have been built from
+    /// the same files), instead of being scanned as needed. The header file is only read if
+    /// there are headers after the indexed ones. The index is trusted, but may be checked with
+    /// HeaderStorage::validate. Fails if index is of an attached file.
+    pub fn with_index(
+        binary_file: B,
+        header_file: H,
+        index: &FileIndex,
+    ) -> Result<DettachedHeader<B, H>, MetaFileError> {
+        if index.attached {
+            return Err(MetaFileError::InvalidIndex("attached"));
+        }
+        Ok(DettachedHeader {
+            header_storage: HeaderStorage::from_index(index),
+            // The indexed headers are skipped once the header file is first read
+            next_header_pos: index
+                .segments
+                .last()
+                .map_or(0, |last| last.header_pos + last.header_len),
+            ..DettachedHeader::new(binary_file, header_file)
+        })
+    }
+
+    /// Returns the underlying (binary file, header file), positioned wherever the reader left them
+    pub fn into_inner(self) -> (B, H) {
+        (self.binary_file, self.header_file)
+    }
+}
+
+impl<D: AsRef<[u8]>, E: AsRef<[u8]>> DettachedHeader<Cursor<D>, Cursor<E>> {
+    /// Reader of a data and header file already in memory, for example as Vec<u8> or &[u8]
+    pub fn from_bytes(data: D, header: E) -> DettachedHeader<Cursor<D>, Cursor<E>> {
+        DettachedHeader::new(Cursor::new(data), Cursor::new(header))
+    }
+}
+
+impl<B: Read + Seek, H: Read> HeaderReader for DettachedHeader<B, H> {
+    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
+        &mut self.header_storage
+    }
+
+    fn get_header_storage(&self) -> &HeaderStorage {
+        &self.header_storage
+    }
+
+    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
+        let mut reader = CountingReader {
+            inner: &mut self.header_file,
+            count: &mut self.header_file_pos,
+            peeked: &mut self.peeked,
+        };
+        // Skip whatever is left before the next header (unparsed extra dicts or indexed headers)
+        let header_pos = self.next_header_pos;
+        let skip = header_pos.saturating_sub(*reader.count);
+        if std::io::copy(&mut (&mut reader).take(skip), &mut std::io::sink())? < skip {
+            return Ok(None);
+        }
+
+        let header_tag = match parse_maybe_eof(&mut reader) {
+            Ok(Some(v)) => v,
+            Ok(None) => return Ok(None),
+            Err(e) => return Err(MetaFileError::ParseError(e)),
+        };
+        let mut header = if self.header_storage.skip_extra_dict {
+            let header = Header::from_tags(header_pos, header_tag, Tag::Null())?;
+            // strt is the length of the header and extra dict
+            self.next_header_pos = header_pos + header.strt;
+            header
+        } else {
+            let extra = parse(&mut reader)?;
+            self.next_header_pos = *reader.count;
+            Header::from_tags(header_pos, header_tag, extra)?
+        };
+        // Data segments are stored back-to-back in the binary file
+        header.abs_pos = start_byte;
+        header.attached = false;
+
+        // An empty last segment may be that of a file whose sink didn't finalize the header. The
+        // check is only exact if the extra dict was parsed, otherwise its end is not yet reached.
+        if header.bytes == 0 && *reader.count == self.next_header_pos && reader.at_eof()? {
+            header.bytes = unfinished_segment_bytes(&mut self.binary_file, &header)?;
+        }
+        Ok(Some(header))
+    }
+}
+
+impl<B: Read + Seek, H: Read> SampleReadSeek for DettachedHeader<B, H> {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
+        self
+    }
+
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
+        &mut self.binary_file
+    }
+}
+
+/// Gets the length in bytes of the data of a segment from its header and extra dicts, see
+/// RawHeaderReader::with_segment_bytes
+type SegmentBytesFn = dyn Fn(&Tag, &Tag) -> Option<u64>;
+
+/// Reader of files with attached headers written by non-standard sinks, whose header dicts
+/// don't follow the GNU Radio schema. Each header is still stored as two PMTs (a header dict
+/// and an extra dict, which may be anything) right before its data, but they are not decoded:
+/// the format and sample rate of the samples are supplied by the user, and segments are taken
+/// to be contiguous in time.
+pub struct RawHeaderReader<T: Read + Seek> {
+    header_storage: HeaderStorage,
+    file: T,
+    format: Format,
+    samp_rate: f64,
+    segment_bytes: Option<Box<SegmentBytesFn>>,
+    /// Header and extra dicts of every loaded header, keyed as the headers in the store
+    raw_tags: BTreeMap<u64, (Tag, Tag)>,
+}
+
+impl<T: Read + Seek> RawHeaderReader<T> {
+    /// By default, the data after the first header extends to the end of the file, see
+    /// with_segment_bytes for files with more than one header.
+    pub fn new(file: T, format: Format, samp_rate: f64) -> RawHeaderReader<T> {
+        RawHeaderReader {
+            header_storage: Default::default(),
+            file,
+            format,
+            samp_rate,
+            segment_bytes: None,
+            raw_tags: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the function giving the length in bytes of the data of a segment from its header and
+    /// extra dicts, with the data after the header extending to the end of the file if it
+    /// returns None.
+    pub fn with_segment_bytes(mut self, f: impl Fn(&Tag, &Tag) -> Option<u64> + 'static) -> Self {
+        self.segment_bytes = Some(Box::new(f));
+        self
+    }
+
+    /// Returns the (header, extra) dicts of every segment of the file, in file order. Loads all
+    /// headers.
+    pub fn raw_tags(&mut self) -> Result<Vec<(Tag, Tag)>, MetaFileError> {
+        self.load_all_headers()?;
+        Ok(self.raw_tags.values().cloned().collect())
+    }
+
+    /// Returns the underlying file, positioned wherever the reader left it
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+}
+
+impl<T: Read + Seek> HeaderReader for RawHeaderReader<T> {
+    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
+        &mut self.header_storage
+    }
     fn get_header_storage(&self) -> &HeaderStorage {
         &self.header_storage
     }
 
-    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
-        // header_file seek is always at the next header, so we can simply
-        let header_tag = match parse_maybe_eof(&mut self.header_file) {
-            Ok(Some(v)) => v,
-            Ok(None) => return Ok(None),
-            Err(e) => return Err(MetaFileError::ParseError(e)),
+    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
+        let old_pos = self.file.stream_position()?;
+        self.file.seek(SeekFrom::Start(start_byte))?;
+
+        let header_tag = match parse_maybe_eof(&mut self.file) {
+            Ok(Some(v)) => v,
+            Ok(None) => {
+                self.file.seek(SeekFrom::Start(old_pos))?;
+                return Ok(None);
+            }
+            Err(e) => return Err(MetaFileError::ParseError(e)),
+        };
+        let extra = parse(&mut self.file)?;
+        let data_start = self.file.stream_position()?;
+        let bytes = match self
+            .segment_bytes
+            .as_ref()
+            .and_then(|f| f(&header_tag, &extra))
+        {
+            Some(v) => v,
+            None => self.file.seek(SeekFrom::End(0))?.saturating_sub(data_start),
+        };
+        self.file.seek(SeekFrom::Start(old_pos))?;
+
+        // Segments are contiguous in time, starting at 0
+        let rx_time = match self
+            .header_storage
+            .store
+            .range(..(start_byte, 0))
+            .next_back()
+        {
+            Some((_, prev)) => prev.get_sample_time(prev.get_num_samples() as i64),
+            None => Timestamp::ZERO,
+        };
+        let mut header = HeaderBuilder::new()
+            .samp_rate(self.samp_rate)
+            .dtype(self.format.dtype)
+            .cplx(self.format.complex)
+            .rx_time(rx_time)
+            .extra(extra.clone())
+            .strt(data_start - start_byte)
+            .attached_at(start_byte)
+            .build();
+        header.bytes = bytes;
+        self.raw_tags.insert(start_byte, (header_tag, extra));
+
+        Ok(Some(header))
+    }
+}
+
+impl<T: Read + Seek> SampleReadSeek for RawHeaderReader<T> {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
+        self
+    }
+
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
+        &mut self.file
+    }
+}
+
+/// Reader of a file on disk with either attached or dettached headers, see open_auto. Files are
+/// buffered, as headers are parsed with many small reads.
+pub enum Reader {
+    Attached(AttachedHeader<BufReader<File>>),
+    Dettached(DettachedHeader<BufReader<File>, BufReader<File>>),
+}
+
+/// Opens the file at data_path, which has dettached headers if there is a header file next to
+/// it named as GNU Radio does (data_path with ".hdr" appended), or attached headers otherwise.
+pub fn open_auto<P: AsRef<Path>>(data_path: P) -> Result<Reader, MetaFileError> {
+    let data_path = data_path.as_ref();
+    let mut header_path = data_path.as_os_str().to_owned();
+    header_path.push(".hdr");
+
+    let binary_file = BufReader::new(File::open(data_path)?);
+    Ok(match File::open(&header_path) {
+        Ok(header_file) => Reader::Dettached(DettachedHeader::new(
+            binary_file,
+            BufReader::new(header_file),
+        )),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Reader::Attached(AttachedHeader::new(binary_file))
+        }
+        Err(e) => return Err(e.into()),
+    })
+}
+
+impl HeaderReader for Reader {
+    fn get_header_storage_mut(&mut self) -> &mut HeaderStorage {
+        match self {
+            Reader::Attached(r) => r.get_header_storage_mut(),
+            Reader::Dettached(r) => r.get_header_storage_mut(),
+        }
+    }
+
+    fn get_header_storage(&self) -> &HeaderStorage {
+        match self {
+            Reader::Attached(r) => r.get_header_storage(),
+            Reader::Dettached(r) => r.get_header_storage(),
+        }
+    }
+
+    fn load_next_header(&mut self, start_byte: u64) -> Result<Option<Header>, MetaFileError> {
+        match self {
+            Reader::Attached(r) => r.load_next_header(start_byte),
+            Reader::Dettached(r) => r.load_next_header(start_byte),
+        }
+    }
+}
+
+impl SampleReadSeek for Reader {
+    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
+        self
+    }
+
+    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
+        match self {
+            Reader::Attached(r) => &mut r.file,
+            Reader::Dettached(r) => &mut r.binary_file,
+        }
+    }
+}
+
+#[cfg(test)]
+mod core_tests {
+    use super::*;
+    use crate::decompress::DecompressingReader;
+    use crate::index::SegmentInfo;
+    use crate::pmt::{HeaderFileIter, write};
+    use std::fs::File;
+
+    /// A segment of a synthetic test file, written as GNU Radio would
+    struct TestSegment {
+        dtype: i32,
+        cplx: bool,
+        size: i32,
+        rx_rate: f64,
+        /// If None, continues right after the previous segment (or 0 if first)
+        rx_time: Option<(u64, f64)>,
+        extra: Tag,
+        data: Vec<u8>,
+    }
+
+    impl TestSegment {
+        /// Byte segment, each sample being its index in the segment (wrapping)
+        fn bytes(num: u64) -> TestSegment {
+            TestSegment {
+                dtype: 0,
+                cplx: false,
+                size: 1,
+                rx_rate: 1e6,
+                rx_time: None,
+                extra: Tag::Null(),
+                data: (0..num).map(|i| i as u8).collect(),
+            }
+        }
+
+        fn samples<T: SampleType>(samples: &[T]) -> TestSegment {
+            let dtype = match T::DTYPE {
+                DataType::Byte => 0,
+                DataType::Short => 1,
+                DataType::Int => 2,
+                DataType::Float => 5,
+                DataType::Double => 6,
+            };
+            // SAFETY: SampleType guarantees plain old data
+            let data = unsafe {
+                std::slice::from_raw_parts(
+                    samples.as_ptr() as *const u8,
+                    std::mem::size_of_val(samples),
+                )
+            };
+            TestSegment {
+                dtype,
+                cplx: T::COMPLEX,
+                size: T::byte_width() as i32,
+                rx_rate: 1e6,
+                rx_time: None,
+                extra: Tag::Null(),
+                data: data.to_vec(),
+            }
+        }
+
+        fn num_samples(&self) -> u64 {
+            self.data.len() as u64 / self.size as u64
+        }
+
+        /// Serializes the header and extra dict exactly as GNU Radio does (i.e. strt is the
+        /// length of the header and extra dict)
+        fn header(&self, rx_time: (u64, f64)) -> Vec<u8> {
+            let make_tag = |strt: u64| {
+                let mut dict = std::collections::BTreeMap::new();
+                dict.insert("version".to_string(), Tag::Int32(0));
+                dict.insert("rx_rate".to_string(), Tag::Double(self.rx_rate));
+                dict.insert(
+                    "rx_time".to_string(),
+                    Tag::Tuple(vec![Tag::UInt64(rx_time.0), Tag::Double(rx_time.1)]),
+                );
+                dict.insert("size".to_string(), Tag::Int32(self.size));
+                dict.insert("type".to_string(), Tag::Int32(self.dtype));
+                dict.insert("cplx".to_string(), Tag::Bool(self.cplx));
+                dict.insert("strt".to_string(), Tag::UInt64(strt));
+                dict.insert("bytes".to_string(), Tag::UInt64(self.data.len() as u64));
+                Tag::Dict(dict)
+            };
+
+            let mut extra = Vec::new();
+            write(&mut extra, &self.extra).unwrap();
+            let mut probe = Vec::new();
+            write(&mut probe, &make_tag(0)).unwrap();
+
+            let mut out = Vec::new();
+            write(&mut out, &make_tag((probe.len() + extra.len()) as u64)).unwrap();
+            out.extend(extra);
+            out
+        }
+    }
+
+    /// Returns the serialized header of each segment, and its data
+    fn build_segments(segments: &[TestSegment]) -> Vec<(Vec<u8>, &[u8])> {
+        let mut t = (0, 0.0);
+        segments
+            .iter()
+            .map(|seg| {
+                let rx_time = seg.rx_time.unwrap_or(t);
+                let frac = rx_time.1 + seg.num_samples() as f64 / seg.rx_rate;
+                t = (rx_time.0 + frac.trunc() as u64, frac.fract());
+                (seg.header(rx_time), seg.data.as_slice())
+            })
+            .collect()
+    }
+
+    fn attached_file(segments: &[TestSegment]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (header, data) in build_segments(segments) {
+            out.extend(header);
+            out.extend(data);
+        }
+        out
+    }
+
+    /// Returns (binary file, header file)
+    fn dettached_file(segments: &[TestSegment]) -> (Vec<u8>, Vec<u8>) {
+        let mut bin = Vec::new();
+        let mut hdr = Vec::new();
+        for (header, data) in build_segments(segments) {
+            hdr.extend(header);
+            bin.extend(data);
+        }
+        (bin, hdr)
+    }
+
+    /// Returns the binary file (always) and the header file if it exists
+    fn get_or_run_gnuradio(file: &'static str) -> (File, Option<File>) {
+        use std::process::Command;
+
+        let src_path = format!("test_files/{}.grc", file);
+        let src_path_py = format!("target/test_files/{}.py", file);
+        let dst_path = format!("target/test_files/{}", file);
+        // not always generated
+        let dst_path_hdr = format!("target/test_files/{}.grh", file);
+
+        // Compile the file
+        let cout = Command::new("grcc")
+            .args([src_path.as_str(), "-o", "target/test_files/"])
+            .output()
+            .unwrap_or_else(|_| panic!("failed to run GNU radio compiler on {}", src_path));
+        if !cout.status.success() {
+            panic!(
+                "GNU radio compiler failed on file {}, error: {}",
+                src_path,
+                String::from_utf8(cout.stderr).unwrap()
+            )
+        }
+
+        let out = Command::new("python3")
+            .args([src_path_py.as_str(), "--out", dst_path.as_str()])
+            .output()
+            .unwrap_or_else(|_| panic!("failed to run GNU radio on {}", src_path));
+        if !out.status.success() {
+            panic!(
+                "GNU radio failed on file {}, error: {}",
+                file,
+                String::from_utf8(out.stderr).unwrap(),
+            )
+        }
+
+        (File::open(dst_path).unwrap(), File::open(dst_path_hdr).ok())
+    }
+
+    #[test]
+    fn read_byte_samples_attached() {
+        let (file, _) = get_or_run_gnuradio("bytes_increasing");
+        let mut reader = AttachedHeader::new(file);
+        let mut samples: [i8; 256] = [0; 256];
+
+        let num_read = reader.read_samples(&mut samples).unwrap();
+        // Basic sample reading checks
+        assert_eq!(num_read, 256);
+        for (i, &samp) in samples.iter().enumerate() {
+            assert_eq!(samp, i as i8);
+        }
+
+        // Public header interface
+        let _header = reader.get_last_read_header();
+
+        // Check sane internal state for headers
+
+        // Further reads should return nothing
+    }
+
+    #[test]
+    fn header_lookup_irregular_segments_attached() {
+        let lens = [10, 1_000_000, 42];
+        let file = attached_file(&lens.map(TestSegment::bytes));
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut header_start = 0;
+        for len in lens {
+            let header = reader.get_header_for_byte(header_start).unwrap().unwrap();
+            assert_eq!(header.bytes, len);
+            assert_eq!(header.pos_in_file, header_start);
+            let data_end = header.abs_pos + header.bytes;
+
+            // Bytes of the header itself, and first and last data byte
+            for byte in [header_start, header.abs_pos, data_end - 1] {
+                let h = reader.get_header_for_byte(byte).unwrap().unwrap();
+                assert_eq!(h, header);
+            }
+
+            header_start = data_end;
+        }
+
+        assert!(reader.get_header_for_byte(header_start).unwrap().is_none());
+        assert!(reader.get_header_for_byte(u64::MAX).unwrap().is_none());
+        // Lookups going backwards are served from the already loaded headers
+        let first = reader.get_header_for_byte(5).unwrap().unwrap();
+        assert_eq!(first.bytes, 10);
+    }
+
+    #[test]
+    fn header_lookup_irregular_segments_dettached() {
+        let lens = [10, 1_000_000, 42];
+        let (bin, hdr) = dettached_file(&lens.map(TestSegment::bytes));
+        let mut reader = DettachedHeader::new(Cursor::new(bin), Cursor::new(hdr));
+
+        let mut data_start = 0;
+        for len in lens {
+            let header = reader.get_header_for_byte(data_start).unwrap().unwrap();
+            assert_eq!(header.bytes, len);
+            assert_eq!(header.abs_pos, data_start);
+
+            let last = reader
+                .get_header_for_byte(data_start + len - 1)
+                .unwrap()
+                .unwrap();
+            assert_eq!(last, header);
+
+            data_start += len;
+        }
+
+        assert!(reader.get_header_for_byte(data_start).unwrap().is_none());
+    }
+
+    #[test]
+    fn read_custom_sample_type() {
+        #[repr(C)]
+        #[derive(Clone, Copy, Default, Debug, PartialEq)]
+        struct Iq {
+            i: f32,
+            q: f32,
+        }
+
+        unsafe impl SampleType for Iq {
+            const DTYPE: DataType = DataType::Float;
+            const COMPLEX: bool = true;
+        }
+        assert!(DataType::Float.reads_directly_to::<Iq>(true));
+        assert!(!DataType::Float.reads_directly_to::<Iq>(false));
+        assert!(DataType::Float.converts_to::<Iq>(true));
+        assert!(!DataType::Double.converts_to::<Iq>(false));
+
+        let samples: Vec<Complex<f32>> = (0..100)
+            .map(|i| Complex::new(i as f32, -(i as f32)))
+            .collect();
+        let file = attached_file(&[TestSegment::samples(&samples)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [Iq::default(); 128];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 100);
+        for (i, iq) in buf[..100].iter().enumerate() {
+            assert_eq!(
+                *iq,
+                Iq {
+                    i: i as f32,
+                    q: -(i as f32)
+                }
+            );
+        }
+
+        // Not directly readable into the scalar type
+        let file = attached_file(&[TestSegment::samples(&samples)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        let mut buf = [0.0f32; 10];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_across_segments() {
+        let file = attached_file(&[TestSegment::bytes(100), TestSegment::bytes(50)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [0i8; 200];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 150);
+        assert!(buf[..100].iter().enumerate().all(|(i, &b)| b == i as i8));
+        assert!(buf[100..150].iter().enumerate().all(|(i, &b)| b == i as i8));
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn last_read_offset_and_time() {
+        let samples: Vec<f32> = (0..50).map(|i| i as f32).collect();
+        let file = attached_file(&[TestSegment::samples(&samples)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        assert_eq!(reader.get_last_read_offset_in_header().unwrap(), None);
+        assert_eq!(reader.get_last_read_rx_time().unwrap(), None);
+
+        let mut buf = [0f32; 10];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 10);
+        assert_eq!(reader.get_last_read_offset_in_header().unwrap(), Some(10));
+        let time = reader.get_last_read_rx_time().unwrap().unwrap();
+        assert!((time.as_secs_f64() - 9e-6).abs() < 1e-12);
+    }
+
+    #[test]
+    fn empty_segment_between_data() {
+        let first: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        let second: Vec<f32> = (10..25).map(|i| i as f32).collect();
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment::samples::<f32>(&[]),
+            TestSegment::samples(&second),
+        ];
+
+        let file = attached_file(&segments);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        let mut buf = [0f32; 30];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 25);
+        assert!(buf[..25].iter().enumerate().all(|(i, &v)| v == i as f32));
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::new(Cursor::new(bin), Cursor::new(hdr));
+        let mut buf = [0f32; 30];
+        assert_eq!(reader.read_samples(&mut buf[..5]).unwrap(), 5);
+        assert_eq!(reader.read_samples(&mut buf[5..]).unwrap(), 20);
+        assert!(buf[..25].iter().enumerate().all(|(i, &v)| v == i as f32));
+    }
+
+    #[test]
+    fn read_bytes_boundary() {
+        let file = attached_file(&[TestSegment::bytes(100), TestSegment::bytes(50)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [0u8; 64];
+        assert_eq!(
+            reader.read_bytes_until_boundary(&mut buf).unwrap(),
+            (64, false)
+        );
+        assert_eq!(
+            reader.read_bytes_until_boundary(&mut buf).unwrap(),
+            (36, true)
+        );
+        assert!(
+            buf[..36]
+                .iter()
+                .enumerate()
+                .all(|(i, &b)| b == 64 + i as u8)
+        );
+        assert_eq!(
+            reader.read_bytes_until_boundary(&mut buf).unwrap(),
+            (50, true)
+        );
+        assert!(buf[..50].iter().enumerate().all(|(i, &b)| b == i as u8));
+        assert_eq!(
+            reader.read_bytes_until_boundary(&mut buf).unwrap(),
+            (0, false)
+        );
+    }
+
+    #[test]
+    fn slice_reader_zero_copy() {
+        let first: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        let second: Vec<f32> = (10..15).map(|i| i as f32).collect();
+        let file = attached_file(&[TestSegment::samples(&first), TestSegment::samples(&second)]);
+        let strt = AttachedHeader::new(Cursor::new(&file))
+            .get_header_for_byte(0)
+            .unwrap()
+            .unwrap()
+            .abs_pos as usize;
+
+        // Place the file in memory such that the samples of the first segment are aligned
+        let mut backing = vec![0u32; file.len() / 4 + 2];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut backing);
+        let pad = (4 - strt % 4) % 4;
+        bytes[pad..pad + file.len()].copy_from_slice(&file);
+        let data = &bytes[pad..pad + file.len()];
+
+        let mut reader = SliceReader::new(data);
+        let samples = reader.read_samples_ref::<f32>().unwrap();
+        assert_eq!(samples, first.as_slice());
+        assert_eq!(samples.as_ptr() as *const u8, data[strt..].as_ptr());
+
+        // The second segment may or may not be aligned, depending on the header length
+        let second_pos = reader
+            .get_header_for_byte(data.len() as u64 - 1)
+            .unwrap()
+            .unwrap()
+            .abs_pos;
+        match reader.read_samples_ref::<f32>() {
+            Ok(samples) => {
+                assert_eq!(samples, second.as_slice());
+                assert_eq!(
+                    samples.as_ptr() as *const u8,
+                    data[second_pos as usize..].as_ptr()
+                );
+            }
+            Err(e) => {
+                assert!(matches!(e, MetaFileError::Unaligned));
+                assert_ne!((data.as_ptr() as u64 + second_pos) % 4, 0);
+            }
+        }
+
+        let shifted = &mut bytes[pad + 1..pad + 1 + file.len()];
+        shifted.copy_from_slice(&file);
+        let mut reader = SliceReader::new(shifted);
+        assert!(matches!(
+            reader.read_samples_ref::<f32>(),
+            Err(MetaFileError::Unaligned)
+        ));
+    }
+
+    #[test]
+    fn last_read_meta_center_freq() {
+        let samples: Vec<Complex<f32>> = (0..20).map(|i| Complex::new(i as f32, 0.0)).collect();
+        let extra = Tag::Dict([("rx_freq".to_string(), Tag::Double(433.92e6))].into());
+        let segments = [
+            TestSegment {
+                extra,
+                ..TestSegment::samples(&samples)
+            },
+            TestSegment::samples(&samples),
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        assert!(reader.get_last_read_meta().unwrap().is_none());
+
+        let mut buf = [Complex::<f32>::default(); 5];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 5);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 5);
+        let meta = reader.get_last_read_meta().unwrap().unwrap();
+        assert_eq!(meta.samp_rate, 1e6);
+        assert_eq!(meta.center_freq, Some(433.92e6));
+        assert_eq!(meta.bandwidth, None);
+        assert!((meta.rx_time.as_secs_f64() - 5e-6).abs() < 1e-12);
+
+        // Reading across into the second segment, which lacks the tag
+        let mut buf = [Complex::<f32>::default(); 20];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 20);
+        let meta = reader.get_last_read_meta().unwrap().unwrap();
+        assert_eq!(meta.center_freq, Some(433.92e6));
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 10);
+        assert_eq!(
+            reader.get_last_read_meta().unwrap().unwrap().center_freq,
+            None
+        );
+
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 0);
+        assert!(reader.get_last_read_meta().unwrap().is_none());
+    }
+
+    #[test]
+    fn preload_headers_for_range() {
+        let segments = [100, 100, 100, 100].map(TestSegment::bytes);
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let second = reader.get_header_at_index(1).unwrap().unwrap();
+
+        // Within the data of the second segment
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let range = second.data_file_range();
+        assert_eq!(
+            reader
+                .preload_headers_for_range(range.start + 10, range.end - 10)
+                .unwrap(),
+            1
+        );
+        assert_eq!(reader.get_header_storage().len(), 2);
+
+        // Up to the inline header of the third segment
+        assert_eq!(
+            reader
+                .preload_headers_for_range(range.start, range.end + 1)
+                .unwrap(),
+            2
+        );
+        assert_eq!(reader.get_header_storage().len(), 3);
+
+        assert_eq!(reader.preload_headers_for_range(50, 50).unwrap(), 0);
+        assert_eq!(reader.get_header_storage().len(), 3);
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin, hdr);
+        assert_eq!(reader.preload_headers_for_range(150, 250).unwrap(), 2);
+        assert_eq!(reader.get_header_storage().len(), 3);
+    }
+
+    #[test]
+    fn scan_limit() {
+        let mut segments: Vec<_> = (0..10).map(|_| TestSegment::bytes(0)).collect();
+        segments.push(TestSegment::bytes(100));
+        let (bin, hdr) = dettached_file(&segments);
+
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_headers_to_scan(5);
+        assert!(matches!(
+            reader.get_header_for_byte(0),
+            Err(MetaFileError::ScanLimitExceeded(5))
+        ));
+        // The limit applies to each lookup, so later ones continue where it stopped
+        let mut buf = [0i8; 200];
+        assert!(reader.read_samples(&mut buf).is_err());
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 100);
+
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_headers_to_scan(11);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 100);
+    }
+
+    #[test]
+    fn evict_cached_headers() {
+        let ramp: Vec<f32> = (0..500).map(|i| i as f32).collect();
+        let segments: Vec<_> = ramp.chunks(50).map(TestSegment::samples).collect();
+        let (bin, hdr) = dettached_file(&segments);
+
+        // Streaming forward keeps only the last headers, including that of the current segment
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_cached_headers(Some(3));
+        let mut buf = [0f32; 120];
+        for _ in 0..2 {
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 120);
+        }
+        assert_eq!(reader.read_samples(&mut buf[..15]).unwrap(), 15);
+        assert_eq!(buf[..15], ramp[240..255]);
+        assert_eq!(reader.get_header_storage().len(), 3);
+        let current = reader.get_last_read_header().unwrap().unwrap();
+        assert_eq!(current.first_sample, 250);
+        assert_eq!(reader.position_samples().unwrap(), 255);
+
+        // Retained segments can still be looked up and seeked to, evicted ones can't
+        assert_eq!(reader.get_header_at_index(5).unwrap().unwrap(), current);
+        assert!(matches!(
+            reader.get_header_at_index(2),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.get_header_for_byte(0),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.seek(SeekFrom::Start(10), SeekPreserve::None),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert_eq!(
+            reader
+                .seek(SeekFrom::Start(210), SeekPreserve::None)
+                .unwrap(),
+            210
+        );
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 120);
+        assert_eq!(buf[..], ramp[210..330]);
+
+        // Retained segments keep their index in the file, segments of 50 us
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_cached_headers(Some(3));
+        for _ in 0..3 {
+            assert_eq!(reader.read_samples(&mut buf[..100]).unwrap(), 100);
+        }
+        let location = reader
+            .segment_at_time(Timestamp::from_parts(0, 460e-6))
+            .unwrap();
+        assert_eq!(location.index, 9);
+        assert!(matches!(
+            reader.segment_at_time(Timestamp::ZERO),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.seek_to_time(Timestamp::ZERO, SeekPreserve::None),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        // Methods needing every header error instead of working on those retained
+        assert!(matches!(
+            reader.load_all_headers(),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.find_all_tags("key"),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.build_index(),
+            Err(MetaFileError::EvictedHeader)
+        ));
+        assert!(matches!(
+            reader.stats::<f32>(),
+            Err(MetaFileError::EvictedHeader)
+        ));
+
+        // Without reads, only the last headers loaded are kept
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_cached_headers(Some(3));
+        assert_eq!(reader.segments().count(), 10);
+        assert_eq!(reader.get_header_storage().len(), 3);
+        assert!(matches!(
+            reader.segments().next(),
+            Some(Err(MetaFileError::EvictedHeader))
+        ));
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.set_max_cached_headers(Some(3));
+        assert_eq!(
+            reader
+                .seek(SeekFrom::Start(460), SeekPreserve::None)
+                .unwrap(),
+            460
+        );
+        assert_eq!(reader.get_header_storage().len(), 3);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 40);
+        assert_eq!(buf[..40], ramp[460..]);
+
+        // Data segments of 200 bytes, so the first three end at or before byte 650
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        reader.load_all_headers().unwrap();
+        reader.clear_cache_before(650);
+        assert_eq!(reader.get_header_storage().len(), 7);
+        assert_eq!(
+            reader.get_header_at_index(3).unwrap().unwrap().first_sample,
+            150
+        );
+        // The last header is always kept, to continue loading from it
+        reader.clear_cache_before(u64::MAX);
+        assert_eq!(reader.get_header_storage().len(), 1);
+        assert_eq!(
+            reader.get_header_at_index(9).unwrap().unwrap().first_sample,
+            450
+        );
+        assert!(reader.get_header_at_index(10).unwrap().is_none());
+    }
+
+    #[test]
+    fn segment_at_time() {
+        // 100 samples at 1 MHz, an empty segment, then a 50 us gap and 100 more samples
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(0),
+            TestSegment {
+                rx_time: Some((0, 150e-6)),
+                ..TestSegment::bytes(100)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let at = |reader: &mut AttachedHeader<_>, t: f64| {
+            reader.segment_at_time(Timestamp::from_secs_f64(t * 1e-6))
+        };
+
+        let inside = |index| SegmentLocation {
+            index,
+            in_gap_after: None,
+        };
+        assert_eq!(at(&mut reader, 0.0).unwrap(), inside(0));
+        assert_eq!(at(&mut reader, 99.5).unwrap(), inside(0));
+        assert_eq!(at(&mut reader, 150.0).unwrap(), inside(2));
+        assert_eq!(at(&mut reader, 249.0).unwrap(), inside(2));
+
+        // In the gap, closer to either side
+        let in_gap = |index| SegmentLocation {
+            index,
+            in_gap_after: Some(0),
+        };
+        assert_eq!(at(&mut reader, 110.0).unwrap(), in_gap(0));
+        assert_eq!(at(&mut reader, 140.0).unwrap(), in_gap(2));
+
+        assert!(matches!(
+            at(&mut reader, 250.0),
+            Err(MetaFileError::TimeOutOfRange)
+        ));
+        assert!(matches!(
+            reader.segment_at_time(Timestamp::from_secs_f64(-1.0)),
+            Err(MetaFileError::TimeOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn seek_to_time_segment_and_gap() {
+        let first: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let second: Vec<f32> = (100..200).map(|i| i as f32).collect();
+        // 100 samples at 1 MHz, then a 50 us gap
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment {
+                rx_time: Some((0, 150e-6)),
+                ..TestSegment::samples(&second)
+            },
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        let mut buf = [0f32; 1];
+
+        let t = Timestamp::from_secs_f64(42e-6);
+        assert_eq!(reader.seek_to_time(t, SeekPreserve::All).unwrap(), 42);
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 42.0);
+
+        let t = Timestamp::from_secs_f64(170.5e-6);
+        assert_eq!(reader.seek_to_time(t, SeekPreserve::All).unwrap(), 120);
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 120.0);
+
+        let t = Timestamp::from_secs_f64(120e-6);
+        assert!(matches!(
+            reader.seek_to_time(t, SeekPreserve::All),
+            Err(MetaFileError::TimeOutOfRange)
+        ));
+        // Position is left unmodified
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 121.0);
+
+        reader.set_time_gap_seek(TimeGapSeek::NextSegment);
+        assert_eq!(reader.seek_to_time(t, SeekPreserve::All).unwrap(), 100);
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 100.0);
+
+        let t = Timestamp::from_secs_f64(1.0);
+        assert!(matches!(
+            reader.seek_to_time(t, SeekPreserve::All),
+            Err(MetaFileError::TimeOutOfRange)
+        ));
+    }
+
+    #[test]
+    fn seek_nearest_time() {
+        let first: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let second: Vec<f32> = (100..200).map(|i| i as f32).collect();
+        // 100 samples at 1 MHz, then a 50 us gap
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment {
+                rx_time: Some((0, 150e-6)),
+                ..TestSegment::samples(&second)
+            },
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        let mut buf = [0f32; 1];
+        let us = |t: f64| Timestamp::from_secs_f64(t * 1e-6);
+        let max_gap = us(5.0);
+
+        // Exactly on a sample
+        assert_eq!(reader.seek_nearest_time(us(42.0), max_gap).unwrap(), 42);
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 42.0);
+
+        // Between samples, to the closest one
+        assert_eq!(reader.seek_nearest_time(us(170.7), max_gap).unwrap(), 121);
+        assert_eq!(reader.seek_nearest_time(us(170.3), max_gap).unwrap(), 120);
+
+        // Near the edges of the gap, to the last or first sample around it
+        assert_eq!(reader.seek_nearest_time(us(103.0), max_gap).unwrap(), 99);
+        assert_eq!(reader.seek_nearest_time(us(146.0), max_gap).unwrap(), 100);
+
+        // Deep in the gap, or far outside the file
+        reader
+            .seek(SeekFrom::Start(10), SeekPreserve::None)
+            .unwrap();
+        for t in [125.0, -10.0, 300.0] {
+            assert!(matches!(
+                reader.seek_nearest_time(us(t), max_gap),
+                Err(MetaFileError::TimeOutOfRange)
+            ));
+        }
+        // Position is left unmodified
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(buf[0], 10.0);
+
+        // Unless the gap is allowed (sample 99 is at 99 us, 100 at 150 us)
+        assert_eq!(reader.seek_nearest_time(us(125.0), us(30.0)).unwrap(), 100);
+    }
+
+    #[test]
+    fn seek_preserve() {
+        let shorts: Vec<i16> = (0..10).collect();
+        let floats: Vec<f32> = (10..20).map(|i| i as f32).collect();
+        let file = attached_file(&[TestSegment::samples(&shorts), TestSegment::samples(&floats)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        assert_eq!(
+            reader.seek(SeekFrom::Start(5), SeekPreserve::All).unwrap(),
+            5
+        );
+        assert_eq!(
+            reader
+                .seek(SeekFrom::Current(2), SeekPreserve::Segment)
+                .unwrap(),
+            7
+        );
+        assert!(matches!(
+            reader.seek(SeekFrom::Current(5), SeekPreserve::All),
+            Err(MetaFileError::IncompatibleSeek)
+        ));
+        // Floats don't convert to shorts...
+        assert!(matches!(
+            reader.seek(SeekFrom::End(-3), SeekPreserve::Convertability),
+            Err(MetaFileError::IncompatibleSeek)
+        ));
+        assert_eq!(
+            reader.seek(SeekFrom::End(-3), SeekPreserve::None).unwrap(),
+            17
+        );
+        let mut buf = [0f32; 5];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 3);
+        assert_eq!(buf[..3], [17.0, 18.0, 19.0]);
+
+        // ...but shorts convert to floats
+        assert_eq!(
+            reader
+                .seek(SeekFrom::Start(3), SeekPreserve::Convertability)
+                .unwrap(),
+            3
+        );
+        assert!(matches!(
+            reader.seek(SeekFrom::Start(12), SeekPreserve::Format),
+            Err(MetaFileError::IncompatibleSeek)
+        ));
+        assert_eq!(
+            reader.seek(SeekFrom::Start(0), SeekPreserve::None).unwrap(),
+            0
+        );
+        assert!(reader.seek(SeekFrom::End(1), SeekPreserve::None).is_err());
+    }
+
+    #[test]
+    fn read_iq_complex_float() {
+        let samples: Vec<Complex<f32>> =
+            (0..30).map(|i| Complex::new(i as f32, -i as f32)).collect();
+        let file = attached_file(&[TestSegment::samples(&samples)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [Complex::default(); 40];
+        assert_eq!(reader.read_iq(&mut buf).unwrap(), 30);
+        assert_eq!(buf[..30], samples[..]);
+        assert_eq!(reader.read_iq(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_iq_wrong_type() {
+        let file = attached_file(&[TestSegment::bytes(10)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [Complex::default(); 10];
+        let err = reader.read_iq(&mut buf).unwrap_err();
+        assert!(matches!(
+            err,
+            MetaFileError::WrongSampleType {
+                expected: "cf32",
+                found: "i8"
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Expected samples of type cf32, but segment is of type i8"
+        );
+    }
+
+    #[test]
+    fn read_through_decompressing_reader() {
+        let samples: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let file = attached_file(&[
+            TestSegment::samples(&samples),
+            TestSegment::samples(&samples),
+        ]);
+
+        // A plain slice is a non-seekable stream, as decoders are
+        for stream in [
+            DecompressingReader::new(&file[..]).unwrap(),
+            DecompressingReader::new_temp_file(&file[..]).unwrap(),
+        ] {
+            let mut reader = AttachedHeader::new(stream);
+            reader
+                .seek(SeekFrom::Start(150), SeekPreserve::All)
+                .unwrap();
+            let mut buf = [0f32; 100];
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 50);
+            assert_eq!(buf[0], 50.0);
+        }
+    }
+
+    #[test]
+    fn header_at_index() {
+        let segments: Vec<TestSegment> = (1..=5).map(|i| TestSegment::bytes(i * 10)).collect();
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+
+        assert_eq!(reader.get_header_at_index(2).unwrap().unwrap().bytes, 30);
+        assert_eq!(reader.get_header_at_index(0).unwrap().unwrap().bytes, 10);
+        assert_eq!(reader.get_header_at_index(4).unwrap().unwrap().bytes, 50);
+        assert!(reader.get_header_at_index(5).unwrap().is_none());
+        assert!(reader.get_header_at_index(100).unwrap().is_none());
+    }
+
+    #[test]
+    fn skip_extra_dict() {
+        let samples: Vec<i16> = (0..20).collect();
+        let segments: Vec<TestSegment> = (0..4)
+            .map(|i| TestSegment {
+                extra: Tag::Dict([("segment".to_string(), Tag::Int32(i as i32))].into()),
+                ..TestSegment::samples(&samples[i * 5..i * 5 + 5])
+            })
+            .collect();
+        let file = attached_file(&segments);
+        let (bin, hdr) = dettached_file(&segments);
+
+        let mut attached = AttachedHeader::new(Cursor::new(file.clone()));
+        let mut skipping = AttachedHeader::new(Cursor::new(file));
+        skipping.set_skip_extra_dict(true);
+        let expected = attached.load_all_headers().unwrap();
+        let headers = skipping.load_all_headers().unwrap();
+        assert_eq!(headers.len(), 4);
+        for (h, e) in headers.iter().zip(&expected) {
+            assert_eq!(*h.extra_dict, Tag::Null());
+            assert_eq!((h.abs_pos, h.bytes), (e.abs_pos, e.bytes));
+        }
+        let mut buf = [0i16; 30];
+        assert_eq!(skipping.read_samples(&mut buf).unwrap(), 20);
+        assert_eq!(buf[..20], samples[..]);
+
+        let mut dettached = DettachedHeader::new(Cursor::new(bin), Cursor::new(hdr));
+        dettached.set_skip_extra_dict(true);
+        let headers = dettached.load_all_headers().unwrap();
+        assert_eq!(headers.len(), 4);
+        assert!(headers.iter().all(|h| *h.extra_dict == Tag::Null()));
+        let mut buf = [0i16; 30];
+        assert_eq!(dettached.read_samples(&mut buf).unwrap(), 20);
+        assert_eq!(buf[..20], samples[..]);
+    }
+
+    #[test]
+    fn read_samples_with_callback() {
+        let segments = [
+            TestSegment::bytes(10),
+            TestSegment::bytes(20),
+            TestSegment::bytes(30),
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+
+        let mut entered = Vec::new();
+        let mut buf = [0i8; 15];
+        assert_eq!(
+            reader
+                .read_samples_with(&mut buf, |h| entered.push(h.bytes))
+                .unwrap(),
+            15
+        );
+        assert_eq!(entered, [10, 20]);
+
+        // Continuing within the second segment doesn't enter it again
+        let mut buf = [0i8; 100];
+        assert_eq!(
+            reader
+                .read_samples_with(&mut buf, |h| entered.push(h.bytes))
+                .unwrap(),
+            45
+        );
+        assert_eq!(entered, [10, 20, 30]);
+    }
+
+    /// Header of a `complex` segment at 32 kHz with no extra dict, byte by byte as written by the
+    /// File Meta Sink of GNU Radio 3.10.9 (keys are in reverse order of insertion, and the empty
+    /// extra dict is a single Null).
+    const GR_3_10_HEADER: [u8; 150] = [
+        0x09, 0x07, 0x02, 0x00, 0x05, 0x62, 0x79, 0x74, 0x65, 0x73, 0x0b, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x09, 0x07, 0x02, 0x00, 0x04, 0x73, 0x74, 0x72, 0x74, 0x0b, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x96, 0x09, 0x07, 0x02, 0x00, 0x04, 0x63, 0x70, 0x6c,
+        0x78, 0x00, 0x09, 0x07, 0x02, 0x00, 0x04, 0x74, 0x79, 0x70, 0x65, 0x03, 0x00, 0x00, 0x00,
+        0x05, 0x09, 0x07, 0x02, 0x00, 0x04, 0x73, 0x69, 0x7a, 0x65, 0x03, 0x00, 0x00, 0x00, 0x08,
+        0x09, 0x07, 0x02, 0x00, 0x07, 0x72, 0x78, 0x5f, 0x74, 0x69, 0x6d, 0x65, 0x0c, 0x00, 0x00,
+        0x00, 0x02, 0x0b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x07, 0x02, 0x00, 0x07, 0x72, 0x78, 0x5f, 0x72, 0x61,
+        0x74, 0x65, 0x04, 0x40, 0xdf, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x07, 0x02, 0x00,
+        0x07, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x03, 0x00, 0x00, 0x00, 0x00, 0x06, 0x06,
+    ];
+
+    fn gr_3_10_header(bytes: u8, rx_time_frac: f64) -> Vec<u8> {
+        let mut header = GR_3_10_HEADER.to_vec();
+        // Last byte of the "bytes" value, and the fractional part of "rx_time"
+        header[18] = bytes;
+        header[102..110].copy_from_slice(&rx_time_frac.to_be_bytes());
+        header
+    }
+
+    #[test]
+    fn strt_semantics_gnuradio_3_10() {
+        let first: Vec<Complex<f32>> = (0..8).map(|i| Complex::new(i as f32, 0.0)).collect();
+        let second: Vec<Complex<f32>> = (8..12).map(|i| Complex::new(i as f32, 0.0)).collect();
+        let as_bytes = |s: &[Complex<f32>]| -> Vec<u8> {
+            s.iter()
+                .flat_map(|c| [c.re.to_ne_bytes(), c.im.to_ne_bytes()].concat())
+                .collect()
+        };
+
+        let mut file = gr_3_10_header(64, 0.0);
+        file.extend(as_bytes(&first));
+        file.extend(gr_3_10_header(32, 8.0 / 32000.0));
+        file.extend(as_bytes(&second));
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!(headers[0].strt, 150);
+        assert_eq!((headers[0].pos_in_file, headers[0].abs_pos), (0, 150));
+        assert_eq!((headers[1].pos_in_file, headers[1].abs_pos), (214, 364));
+        let mut buf = [Complex::default(); 12];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 12);
+        assert_eq!(buf[..8], first[..]);
+        assert_eq!(buf[8..], second[..]);
+
+        let mut hdr = gr_3_10_header(64, 0.0);
+        hdr.extend(gr_3_10_header(32, 8.0 / 32000.0));
+        let mut bin = as_bytes(&first);
+        bin.extend(as_bytes(&second));
+        let mut reader = DettachedHeader::new(Cursor::new(bin), Cursor::new(hdr));
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!((headers[0].pos_in_file, headers[0].abs_pos), (0, 0));
+        assert_eq!((headers[1].pos_in_file, headers[1].abs_pos), (150, 64));
+        assert_eq!(headers[1].header_byte_len(), 0);
+        let mut buf = [Complex::default(); 12];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 12);
+        assert_eq!(buf[..8], first[..]);
+        assert_eq!(buf[8..], second[..]);
+    }
+
+    #[test]
+    fn from_bytes_in_memory() {
+        let samples: Vec<i32> = (0..50).collect();
+        let segments = [TestSegment::samples(&samples)];
+        let mut buf = [0i32; 64];
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 50);
+        assert_eq!(buf[..50], samples[..]);
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin.as_slice(), hdr);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 50);
+        assert_eq!(buf[..50], samples[..]);
+    }
+
+    #[test]
+    fn dropouts_continuous_and_gapped() {
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(100),
+            TestSegment::bytes(100),
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        assert!(reader.dropouts().unwrap().is_empty());
+        let headers = reader.load_all_headers().unwrap();
+        assert!(headers[1].gap_to(&headers[0]).as_secs_f64().abs() < 1e-9);
+
+        // 250 us dropped before the third segment
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(100),
+            TestSegment {
+                rx_time: Some((0, 450e-6)),
+                ..TestSegment::bytes(100)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let dropouts = reader.dropouts().unwrap();
+        assert_eq!(dropouts.len(), 1);
+        assert_eq!(dropouts[0].0, 200);
+        assert!((dropouts[0].1.as_secs_f64() - 250e-6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn read_run_stop_reasons() {
+        let samples: Vec<i16> = (0..10).collect();
+        let segments = [
+            TestSegment::samples(&samples),
+            TestSegment::samples(&samples),
+            TestSegment {
+                rx_time: Some((1, 0.0)),
+                ..TestSegment::samples(&samples)
+            },
+            TestSegment {
+                rx_rate: 2e6,
+                ..TestSegment::samples(&samples)
+            },
+            TestSegment {
+                rx_rate: 2e6,
+                ..TestSegment::samples(&[0i32; 10])
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+
+        let mut buf = [0i16; 15];
+        assert_eq!(
+            reader.read_run(&mut buf).unwrap(),
+            (15, StopReason::BufferFull)
+        );
+        assert_eq!(
+            reader.read_run(&mut buf).unwrap(),
+            (5, StopReason::TimeDiscontinuity)
+        );
+        reader
+            .seek(SeekFrom::Start(20), SeekPreserve::None)
+            .unwrap();
+        assert_eq!(
+            reader.read_run(&mut buf).unwrap(),
+            (10, StopReason::SampleRateChange)
+        );
+        reader
+            .seek(SeekFrom::Start(30), SeekPreserve::None)
+            .unwrap();
+        assert_eq!(
+            reader.read_run(&mut buf).unwrap(),
+            (10, StopReason::FormatChange)
+        );
+        reader
+            .seek(SeekFrom::Start(40), SeekPreserve::None)
+            .unwrap();
+        assert_eq!(
+            reader.read_run(&mut buf).unwrap(),
+            (0, StopReason::NotDirectlyReadable)
+        );
+        let mut ints = [0i32; 15];
+        assert_eq!(reader.read_run(&mut ints).unwrap(), (10, StopReason::Eof));
+    }
+
+    #[test]
+    fn read_gap_filled_zeros() {
+        let first: Vec<f32> = (1..=10).map(|i| i as f32).collect();
+        let second: Vec<f32> = (11..=20).map(|i| i as f32).collect();
+        // 25 samples missing between both segments
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment {
+                rx_time: Some((0, 35e-6)),
+                ..TestSegment::samples(&second)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+
+        let mut buf = [-1f32; 20];
+        assert_eq!(reader.read_gap_filled(&mut buf).unwrap(), 20);
+        assert_eq!(buf[..10], first[..]);
+        assert!(buf[10..].iter().all(|&v| v == 0.0));
+
+        // Resumes mid-gap
+        let mut buf = [-1f32; 30];
+        assert_eq!(reader.read_gap_filled(&mut buf).unwrap(), 25);
+        assert!(buf[..15].iter().all(|&v| v == 0.0));
+        assert_eq!(buf[15..25], second[..]);
+        assert_eq!(reader.read_gap_filled(&mut buf).unwrap(), 0);
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin, hdr);
+        let mut buf = [-1f32; 50];
+        assert_eq!(reader.read_gap_filled(&mut buf).unwrap(), 45);
+        assert_eq!(buf.iter().filter(|&&v| v == 0.0).count(), 25);
+        assert_eq!(buf[35..45], second[..]);
+    }
+
+    #[test]
+    fn header_for_sample_mixed_dtypes() {
+        let shorts: Vec<i16> = (0..10).collect();
+        let floats: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let file = attached_file(&[TestSegment::samples(&shorts), TestSegment::samples(&floats)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let first = reader.get_header_for_sample(5).unwrap().unwrap();
+        assert_eq!(first.dtype, DataType::Short);
+        assert_eq!(reader.get_header_for_sample(9).unwrap().unwrap(), first);
+
+        let second = reader.get_header_for_sample(10).unwrap().unwrap();
+        assert_eq!(second.dtype, DataType::Float);
+        assert_eq!(reader.get_header_for_sample(29).unwrap().unwrap(), second);
+
+        assert!(reader.get_header_for_sample(30).unwrap().is_none());
+    }
+
+    #[test]
+    fn sample_index_ranges() {
+        let segments = [10, 0, 20, 30].map(TestSegment::bytes);
+        let (bin, hdr) = dettached_file(&segments);
+        let attached = AttachedHeader::from_bytes(attached_file(&segments)).load_all_headers();
+        let dettached = DettachedHeader::from_bytes(bin, hdr).load_all_headers();
+
+        for headers in [attached.unwrap(), dettached.unwrap()] {
+            let ranges: Vec<_> = headers.iter().map(Header::sample_index_range).collect();
+            assert_eq!(ranges, [0..10, 10..10, 10..30, 30..60]);
+        }
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let header = reader.get_header_for_sample(45).unwrap().unwrap();
+        assert_eq!(header.first_sample, 30);
+        let segment: Vec<_> = reader.segments().map(|h| h.unwrap().first_sample).collect();
+        assert_eq!(segment, [0, 10, 10, 30]);
+    }
+
+    #[test]
+    fn read_deinterleaved_two_channels() {
+        let samples: Vec<f32> = (0..21)
+            .map(|i| {
+                if i % 2 == 0 {
+                    i as f32 / 2.0
+                } else {
+                    100.0 + (i / 2) as f32
+                }
+            })
+            .collect();
+        let file = attached_file(&[TestSegment::samples(&samples)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut out = vec![vec![0.0f32; 8]; 2];
+        assert_eq!(reader.read_deinterleaved(2, &mut out).unwrap(), 8);
+        assert_eq!(out[0], (0..8).map(|i| i as f32).collect::<Vec<_>>());
+        assert_eq!(out[1], (100..108).map(|i| i as f32).collect::<Vec<_>>());
+
+        // Only 5 samples remain, the odd one is not read
+        assert_eq!(reader.read_deinterleaved(2, &mut out).unwrap(), 2);
+        assert_eq!(out[0][..2], [8.0, 9.0]);
+        assert_eq!(out[1][..2], [108.0, 109.0]);
+        let mut rest = [0.0f32; 4];
+        assert_eq!(reader.read_samples(&mut rest).unwrap(), 1);
+        assert_eq!(rest[0], 10.0);
+
+        assert!(matches!(
+            reader.read_deinterleaved(3, &mut out),
+            Err(MetaFileError::InvalidArgument(_))
+        ));
+
+        // The partial set of channels spans two segments, with a header in between
+        let samples: Vec<f32> = (0..7).map(|i| i as f32).collect();
+        let segments = [
+            TestSegment::samples(&samples[..6]),
+            TestSegment::samples(&samples[6..]),
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let mut out = vec![vec![0.0f32; 2]; 4];
+        assert_eq!(reader.read_deinterleaved(4, &mut out).unwrap(), 1);
+        assert_eq!(reader.read_samples(&mut rest).unwrap(), 3);
+        assert_eq!(rest[..3], [4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn invalid_header_error_source() {
+        use std::error::Error;
+
+        let mut dict = BTreeMap::new();
+        dict.insert("rx_rate".to_string(), Tag::Double(1.0));
+        let mut hdr = Vec::new();
+        write(&mut hdr, &Tag::Dict(dict)).unwrap();
+        write(&mut hdr, &Tag::Null()).unwrap();
+        let mut reader = DettachedHeader::from_bytes(Vec::new(), hdr);
+
+        let err = reader.load_all_headers().unwrap_err();
+        assert!(matches!(
+            err,
+            MetaFileError::InvalidHeaderError(InvalidHeaderError::MissingField("rx_time"))
+        ));
+        assert_eq!(err.to_string(), "Invalid header error");
+        let source = err.source().unwrap();
+        assert!(source.is::<InvalidHeaderError>());
+        assert_eq!(source.to_string(), "Missing field rx_time in header");
+        assert!(source.source().is_none());
+    }
+
+    #[test]
+    fn iterate_header_file() {
+        let (_, hdr) = dettached_file(&[10, 20, 30].map(TestSegment::bytes));
+
+        let mut count = 0;
+        for tags in HeaderFileIter::new(Cursor::new(&hdr)) {
+            let (main, extra) = tags.unwrap();
+            assert!(matches!(main, Tag::Dict(_)));
+            assert_eq!(extra, Tag::Null());
+            count += 1;
+        }
+        assert_eq!(count, 3);
+
+        // Truncated files yield an error, and then stop
+        let mut iter = HeaderFileIter::new(Cursor::new(&hdr[..hdr.len() - 4]));
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn sample_rate_change() {
+        let segments = [
+            TestSegment::bytes(10),
+            TestSegment::bytes(20),
+            // Empty segments don't start a run, whatever their rate
+            TestSegment {
+                rx_rate: 5e6,
+                ..TestSegment::bytes(0)
+            },
+            TestSegment {
+                rx_rate: 2e6,
+                ..TestSegment::bytes(30)
+            },
+            // Same rate as the previous segment, but for the last bit
+            TestSegment {
+                rx_rate: f64::from_bits(2e6f64.to_bits() + 1),
+                ..TestSegment::bytes(40)
+            },
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        assert_eq!(
+            reader.sample_rate_segments().unwrap(),
+            vec![(0, 1e6), (30, 2e6)]
+        );
+
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments[..2])));
+        assert_eq!(reader.sample_rate_segments().unwrap(), vec![(0, 1e6)]);
+    }
+
+    #[test]
+    fn tag_changes() {
+        let timemark = |secs: u64| {
+            let pair = Tag::Pair(Box::new(Tag::UInt64(secs)), Box::new(Tag::Double(0.25)));
+            Tag::Dict([("timemark".to_string(), pair)].into())
+        };
+        let changing: Vec<TestSegment> = (0..4)
+            .map(|i| TestSegment {
+                extra: timemark(1000 + i),
+                ..TestSegment::bytes(10)
+            })
+            .collect();
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&changing)));
+        assert_eq!(reader.segments_with_tag_changes().unwrap().len(), 4);
+
+        let constant: Vec<TestSegment> = (0..4)
+            .map(|_| TestSegment {
+                extra: timemark(1000),
+                ..TestSegment::bytes(10)
+            })
+            .collect();
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&constant)));
+        let changes = reader.segments_with_tag_changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].abs_pos,
+            reader.get_header_for_byte(0).unwrap().unwrap().abs_pos
+        );
+    }
+
+    #[test]
+    fn read_conv_upcast() {
+        let shorts: Vec<i16> = vec![-32768, -1, 0, 1, 32767];
+        let file = attached_file(&[TestSegment::samples(&shorts)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [0.0f64; 8];
+        assert_eq!(reader.read_conv(&mut buf).unwrap(), 5);
+        assert_eq!(buf[..5], [-32768.0, -1.0, 0.0, 1.0, 32767.0]);
+
+        // Down-casting is not allowed
+        let file = attached_file(&[TestSegment::samples(&shorts)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        let mut buf = [0i8; 8];
+        assert_eq!(reader.read_conv(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn read_conv_checked_lossy() {
+        let doubles = vec![1.0, 1e300, -1e300, 0.1, f64::INFINITY];
+        let file = attached_file(&[TestSegment::samples(&doubles)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+
+        let mut buf = [0.0f32; 5];
+        let (num, stats) = reader.read_conv_checked(&mut buf).unwrap();
+        assert_eq!(num, 5);
+        assert_eq!(stats.clipped, 2);
+        assert_eq!(stats.rounded, 1);
+        assert_eq!(buf[1], f32::INFINITY);
+        assert_eq!(buf[2], f32::NEG_INFINITY);
+
+        // Exact conversion is never lossy
+        let file = attached_file(&[TestSegment::samples(&doubles)]);
+        let mut reader = AttachedHeader::new(Cursor::new(file));
+        let mut buf = [0.0f64; 5];
+        let (num, stats) = reader.read_conv_checked(&mut buf).unwrap();
+        assert_eq!(num, 5);
+        assert_eq!(stats, ConversionStats::default());
+    }
+
+    #[test]
+    fn read_conv_f64_to_f32_exact() {
+        let one_ulp = f32::EPSILON as f64;
+        let doubles = vec![
+            // Halfway between two floats, rounded to the even one
+            1.0 + one_ulp / 2.0,
+            1.0 + one_ulp * 1.5,
+            // Not halfway, rounded to the nearest
+            1.0 + one_ulp * 0.75,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::MAX,
+            // Subnormal as f64, flushed to zero as f32
+            1e-310,
+            // Normal as f64, subnormal as f32
+            1e-40,
+            -0.0,
+            f64::NAN,
+        ];
+        let file = attached_file(&[TestSegment::samples(&doubles)]);
+        let mut reader = AttachedHeader::from_bytes(file);
+
+        let mut buf = [0.0f32; 10];
+        let (num, stats) = reader.read_conv_checked(&mut buf).unwrap();
+        assert_eq!(num, 10);
+        let expected = [
+            1.0,
+            1.0 + 2.0 * f32::EPSILON,
+            1.0 + f32::EPSILON,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::INFINITY,
+            0.0,
+            1e-40,
+        ];
+        for (out, expected) in buf.iter().zip(expected) {
+            assert_eq!(out.to_bits(), expected.to_bits());
+        }
+        assert!(buf[7].is_subnormal());
+        assert_eq!(buf[8].to_bits(), (-0.0f32).to_bits());
+        assert!(buf[9].is_nan());
+        // f64::MAX is clipped, and the values between two floats and the tiny ones rounded
+        assert_eq!(
+            stats,
+            ConversionStats {
+                clipped: 1,
+                rounded: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn copy_payload_attached() {
+        let shorts: Vec<i16> = (0..100).collect();
+        let segments = [
+            TestSegment::samples(&shorts),
+            TestSegment::samples(&shorts[..7]),
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+
+        let header = reader.get_header_for_byte(0).unwrap().unwrap();
+        assert_eq!(header.header_byte_len(), header.strt);
+        assert_eq!(header.abs_pos, header.header_byte_len());
+
+        let mut payload = Vec::new();
+        assert_eq!(reader.copy_payload(&mut payload).unwrap(), 107 * 2);
+        assert_eq!(payload.len(), 107 * 2);
+        let (bin, _) = dettached_file(&segments);
+        assert_eq!(payload, bin);
+
+        // Dettached files have no inline headers
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::new(Cursor::new(bin), Cursor::new(hdr));
+        let header = reader.get_header_for_byte(0).unwrap().unwrap();
+        assert_eq!(header.header_byte_len(), 0);
+    }
+
+    #[test]
+    fn rx_time_modes() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(100)];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        assert_eq!(reader.detect_rx_time_mode().unwrap(), RxTimeMode::Relative);
+        assert_eq!(reader.get_rx_time_mode(), RxTimeMode::Relative);
+
+        let second = reader.get_header_for_sample(150).unwrap().unwrap();
+        let t = reader.relative_sample_time(&second, 50).unwrap();
+        assert!((t.as_secs_f64() - 150e-6).abs() < 1e-9);
+        assert!(reader.absolute_sample_time(&second, 50).is_none());
+
+        let segments = [
+            TestSegment {
+                rx_time: Some((1_700_000_000, 0.25)),
+                ..TestSegment::bytes(100)
+            },
+            TestSegment::bytes(100),
+        ];
+        let mut reader = AttachedHeader::new(Cursor::new(attached_file(&segments)));
+        let mode = reader.detect_rx_time_mode().unwrap();
+        assert_eq!(mode, RxTimeMode::AbsoluteUnix);
+        reader.set_rx_time_mode(mode);
+
+        let second = reader.get_header_for_sample(150).unwrap().unwrap();
+        let t = reader.relative_sample_time(&second, 50).unwrap();
+        assert!((t.as_secs_f64() - 150e-6).abs() < 1e-9);
+        let abs = reader.absolute_sample_time(&second, 50).unwrap();
+        assert!((abs.as_secs_f64() - (1_700_000_000.25 + 150e-6)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn into_inner() {
+        let segments = [TestSegment::bytes(100)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let mut buf = [0i8; 10];
+        reader.read_samples(&mut buf).unwrap();
+        let mut file = reader.into_inner();
+        let mut byte = [0];
+        file.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 10);
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin, hdr);
+        reader.read_samples(&mut buf).unwrap();
+        let (mut bin, hdr) = reader.into_inner();
+        bin.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], 10);
+        assert_eq!(hdr.get_ref().len() as u64, hdr.position());
+    }
+
+    #[test]
+    fn build_and_use_index() {
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(0),
+            TestSegment {
+                extra: Tag::Symbol("tagged".into()),
+                ..TestSegment::bytes(50)
+            },
+            TestSegment {
+                rx_rate: 2e6,
+                ..TestSegment::bytes(30)
+            },
+        ];
+
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin.clone(), hdr);
+        let index = reader.build_index().unwrap();
+        // The empty segment is indexed too, as in attached files
+        assert_eq!(index.segments.len(), 4);
+        assert_eq!(index.segments[1].byte_range, 100..100);
+        assert_eq!(index.segments[3].first_sample, 150);
+        assert_eq!(index.segments[3].byte_range, 150..180);
+
+        let mut serialized = Vec::new();
+        index.write(&mut serialized).unwrap();
+        let read_back = FileIndex::read(&mut serialized.as_slice()).unwrap();
+        assert_eq!(read_back, index);
+
+        // With an empty header file, any scan would find no segments
+        let mut reader =
+            DettachedHeader::with_index(Cursor::new(bin), Cursor::new(vec![]), &read_back).unwrap();
+        reader
+            .seek(SeekFrom::Start(155), SeekPreserve::None)
+            .unwrap();
+        let mut buf = [0i8; 40];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 25);
+        assert_eq!(buf[0], 5);
+        assert_eq!(
+            reader.get_header_for_sample(120).unwrap(),
+            index.headers().nth(2)
+        );
+
+        // Attached, with the headers of the file corrupted so they can't be scanned
+        let mut file = attached_file(&segments);
+        let index = AttachedHeader::from_bytes(file.clone())
+            .build_index()
+            .unwrap();
+        assert_eq!(index.segments.len(), 4);
+        for segment in &index.segments {
+            file[segment.header_pos as usize] = 0xff;
+        }
+        let mut reader = AttachedHeader::with_index(Cursor::new(file), &index).unwrap();
+        reader
+            .seek(SeekFrom::Start(155), SeekPreserve::None)
+            .unwrap();
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 25);
+        assert_eq!(buf[0], 5);
+    }
+
+    #[test]
+    fn corrupt_index() {
+        let file = attached_file(&[TestSegment::bytes(100), TestSegment::bytes(50)]);
+        let index = AttachedHeader::from_bytes(file.clone())
+            .build_index()
+            .unwrap();
+
+        let read_back = |index: &FileIndex| {
+            let mut serialized = Vec::new();
+            index.write(&mut serialized).unwrap();
+            FileIndex::read(&mut serialized.as_slice())
         };
-        let extra = parse(&mut self.header_file)?;
-        let header = Header::from_tags(start_byte, header_tag, extra)?;
-        Ok(Some(header))
+        let check = |corrupt: fn(&mut SegmentInfo), field: &str| {
+            let mut index = index.clone();
+            corrupt(&mut index.segments[1]);
+            assert!(matches!(
+                read_back(&index),
+                Err(MetaFileError::InvalidIndex(f)) if f == field
+            ));
+        };
+        check(
+            |s| s.byte_range = s.byte_range.end..s.byte_range.start,
+            "end",
+        );
+        check(|s| s.num_samples += 1, "num_samples");
+        check(|s| s.samp_rate = 0.0, "rx_rate");
+        check(|s| s.samp_rate = -1.0, "rx_rate");
+        check(|s| s.samp_rate = f64::NAN, "rx_rate");
+
+        // An index of an attached file can't be used for a dettached one, or the other way around
+        assert!(matches!(
+            DettachedHeader::with_index(Cursor::new(vec![]), Cursor::new(vec![]), &index),
+            Err(MetaFileError::InvalidIndex("attached"))
+        ));
+        let dettached = FileIndex {
+            attached: false,
+            ..index
+        };
+        assert!(matches!(
+            AttachedHeader::with_index(Cursor::new(file), &dettached),
+            Err(MetaFileError::InvalidIndex("attached"))
+        ));
     }
-}
 
-impl<B: Read + Seek, H: Read + Seek> SampleReadSeek for DettachedHeader<B, H> {
-    fn get_header_reader_mut(&mut self) -> &mut impl HeaderReader {
-        self
+    #[test]
+    fn position_samples_and_time() {
+        fn check(reader: &mut impl SampleReadSeek) {
+            assert_eq!(reader.position_samples().unwrap(), 0);
+            assert_eq!(reader.position_time().unwrap(), Some(Timestamp::ZERO));
+
+            let mut buf = [0i8; 30];
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 30);
+            assert_eq!(reader.position_samples().unwrap(), 30);
+            let t = reader.position_time().unwrap().unwrap();
+            assert!((t.as_secs_f64() - 30e-6).abs() < 1e-9);
+
+            // Right at the end of the first segment, the next sample is in the third
+            reader
+                .seek(SeekFrom::Start(100), SeekPreserve::None)
+                .unwrap();
+            assert_eq!(reader.position_samples().unwrap(), 100);
+            let t = reader.position_time().unwrap().unwrap();
+            assert!((t.as_secs_f64() - 100e-6).abs() < 1e-9);
+
+            reader.seek(SeekFrom::End(0), SeekPreserve::None).unwrap();
+            assert_eq!(reader.position_samples().unwrap(), 150);
+            assert_eq!(reader.position_time().unwrap(), None);
+        }
+
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(0),
+            TestSegment::bytes(50),
+        ];
+        check(&mut AttachedHeader::from_bytes(attached_file(&segments)));
+        let (bin, hdr) = dettached_file(&segments);
+        check(&mut DettachedHeader::from_bytes(bin, hdr));
     }
 
-    fn get_sample_reader_mut(&mut self) -> &mut (impl Read + Seek) {
-        &mut self.binary_file
+    #[test]
+    fn flatten_timeline_with_gap() {
+        let first: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let second: Vec<f32> = (100..150).map(|i| i as f32).collect();
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment {
+                rx_time: Some((1, 0.0)),
+                ..TestSegment::samples(&second)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let (samples, map) = reader.flatten_timeline::<f32>().unwrap();
+        assert_eq!(reader.position_samples().unwrap(), 0);
+
+        assert_eq!(samples, [first, second].concat());
+        assert_eq!(map.len(), 2);
+        assert_eq!(map[1].source_first_sample, 100);
+        assert_eq!(map[1].first_sample, 100);
+        assert_eq!(map[1].num_samples, 50);
+        assert_eq!(map[1].rx_time, Timestamp::from_parts(1, 0.0));
+        // The gap of almost a second is removed
+        assert!((map[1].timeline_time.as_secs_f64() - 100e-6).abs() < 1e-9);
+
+        assert!(matches!(
+            reader.flatten_timeline::<i16>(),
+            Err(MetaFileError::WrongSampleType { .. })
+        ));
     }
-}
 
-#[cfg(test)]
-mod core_tests {
-    use super::*;
-    use std::fs::File;
+    #[test]
+    fn stats_of_ramp() {
+        // A ramp from -50 to 5149, longer than a block, with a segment of other format in between
+        let first: Vec<f32> = (-50..5000).map(|i| i as f32).collect();
+        let second: Vec<f32> = (5000..5150).map(|i| i as f32).collect();
+        let shorts: Vec<i16> = vec![30000; 10];
+        let segments = [
+            TestSegment::samples(&first),
+            TestSegment::samples(&shorts),
+            TestSegment::samples(&second),
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let stats = reader.stats::<f32>().unwrap();
+        assert_eq!(reader.position_samples().unwrap(), 0);
 
-    /// Returns the binary file (always) and the header file if it exists
-    fn get_or_run_gnuradio(file: &'static str) -> (File, Option<File>) {
-        use std::path::Path;
-        use std::process::Command;
+        let values: Vec<f64> = (-50..5150).map(|i| i as f64).collect();
+        let mean_square = values.iter().map(|v| v * v).sum::<f64>() / values.len() as f64;
+        assert_eq!(stats.count, 5200);
+        assert_eq!(stats.min, -50.0);
+        assert_eq!(stats.max, 5149.0);
+        assert!((stats.mean - 2549.5).abs() < 1e-9);
+        assert!((stats.rms - mean_square.sqrt()).abs() < 1e-9);
 
-        let src_path = format!("test_files/{}.grc", file);
-        let src_path_py = format!("target/test_files/{}.py", file);
-        let dst_path = format!("target/test_files/{}", file);
-        // not always generated
-        let dst_path_hdr = format!("target/test_files/{}.grh", file);
+        // Complex samples contribute their magnitude
+        let cplx = [Complex::new(3.0f64, 4.0), Complex::new(0.0, -1.0)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&[TestSegment::samples(&cplx)]));
+        let stats = reader.stats::<Complex<f64>>().unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 5.0, 3.0));
+        assert!((stats.rms - 13f64.sqrt()).abs() < 1e-12);
 
-        // Compile the file
-        let cout = Command::new("grcc")
-            .args([src_path.as_str(), "-o", "target/test_files/"])
-            .output()
-            .unwrap_or_else(|_| panic!("failed to run GNU radio compiler on {}", src_path));
-        if !cout.status.success() {
-            panic!(
-                "GNU radio compiler failed on file {}, error: {}",
-                src_path,
-                String::from_utf8(cout.stderr).unwrap()
-            )
+        assert_eq!(reader.stats::<f32>().unwrap().count, 0);
+    }
+
+    #[test]
+    fn read_samples_meta_first_header() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let mut buf = [0i8; 60];
+
+        let (n, header) = reader.read_samples_meta(&mut buf).unwrap();
+        assert_eq!(n, 60);
+        assert_eq!(header, reader.get_last_read_header().unwrap());
+
+        // Crosses into the second segment, but the header is of the first sample read
+        let (n, header) = reader.read_samples_meta(&mut buf).unwrap();
+        assert_eq!(n, 60);
+        assert_eq!(header, reader.get_header_at_index(0).unwrap());
+        assert_ne!(header, reader.get_last_read_header().unwrap());
+
+        let (n, header) = reader.read_samples_meta(&mut buf).unwrap();
+        assert_eq!(n, 30);
+        assert_eq!(header, reader.get_header_at_index(1).unwrap());
+
+        assert_eq!(reader.read_samples_meta(&mut buf).unwrap(), (0, None));
+    }
+
+    #[test]
+    fn read_samples_resume_after_stops() {
+        let segments = [
+            TestSegment::samples(&[1.0f32, 2.0]),
+            TestSegment::samples(&[3i16]),
+            TestSegment {
+                rx_time: Some((1, 0.0)),
+                ..TestSegment::samples(&[4.0f32, 5.0, 6.0])
+            },
+        ];
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(&bin, &hdr);
+        let mut buf = [0f32; 2];
+        assert_eq!(reader.read_samples_resume(&mut buf).unwrap().0, 2);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 0);
+
+        // Skips the short segment, and reads across the time gap after it
+        let (n, header) = reader.read_samples_resume(&mut buf).unwrap();
+        assert_eq!((n, buf), (2, [4.0, 5.0]));
+        assert_eq!(header, reader.get_header_at_index(2).unwrap());
+        assert_eq!(reader.get_last_read_header().unwrap(), header);
+        assert_eq!(reader.position_samples().unwrap(), 5);
+
+        assert_eq!(reader.read_samples_resume(&mut buf).unwrap().0, 1);
+        assert_eq!(reader.read_samples_resume(&mut buf).unwrap(), (0, None));
+    }
+
+    #[test]
+    fn overlapping_segments() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let file = attached_file(&segments);
+        let mut index = AttachedHeader::from_bytes(file.clone())
+            .build_index()
+            .unwrap();
+        let reader = AttachedHeader::with_index(Cursor::new(file.clone()), &index).unwrap();
+        reader.get_header_storage().validate().unwrap();
+
+        // The second header claims to start in the middle of the data of the first
+        let first_end = index.segments[0].byte_range.end;
+        index.segments[1].header_pos = first_end - 10;
+        let reader = AttachedHeader::with_index(Cursor::new(file), &index).unwrap();
+        assert!(matches!(
+            reader.get_header_storage().validate(),
+            Err(MetaFileError::OverlappingSegments { prev_end, next_start })
+                if prev_end == first_end && next_start == first_end - 10
+        ));
+
+        let mut storage = HeaderStorage::default();
+        let headers: Vec<Header> = index.headers().collect();
+        storage.add_header_for_byte(0, headers[0].clone()).unwrap();
+        assert!(matches!(
+            storage.add_header_for_byte(first_end - 10, headers[1].clone()),
+            Err(MetaFileError::OverlappingSegments { .. })
+        ));
+    }
+
+    #[test]
+    fn dettached_header_stream() {
+        /// Like a pipe, can only be read forward
+        struct NoSeek<R: Read>(R);
+
+        impl<R: Read> Read for NoSeek<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.read(buf)
+            }
         }
 
-        let out = Command::new("python3")
-            .args([src_path_py.as_str(), "--out", dst_path.as_str()])
-            .output()
-            .unwrap_or_else(|_| panic!("failed to run GNU radio on {}", src_path));
-        if !out.status.success() {
-            panic!(
-                "GNU radio failed on file {}, error: {}",
-                file,
-                String::from_utf8(out.stderr).unwrap(),
-            )
+        let segments = [
+            TestSegment {
+                extra: Tag::Symbol("skipped".into()),
+                ..TestSegment::bytes(100)
+            },
+            TestSegment::bytes(50),
+        ];
+        let (bin, hdr) = dettached_file(&segments);
+        let path = std::env::temp_dir().join(format!(
+            "gnuradio-meta-test-{}-stream.bin",
+            std::process::id()
+        ));
+        std::fs::write(&path, &bin).unwrap();
+
+        for skip_extra_dict in [false, true] {
+            let binary_file = File::open(&path).unwrap();
+            let mut reader = DettachedHeader::new(binary_file, NoSeek(hdr.as_slice()));
+            reader.set_skip_extra_dict(skip_extra_dict);
+            let mut buf = [0i8; 200];
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 150);
+            assert_eq!(buf[100], 0);
+            let second = reader.get_header_at_index(1).unwrap().unwrap();
+            assert_eq!(second.abs_pos, 100);
         }
+        std::fs::remove_file(&path).unwrap();
+    }
 
-        (File::open(dst_path).unwrap(), File::open(dst_path_hdr).ok())
+    #[test]
+    fn dump_tags_and_segments() {
+        let mut extra = BTreeMap::new();
+        extra.insert("rx_freq".to_string(), Tag::Double(1e8));
+        extra.insert("antenna".to_string(), Tag::Symbol("RX2".into()));
+        let segments = [
+            TestSegment {
+                extra: Tag::Dict(extra),
+                ..TestSegment::bytes(100)
+            },
+            TestSegment::bytes(0),
+            TestSegment::bytes(50),
+        ];
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        // Partially loaded headers are not an issue
+        reader.get_header_at_index(1).unwrap();
+        let headers: Vec<Header> = reader.segments().map(Result::unwrap).collect();
+        assert_eq!(headers, reader.load_all_headers().unwrap());
+        assert_eq!(headers.len(), 3);
+
+        let mut out = Vec::new();
+        reader.dump_tags(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        for line in [
+            "segment 0:",
+            "  rx_rate: 1000000",
+            "  type: i8",
+            "  bytes: 100",
+            "  extra antenna: RX2",
+            "  extra rx_freq: 100000000",
+            "segment 2:",
+            "  rx_time: 0.000100000",
+        ] {
+            assert!(lines.contains(&line), "missing {:?} in {}", line, out);
+        }
     }
 
     #[test]
-    fn read_byte_samples_attached() {
-        let (file, _) = get_or_run_gnuradio("bytes_increasing");
-        let mut reader = AttachedHeader::new(file);
-        let mut samples: [u8; 256] = [0; 256];
+    fn offset_stream_tags() {
+        let segment = |burst_offset: u64| {
+            let mut extra = BTreeMap::new();
+            extra.insert("rx_freq".to_string(), Tag::Double(1e8));
+            extra.insert(
+                "burst".to_string(),
+                Tag::Pair(
+                    Box::new(Tag::UInt64(burst_offset)),
+                    Box::new(Tag::Symbol("start".into())),
+                ),
+            );
+            TestSegment {
+                extra: Tag::Dict(extra),
+                ..TestSegment::bytes(100)
+            }
+        };
+        let file = attached_file(&[segment(30), segment(10)]);
+        let burst = |offset| StreamTag {
+            offset,
+            key: "burst".to_string(),
+            value: Tag::Symbol("start".into()),
+        };
+        let rx_freq = |offset| StreamTag {
+            offset,
+            key: "rx_freq".to_string(),
+            value: Tag::Double(1e8),
+        };
 
-        let num_read = reader.read_samples(&mut samples).unwrap();
-        // Basic sample reading checks
-        assert_eq!(num_read, 256);
-        for (i, &samp) in samples.iter().enumerate() {
-            assert_eq!(samp, i as u8);
+        let mut reader = AttachedHeader::from_bytes(&file);
+        reader.set_offset_tag_keys(vec!["burst".to_string()]);
+        let mut buf = [0i8; 50];
+        assert!(reader.get_last_read_tags().unwrap().is_empty());
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(
+            reader.get_last_read_tags().unwrap(),
+            vec![burst(30), rx_freq(0)]
+        );
+        // Crosses into the second segment
+        reader.read_samples(&mut [0i8; 60]).unwrap();
+        assert_eq!(reader.get_last_read_tags().unwrap(), vec![rx_freq(100)]);
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(reader.get_last_read_tags().unwrap(), vec![burst(110)]);
+
+        // Without the key configured, the pair is the value of a tag at the segment start
+        let mut reader = AttachedHeader::from_bytes(&file);
+        reader.read_samples(&mut buf).unwrap();
+        let tags = reader.get_last_read_tags().unwrap();
+        assert_eq!(tags[0].offset, 0);
+        assert!(matches!(tags[0].value, Tag::Pair(..)));
+    }
+
+    #[test]
+    fn find_tags() {
+        let mark = |secs: u64| Tag::Pair(Box::new(Tag::UInt64(secs)), Box::new(Tag::Double(0.5)));
+        let timemark = |secs: u64| Tag::Dict([("timemark".to_string(), mark(secs))].into());
+        let segments = [
+            TestSegment {
+                extra: timemark(1000),
+                ..TestSegment::bytes(100)
+            },
+            TestSegment::bytes(100),
+            TestSegment {
+                extra: timemark(2000),
+                ..TestSegment::bytes(50)
+            },
+            TestSegment::bytes(100),
+        ];
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        assert_eq!(
+            reader.find_first_tag("timemark").unwrap(),
+            Some((0, &mark(1000)))
+        );
+        // Only the first segment was needed
+        assert_eq!(reader.get_header_storage().len(), 1);
+        assert_eq!(reader.find_first_tag("missing").unwrap(), None);
+
+        let all = reader.find_all_tags("timemark").unwrap();
+        assert_eq!(all, [(0, &mark(1000)), (200, &mark(2000))]);
+
+        // Tags of empty segments are kept in dettached files too
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment {
+                extra: timemark(1000),
+                ..TestSegment::bytes(0)
+            },
+            TestSegment::bytes(100),
+        ];
+        let (bin, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(bin, hdr);
+        assert_eq!(
+            reader.find_all_tags("timemark").unwrap(),
+            [(100, &mark(1000))]
+        );
+        assert_eq!(
+            reader.get_header_at_index(2).unwrap().unwrap().first_sample,
+            100
+        );
+    }
+
+    #[test]
+    fn serialized_gr_stream_tags() {
+        // As written by GNU Radio, in the second segment, with absolute offsets
+        let mut as_dict = BTreeMap::new();
+        as_dict.insert("offset".to_string(), Tag::UInt64(130));
+        as_dict.insert("key".to_string(), Tag::Symbol("rx_freq".into()));
+        as_dict.insert("value".to_string(), Tag::Double(1e8));
+        as_dict.insert("srcid".to_string(), Tag::Symbol("usrp".into()));
+        let as_tuple = Tag::Tuple(vec![
+            Tag::Symbol("burst".into()),
+            Tag::Bool(true),
+            Tag::Null(),
+            Tag::UInt64(150),
+        ]);
+        let mut extra = BTreeMap::new();
+        extra.insert("tag0".to_string(), Tag::Dict(as_dict));
+        extra.insert("tag1".to_string(), as_tuple);
+        extra.insert("gain".to_string(), Tag::Double(30.0));
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment {
+                extra: Tag::Dict(extra),
+                ..TestSegment::bytes(100)
+            },
+        ];
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        reader.read_samples(&mut [0i8; 100]).unwrap();
+        assert!(reader.get_last_read_tags().unwrap().is_empty());
+        reader.read_samples(&mut [0i8; 100]).unwrap();
+        assert_eq!(
+            reader.get_last_read_tags().unwrap(),
+            vec![
+                StreamTag {
+                    offset: 100,
+                    key: "gain".to_string(),
+                    value: Tag::Double(30.0),
+                },
+                StreamTag {
+                    offset: 130,
+                    key: "rx_freq".to_string(),
+                    value: Tag::Double(1e8),
+                },
+                StreamTag {
+                    offset: 150,
+                    key: "burst".to_string(),
+                    value: Tag::Bool(true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_offset_of_sample() {
+        let segments = [
+            TestSegment::bytes(10),
+            TestSegment::samples(&[0i16; 5]),
+            TestSegment::bytes(0),
+            TestSegment::samples(&[Complex::<f32>::default(); 3]),
+        ];
+        let expected = |attached: bool| {
+            let header = |i: u64| if attached { 150 * i } else { 0 };
+            [
+                (0, Some(header(1))),
+                (9, Some(header(1) + 9)),
+                (10, Some(header(2) + 10)),
+                (14, Some(header(2) + 10 + 8)),
+                (15, Some(header(4) + 20)),
+                (17, Some(header(4) + 20 + 16)),
+                (18, None),
+            ]
+        };
+
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        for (sample, byte) in expected(true) {
+            assert_eq!(reader.byte_offset_of_sample(sample).unwrap(), byte);
         }
+        let (data, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(data, hdr);
+        for (sample, byte) in expected(false) {
+            assert_eq!(reader.byte_offset_of_sample(sample).unwrap(), byte);
+        }
+    }
 
-        // Public header interface
-        let header = reader.get_last_read_header();
+    #[test]
+    fn skip_samples() {
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(100),
+            TestSegment {
+                rx_time: Some((5, 0.0)),
+                ..TestSegment::bytes(100)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let mut buf = [0i8; 1];
+        assert_eq!(reader.skip_samples(150).unwrap(), 150);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 50);
+        assert_eq!(reader.position_samples().unwrap(), 151);
 
-        // Check sane internal state for headers
+        // Stops at the time discontinuity, and continues past it on the next call, as reading does
+        assert_eq!(reader.skip_samples(1000).unwrap(), 49);
+        assert_eq!(reader.skip_samples(10).unwrap(), 10);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], 10);
+    }
 
-        // Further reads should return nothing
+    #[test]
+    fn read_decimated_ramp() {
+        let first: Vec<f32> = (0..95).map(|i| i as f32).collect();
+        let second: Vec<f32> = (95..120).map(|i| i as f32).collect();
+        let segments = [TestSegment::samples(&first), TestSegment::samples(&second)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+
+        let mut buf = [0.0f32; 20];
+        assert_eq!(reader.read_decimated(&mut buf, 10).unwrap(), 10);
+        let expected: Vec<f32> = (0..10).map(|i| i as f32 * 10.0).collect();
+        assert_eq!(buf[..10], expected);
+        assert_eq!(reader.position_samples().unwrap(), 95);
+
+        // Starts over at the next segment
+        assert_eq!(reader.read_decimated(&mut buf, 10).unwrap(), 3);
+        assert_eq!(buf[..3], [95.0, 105.0, 115.0]);
+        assert_eq!(reader.read_decimated(&mut buf, 10).unwrap(), 0);
+
+        reader.seek(SeekFrom::Start(0), SeekPreserve::None).unwrap();
+        assert_eq!(reader.read_decimated(&mut buf[..4], 1).unwrap(), 4);
+        assert_eq!(buf[..4], [0.0, 1.0, 2.0, 3.0]);
+        assert!(reader.read_decimated(&mut buf, 0).is_err());
+    }
+
+    #[test]
+    fn unfinished_last_segment() {
+        // The last header was never finalized
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(0),
+            TestSegment::bytes(0),
+        ];
+
+        // The trailing samples the sink wrote before the file was copied
+        let mut data = attached_file(&segments);
+        data.extend(0..30u8);
+        let mut reader = AttachedHeader::from_bytes(data);
+        let mut buf = [0i8; 200];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 130);
+        assert_eq!(buf[100..130], (0..30).collect::<Vec<i8>>());
+        // The empty segment in between is still empty
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!(headers[1].bytes, 0);
+        assert_eq!(headers[2].bytes, 30);
+
+        let (mut data, hdr) = dettached_file(&segments);
+        data.extend(0..30u8);
+        let mut reader = DettachedHeader::from_bytes(data, hdr);
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 130);
+        assert_eq!(buf[100..130], (0..30).collect::<Vec<i8>>());
+        assert_eq!(
+            reader.get_header_for_sample(129).unwrap().unwrap().bytes,
+            30
+        );
+    }
+
+    #[test]
+    fn iterate_header_storage() {
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::bytes(50),
+            TestSegment::bytes(10),
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        assert!(reader.get_header_storage().is_empty());
+        reader.get_header_at_index(1).unwrap();
+
+        let storage = reader.get_header_storage();
+        assert_eq!(storage.len(), 2);
+        let loaded: Vec<(u64, u64)> = storage
+            .into_iter()
+            .map(|(byte, header)| (byte, header.bytes))
+            .collect();
+        assert_eq!(loaded, vec![(0, 100), (250, 50)]);
+    }
+
+    #[test]
+    fn read_conv_scaling() {
+        let shorts: Vec<i16> = vec![i16::MIN, 0, i16::MAX];
+        let file = attached_file(&[TestSegment::samples(&shorts)]);
+
+        let mut reader = AttachedHeader::from_bytes(&file);
+        let mut buf = [0.0f32; 3];
+        assert_eq!(reader.read_conv(&mut buf).unwrap(), 3);
+        assert_eq!(buf, [-32768.0, 0.0, 32767.0]);
+
+        let mut reader = AttachedHeader::from_bytes(&file);
+        reader.set_conversion_scaling(ConversionScaling::Normalized);
+        assert_eq!(reader.read_conv(&mut buf).unwrap(), 3);
+        assert_eq!(buf[..2], [-1.0, 0.0]);
+        assert!((buf[2] - 1.0).abs() < 1e-4 && buf[2] < 1.0);
+
+        // Integer up-casts are not scaled
+        reader.seek(SeekFrom::Start(0), SeekPreserve::None).unwrap();
+        let mut buf = [0i32; 3];
+        assert_eq!(reader.read_conv(&mut buf).unwrap(), 3);
+        assert_eq!(buf, [-32768, 0, 32767]);
+    }
+
+    #[test]
+    fn time_offset() {
+        let segments = [
+            TestSegment {
+                rx_time: Some((10, 0.0)),
+                ..TestSegment::bytes(100)
+            },
+            TestSegment::bytes(100),
+        ];
+        let file = attached_file(&segments);
+        let offset = Timestamp::from_secs_f64(-0.5);
+        let close = |a: Timestamp, b: f64| (a.as_secs_f64() - b).abs() < 1e-9;
+
+        // Applies both to headers loaded before and after setting it
+        let mut reader = AttachedHeader::from_bytes(&file);
+        let first = reader.get_header_at_index(0).unwrap().unwrap();
+        assert!(close(first.with_corrected_rx_time(offset).rx_time, 9.5));
+        reader.set_time_offset(offset);
+        let mut buf = [0i8; 150];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 150);
+        assert!(close(
+            reader.get_last_read_rx_time().unwrap().unwrap(),
+            9.5 + 149e-6
+        ));
+        let second = reader.get_header_at_index(1).unwrap().unwrap();
+        assert!(close(second.get_sample_time(0), 9.5 + 100e-6));
+
+        // The index has the times of the file
+        let index = reader.build_index().unwrap();
+        assert!(close(index.segments[0].rx_time, 10.0));
+    }
+
+    #[test]
+    fn seek_within_segment() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(100)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let mut buf = [0i8; 150];
+        reader.read_samples(&mut buf).unwrap();
+
+        assert_eq!(
+            reader
+                .seek_within_segment(0, SeekPreserve::Segment)
+                .unwrap(),
+            100
+        );
+        assert_eq!(
+            reader
+                .seek_within_segment(20, SeekPreserve::Segment)
+                .unwrap(),
+            120
+        );
+        assert_eq!(reader.read_samples(&mut buf[..1]).unwrap(), 1);
+        assert_eq!(buf[0], 20);
+
+        // Leaving the segment is only allowed if preserve permits it
+        assert!(matches!(
+            reader.seek_within_segment(-1, SeekPreserve::Segment),
+            Err(MetaFileError::IncompatibleSeek)
+        ));
+        assert_eq!(reader.position_samples().unwrap(), 121);
+        assert_eq!(
+            reader.seek_within_segment(-1, SeekPreserve::All).unwrap(),
+            99
+        );
+        assert!(matches!(
+            reader.seek_within_segment(-1000, SeekPreserve::None),
+            Err(MetaFileError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn read_samples_uninit() {
+        let samples: Vec<Complex<f32>> = (0..100).map(|i| Complex::new(i as f32, -1.0)).collect();
+        let segments = [
+            TestSegment::samples(&samples),
+            TestSegment::samples(&samples[..30]),
+        ];
+        let file = attached_file(&segments);
+
+        let mut reader = AttachedHeader::from_bytes(&file);
+        let mut zeroed = vec![Complex::<f32>::default(); 200];
+        let num_read = reader.read_samples(&mut zeroed).unwrap() as usize;
+
+        let mut reader = AttachedHeader::from_bytes(&file);
+        let mut uninit = Vec::with_capacity(200);
+        uninit.resize_with(200, MaybeUninit::<Complex<f32>>::uninit);
+        // SAFETY: Cursor doesn't read from the buffers it's given
+        let read = unsafe { reader.read_samples_uninit_slice(&mut uninit) }.unwrap();
+        assert_eq!(read.len(), 130);
+        assert_eq!(read, &zeroed[..num_read]);
+        assert_eq!(
+            unsafe { reader.read_samples_uninit(&mut uninit) }.unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn file_profile() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let byte = Format {
+            dtype: DataType::Byte,
+            complex: false,
+        };
+        assert_eq!(
+            reader.profile().unwrap(),
+            FileProfile {
+                uniform_format: true,
+                uniform_rate: true,
+                gapless: true,
+                format: Some(byte),
+                samp_rate: Some(1e6),
+            }
+        );
+
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment::samples(&[0i16; 300]),
+            TestSegment {
+                rx_rate: 2e6,
+                rx_time: Some((1, 0.0)),
+                ..TestSegment::bytes(100)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let profile = reader.profile().unwrap();
+        assert!(!profile.uniform_format && !profile.uniform_rate && !profile.gapless);
+        assert_eq!(profile.format.unwrap().dtype, DataType::Short);
+        assert_eq!(profile.samp_rate, Some(1e6));
+
+        let mut reader = AttachedHeader::from_bytes(Vec::new());
+        let profile = reader.profile().unwrap();
+        assert!(profile.uniform_format && profile.gapless);
+        assert_eq!(profile.format, None);
+    }
+
+    #[test]
+    fn read_many_tiny_segments() {
+        let mut segments: Vec<TestSegment> = (0..300)
+            .map(|i| TestSegment {
+                data: vec![i as u8],
+                ..TestSegment::bytes(1)
+            })
+            .collect();
+        // Reading must still stop at a time discontinuity
+        segments[200].rx_time = Some((1, 0.0));
+        segments[201].rx_time = Some((1, 1e-6));
+
+        fn check(reader: &mut impl SampleReadSeek) {
+            let expected: Vec<i8> = (0..300).map(|i| i as i8).collect();
+            let read = |reader: &mut _, buf: &mut [i8]| {
+                let mut entered = 0;
+                let num_read = SampleReadSeek::read_samples_with(reader, buf, |_| entered += 1);
+                (num_read.unwrap(), entered)
+            };
+
+            let mut buf = [0i8; 250];
+            assert_eq!(read(reader, &mut buf[..150]), (150, 150));
+            assert_eq!(read(reader, &mut buf[150..]), (50, 50));
+            assert_eq!(buf[..200], expected[..200]);
+            reader
+                .seek(SeekFrom::Start(201), SeekPreserve::None)
+                .unwrap();
+            assert_eq!(read(reader, &mut buf), (99, 99));
+            assert_eq!(buf[..99], expected[201..]);
+        }
+
+        check(&mut AttachedHeader::from_bytes(attached_file(&segments)));
+        let (data, hdr) = dettached_file(&segments);
+        check(&mut DettachedHeader::from_bytes(data, hdr));
+    }
+
+    #[test]
+    fn samples_remaining_in_segment() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        assert_eq!(reader.samples_remaining_in_segment().unwrap(), 100);
+
+        let mut buf = [0i8; 30];
+        reader.read_samples(&mut buf).unwrap();
+        assert_eq!(reader.samples_remaining_in_segment().unwrap(), 70);
+        let remaining = reader.samples_remaining_in_segment().unwrap() as usize;
+        let mut buf = vec![0i8; remaining];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 70);
+        assert_eq!(reader.samples_remaining_in_segment().unwrap(), 0);
+
+        reader.read_samples(&mut [0i8; 10]).unwrap();
+        assert_eq!(reader.samples_remaining_in_segment().unwrap(), 40);
+        reader.read_samples(&mut [0i8; 100]).unwrap();
+        assert_eq!(reader.samples_remaining_in_segment().unwrap(), 0);
+    }
+
+    #[test]
+    fn complex_integer_matrix() {
+        fn check<T: SampleType + Default + PartialEq + std::fmt::Debug>(
+            samples: &[T],
+            dtype: DataType,
+            size: usize,
+        ) {
+            assert_eq!(T::byte_width(), size);
+            assert_eq!(dtype.item_size_bytes(true), size);
+            assert!(dtype.reads_directly_to::<T>(true));
+            assert!(!dtype.reads_directly_to::<T>(false));
+            assert!(T::reads_directly_from(dtype, true));
+            assert!(!T::reads_directly_from(dtype, false));
+
+            let segments = [TestSegment::samples(samples), TestSegment::samples(samples)];
+            assert_eq!(segments[0].size, size as i32);
+            let expected: Vec<T> = samples.iter().chain(samples).copied().collect();
+
+            let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+            let headers = reader.load_all_headers().unwrap();
+            for header in &headers {
+                assert_eq!(
+                    header.format(),
+                    Format {
+                        dtype,
+                        complex: true
+                    }
+                );
+                assert_eq!(header.size as usize, size);
+                assert_eq!(header.get_num_samples(), samples.len() as u64);
+            }
+            assert_eq!(
+                headers[1].abs_pos - headers[0].abs_pos,
+                150 + (samples.len() * size) as u64
+            );
+            let mut out = vec![T::default(); expected.len() + 1];
+            assert_eq!(
+                reader.read_samples(&mut out).unwrap(),
+                expected.len() as u64
+            );
+            assert_eq!(out[..expected.len()], expected);
+
+            let (data, hdr) = dettached_file(&segments);
+            let mut reader = DettachedHeader::from_bytes(data, hdr);
+            assert_eq!(
+                reader.read_samples(&mut out).unwrap(),
+                expected.len() as u64
+            );
+            assert_eq!(out[..expected.len()], expected);
+        }
+
+        check(
+            &[
+                Complex::new(i8::MIN, i8::MAX),
+                Complex::new(-1, 1),
+                Complex::new(0, 5),
+            ],
+            DataType::Byte,
+            2,
+        );
+        check(
+            &[
+                Complex::new(i16::MIN, i16::MAX),
+                Complex::new(-1, 1),
+                Complex::new(0, 5),
+            ],
+            DataType::Short,
+            4,
+        );
+        check(
+            &[
+                Complex::new(i32::MIN, i32::MAX),
+                Complex::new(-1, 1),
+                Complex::new(0, 5),
+            ],
+            DataType::Int,
+            8,
+        );
+    }
+
+    #[test]
+    fn map_segments() {
+        let segments = [
+            TestSegment::bytes(100),
+            TestSegment {
+                rx_time: Some((3, 0.25)),
+                ..TestSegment::bytes(10)
+            },
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let times = reader.map_segments(|h| h.rx_time).unwrap();
+        assert_eq!(times, vec![Timestamp::ZERO, Timestamp::from_parts(3, 0.25)]);
+    }
+
+    #[test]
+    fn unsupported_sample_type() {
+        // Wrongly claims to be a single i32
+        #[derive(Clone, Copy)]
+        struct Wide(#[allow(dead_code)] u128);
+        unsafe impl SampleType for Wide {
+            const DTYPE: DataType = DataType::Int;
+            const COMPLEX: bool = false;
+        }
+
+        let file = attached_file(&[TestSegment::samples(&[1i32, 2, 3, 4])]);
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut buf = [Wide(0); 4];
+        let unsupported =
+            |e| matches!(e, MetaFileError::UnsupportedSampleType(name) if name.ends_with("Wide"));
+        assert!(unsupported(reader.read_samples(&mut buf).unwrap_err()));
+        assert!(unsupported(reader.read_conv(&mut buf).unwrap_err()));
+        let mut buf = [0i32; 4];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 4);
+    }
+
+    #[test]
+    fn data_and_header_ranges() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!(headers[0].header_byte_range(), 0..150);
+        assert_eq!(headers[0].data_file_range(), 150..250);
+        assert_eq!(headers[1].header_byte_range(), 250..400);
+        assert_eq!(headers[1].data_file_range(), 400..450);
+
+        let (data, hdr) = dettached_file(&segments);
+        let mut reader = DettachedHeader::from_bytes(data, hdr);
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!(headers[0].data_file_range(), 0..100);
+        assert_eq!(headers[1].data_file_range(), 100..150);
+        assert_eq!(headers[1].header_byte_range(), 150..300);
+    }
+
+    #[test]
+    fn iter_blocks() {
+        let samples: Vec<i16> = (0..1000).collect();
+        let segments = [
+            TestSegment::samples(&samples[..600]),
+            TestSegment::samples(&samples[600..]),
+            TestSegment::bytes(100),
+        ];
+        let mut reader = AttachedHeader::from_bytes(attached_file(&segments));
+        let blocks: Vec<Vec<i16>> = reader.iter_blocks(256).map(Result::unwrap).collect();
+        let lens: Vec<usize> = blocks.iter().map(Vec::len).collect();
+        assert_eq!(lens, vec![256, 256, 256, 232]);
+        assert_eq!(blocks.concat(), samples);
+
+        // Reading resumes after the stop
+        let blocks: Vec<Vec<i8>> = reader.iter_blocks(256).map(Result::unwrap).collect();
+        assert_eq!(blocks, vec![(0..100).collect::<Vec<i8>>()]);
+        assert_eq!(reader.iter_blocks::<i8>(256).count(), 0);
+    }
+
+    #[test]
+    fn open_auto_layouts() {
+        let segments = [TestSegment::bytes(100), TestSegment::bytes(50)];
+        let dir = std::env::temp_dir();
+        let attached_path = dir.join(format!(
+            "gnuradio-meta-test-{}-auto-a.bin",
+            std::process::id()
+        ));
+        let dettached_path = dir.join(format!(
+            "gnuradio-meta-test-{}-auto-d.bin",
+            std::process::id()
+        ));
+        let mut header_path = dettached_path.clone().into_os_string();
+        header_path.push(".hdr");
+
+        std::fs::write(&attached_path, attached_file(&segments)).unwrap();
+        let (bin, hdr) = dettached_file(&segments);
+        std::fs::write(&dettached_path, bin).unwrap();
+        std::fs::write(&header_path, hdr).unwrap();
+
+        for (path, attached) in [(&attached_path, true), (&dettached_path, false)] {
+            let mut reader = open_auto(path).unwrap();
+            assert_eq!(matches!(reader, Reader::Attached(_)), attached);
+            let mut buf = [0i8; 200];
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 150);
+            assert_eq!(buf[99..101], [99, 0]);
+        }
+
+        for path in [attached_path, dettached_path, header_path.into()] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn raw_header_reader() {
+        // A sink with its own schema, storing the number of samples of each segment
+        let custom_dict = |n: u64| {
+            let mut dict = BTreeMap::new();
+            dict.insert("fs".to_string(), Tag::Double(48000.0));
+            dict.insert("count".to_string(), Tag::UInt64(n));
+            Tag::Dict(dict)
+        };
+        let samples: Vec<f32> = (0..80).map(|i| i as f32).collect();
+        let mut file = Vec::new();
+        for (dict, segment) in [
+            (custom_dict(50), &samples[..50]),
+            (custom_dict(30), &samples[50..]),
+        ] {
+            write(&mut file, &dict).unwrap();
+            write(&mut file, &Tag::Null()).unwrap();
+            file.extend(segment.iter().flat_map(|s| s.to_ne_bytes()));
+        }
+
+        // Rejected by the standard reader
+        assert!(
+            AttachedHeader::from_bytes(&file)
+                .load_all_headers()
+                .is_err()
+        );
+
+        let format = Format {
+            dtype: DataType::Float,
+            complex: false,
+        };
+        let mut reader = RawHeaderReader::new(Cursor::new(&file), format, 48000.0)
+            .with_segment_bytes(|dict, _| Some(dict.get("count")?.get_u64()? * 4));
+        let mut buf = [0.0f32; 100];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 80);
+        assert_eq!(buf[..80], samples);
+
+        let raw = reader.raw_tags().unwrap();
+        assert_eq!(
+            raw,
+            [
+                (custom_dict(50), Tag::Null()),
+                (custom_dict(30), Tag::Null())
+            ]
+        );
+        let second = reader.get_header_at_index(1).unwrap().unwrap();
+        assert_eq!(second.sample_index_range(), 50..80);
+        assert_eq!(second.rx_time, Timestamp::from_secs_f64(50.0 / 48000.0));
+
+        // Without it, the first header is taken to be the only one
+        let mut reader = RawHeaderReader::new(Cursor::new(&file), format, 48000.0);
+        let headers = reader.load_all_headers().unwrap();
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].abs_pos, second.pos_in_file - 200);
+        assert_eq!(headers[0].data_file_range().end, file.len() as u64);
     }
 }
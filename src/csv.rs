@@ -0,0 +1,239 @@
+//! Export of samples as CSV, to quickly inspect a file in a spreadsheet or plotting tool. Enabled
+//! with the `csv` feature.
+
+use std::io::Write;
+
+use num_complex::Complex;
+
+use crate::core::{HeaderReader, MetaFileError, SampleReadSeek};
+use crate::header::SampleType;
+use crate::pmt::Timestamp;
+
+/// Sample types that can be written as CSV columns: one for real samples, re and im for complex
+pub trait CsvSample: SampleType {
+    /// Names of the columns of the value, in the header line
+    const COLUMNS: &'static str;
+
+    /// Writes the value columns of the sample, without separators around them
+    fn write_columns<W: Write>(&self, out: &mut W) -> std::io::Result<()>;
+}
+
+macro_rules! impl_csv_sample {
+    ($t:ty) => {
+        impl CsvSample for $t {
+            const COLUMNS: &'static str = "value";
+
+            fn write_columns<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+                write!(out, "{}", self)
+            }
+        }
+
+        impl CsvSample for Complex<$t> {
+            const COLUMNS: &'static str = "re,im";
+
+            fn write_columns<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+                write!(out, "{},{}", self.re, self.im)
+            }
+        }
+    };
+}
+
+impl_csv_sample!(i8);
+impl_csv_sample!(i16);
+impl_csv_sample!(i32);
+impl_csv_sample!(f32);
+impl_csv_sample!(f64);
+
+/// Samples read at once by export_csv
+const BLOCK_LEN: usize = 4096;
+
+/// Writes up to max_rows samples, starting at the read position of reader, as CSV with a header
+/// line and columns `index,time,value` (or `index,time,re,im` for complex samples). The index
+/// counts from the start of the file, and time is the rx_time of the sample in seconds, with
+/// nanosecond precision.
+///
+/// Samples are read as read_samples_resume does, so segments not directly readable into T are
+/// skipped, and rows continue after time gaps up to the end of the file.
+/// Returns the number of rows written, and leaves the reader after the last sample written.
+pub fn export_csv<T: CsvSample + Default, R: SampleReadSeek, W: Write>(
+    reader: &mut R,
+    out: &mut W,
+    max_rows: u64,
+) -> Result<u64, MetaFileError> {
+    writeln!(out, "index,time,{}", T::COLUMNS)?;
+
+    let mut buf = vec![T::default(); BLOCK_LEN];
+    let mut rows = 0;
+    while rows < max_rows {
+        let len = (max_rows - rows).min(BLOCK_LEN as u64) as usize;
+        let (num_read, mut header) = match reader.read_samples_resume(&mut buf[..len])? {
+            (0, _) => break,
+            (num_read, header) => (num_read, header.unwrap()),
+        };
+        // The samples of a block are contiguous, up to the read position
+        let end = reader.position_samples()?;
+        for (index, sample) in (end - num_read..end).zip(&buf) {
+            // The block may span several contiguous segments, all of them already loaded
+            if index >= header.sample_index_range().end {
+                header = reader
+                    .get_header_reader_mut()
+                    .get_header_for_sample(index)?
+                    .unwrap();
+            }
+            write!(out, "{},", index)?;
+            write_secs(
+                out,
+                header.get_sample_time((index - header.first_sample) as i64),
+            )?;
+            write!(out, ",")?;
+            sample.write_columns(out)?;
+            writeln!(out)?;
+        }
+        rows += num_read;
+    }
+    Ok(rows)
+}
+
+/// Writes time in seconds rounded to nanoseconds, from the fixed point value itself as a f64
+/// loses precision for UNIX timestamps
+fn write_secs<W: Write>(out: &mut W, time: Timestamp) -> std::io::Result<()> {
+    let sign = if time.is_negative() { "-" } else { "" };
+    let abs = time.unsigned_abs();
+    let nanos = abs.int().to_num::<u128>() * 1_000_000_000
+        + (abs.frac() * 1_000_000_000u128).round().to_num::<u128>();
+    write!(
+        out,
+        "{}{}.{:09}",
+        sign,
+        nanos / 1_000_000_000,
+        nanos % 1_000_000_000
+    )
+}
+
+#[cfg(test)]
+mod csv_tests {
+    use super::*;
+    use crate::core::AttachedHeader;
+    use crate::header::{DataType, HeaderBuilder};
+    use crate::writer::write_from_iter;
+
+    #[test]
+    fn export_ramp() {
+        let template = HeaderBuilder::new()
+            .samp_rate(4.0)
+            .rx_time(Timestamp::from_parts(10, 0.0))
+            .dtype(DataType::Float)
+            .cplx(false)
+            .num_samples(3)
+            .build();
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &template, (0..10).map(|i| i as f32 * 1.5)).unwrap();
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = Vec::new();
+        assert_eq!(
+            export_csv::<f32, _, _>(&mut reader, &mut out, 5).unwrap(),
+            5
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "index,time,value\n0,10.000000000,0\n1,10.250000000,1.5\n2,10.500000000,3\n\
+             3,10.750000000,4.5\n4,11.000000000,6\n"
+        );
+
+        // Continues from the read position, and stops at the end of the file
+        let mut out = Vec::new();
+        assert_eq!(
+            export_csv::<f32, _, _>(&mut reader, &mut out, 100).unwrap(),
+            5
+        );
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .ends_with("\n9,12.250000000,13.5\n")
+        );
+
+        let template = HeaderBuilder::new()
+            .samp_rate(1.0)
+            .dtype(DataType::Short)
+            .cplx(true)
+            .build();
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &template, (0..3i16).map(|i| Complex::new(i, -i))).unwrap();
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = Vec::new();
+        export_csv::<Complex<i16>, _, _>(&mut reader, &mut out, 2).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "index,time,re,im\n0,0.000000000,0,0\n1,1.000000000,1,-1\n"
+        );
+    }
+
+    #[test]
+    fn export_precise_time_and_blocks() {
+        // At 1 GHz, nanoseconds since the UNIX epoch don't fit the precision of a f64
+        let template = HeaderBuilder::new()
+            .samp_rate(1e9)
+            .rx_time(Timestamp::from_parts(1_700_000_000, 0.0))
+            .dtype(DataType::Int)
+            .cplx(false)
+            .num_samples(1000)
+            .build();
+        let len = BLOCK_LEN as i32 * 2 + 10;
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &template, 0..len).unwrap();
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = Vec::new();
+        assert_eq!(
+            export_csv::<i32, _, _>(&mut reader, &mut out, u64::MAX).unwrap(),
+            len as u64
+        );
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), len as usize + 1);
+        assert_eq!(lines[1], "0,1700000000.000000000,0");
+        assert_eq!(lines[2], "1,1700000000.000000001,1");
+        assert_eq!(
+            lines[len as usize],
+            format!("{},1700000000.000008201,{}", len - 1, len - 1)
+        );
+    }
+
+    #[test]
+    fn export_skips_unreadable_and_gaps() {
+        let float = HeaderBuilder::new()
+            .samp_rate(1.0)
+            .dtype(DataType::Float)
+            .cplx(false)
+            .build();
+        let short = HeaderBuilder::new()
+            .samp_rate(1.0)
+            .rx_time(Timestamp::from_parts(2, 0.0))
+            .dtype(DataType::Short)
+            .cplx(false)
+            .build();
+        let after_gap = HeaderBuilder::new()
+            .samp_rate(1.0)
+            .rx_time(Timestamp::from_parts(10, 0.0))
+            .dtype(DataType::Float)
+            .cplx(false)
+            .build();
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &float, [0.5f32, 1.5]).unwrap();
+        write_from_iter(&mut file, &short, [7i16]).unwrap();
+        write_from_iter(&mut file, &after_gap, [3.5f32, 4.5]).unwrap();
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = Vec::new();
+        assert_eq!(
+            export_csv::<f32, _, _>(&mut reader, &mut out, 100).unwrap(),
+            4
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "index,time,value\n0,0.000000000,0.5\n1,1.000000000,1.5\n\
+             3,10.000000000,3.5\n4,11.000000000,4.5\n"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Reading of compressed files. Decompressing streams (such as those of the `flate2` or `zstd`
+//! crates) can't seek, which the readers require, so the decompressed data is buffered first.
+//!
+//! For example, to read a gzip compressed file with attached headers:
+//! ```ignore
+//! let file = File::open("recording.bin.gz")?;
+//! let data = DecompressingReader::new(flate2::read::GzDecoder::new(file))?;
+//! let mut reader = AttachedHeader::new(data);
+//! ```
+
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Seekable buffer of the whole decompressed contents of a stream.
+///
+/// new keeps the data in memory, which is fastest but requires as much memory as the
+/// decompressed file. For large files, new_temp_file instead writes the data to a temporary
+/// file which is deleted on drop, requiring disk space instead.
+pub struct DecompressingReader {
+    inner: Inner,
+}
+
+enum Inner {
+    Memory(Cursor<Vec<u8>>),
+    TempFile(File, PathBuf),
+}
+
+impl DecompressingReader {
+    /// Decompresses the whole stream into memory
+    pub fn new<R: Read>(mut decoder: R) -> std::io::Result<DecompressingReader> {
+        let mut data = Vec::new();
+        decoder.read_to_end(&mut data)?;
+        Ok(DecompressingReader {
+            inner: Inner::Memory(Cursor::new(data)),
+        })
+    }
+
+    /// Decompresses the whole stream into a temporary file, in std::env::temp_dir()
+    pub fn new_temp_file<R: Read>(mut decoder: R) -> std::io::Result<DecompressingReader> {
+        let (mut file, path) = create_temp_file()?;
+        let result = std::io::copy(&mut decoder, &mut file).and_then(|_| file.rewind());
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+
+        Ok(DecompressingReader {
+            inner: Inner::TempFile(file, path),
+        })
+    }
+}
+
+/// Creates a new empty file in std::env::temp_dir(), open for reading and writing, which the
+/// caller must remove
+pub(crate) fn create_temp_file() -> std::io::Result<(File, PathBuf)> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "gnuradio-meta-{}-{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let file = File::options()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(&path)?;
+    Ok((file, path))
+}
+
+/// Stands in for a decoder or stdin in tests: a stream that can't seek, and yields data in
+/// chunks of at most chunk_len bytes
+#[cfg(test)]
+pub(crate) struct Trickle {
+    data: Vec<u8>,
+    pos: usize,
+    chunk_len: usize,
+}
+
+#[cfg(test)]
+impl Trickle {
+    pub(crate) fn new(data: Vec<u8>, chunk_len: usize) -> Trickle {
+        Trickle {
+            data,
+            pos: 0,
+            chunk_len,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf
+            .len()
+            .min(self.chunk_len)
+            .min(self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Read for DecompressingReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            Inner::Memory(cursor) => cursor.read(buf),
+            Inner::TempFile(file, _) => file.read(buf),
+        }
+    }
+}
+
+impl Seek for DecompressingReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match &mut self.inner {
+            Inner::Memory(cursor) => cursor.seek(pos),
+            Inner::TempFile(file, _) => file.seek(pos),
+        }
+    }
+}
+
+impl Drop for DecompressingReader {
+    fn drop(&mut self) {
+        if let Inner::TempFile(_, path) = &self.inner {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod decompress_tests {
+    use super::*;
+
+    #[test]
+    fn memory_and_temp_file() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        for mut reader in [
+            DecompressingReader::new(Trickle::new(data.clone(), 7)).unwrap(),
+            DecompressingReader::new_temp_file(Trickle::new(data.clone(), 7)).unwrap(),
+        ] {
+            let mut out = Vec::new();
+            reader.read_to_end(&mut out).unwrap();
+            assert_eq!(out, data);
+
+            reader.seek(SeekFrom::Start(300)).unwrap();
+            let mut byte = [0];
+            reader.read_exact(&mut byte).unwrap();
+            assert_eq!(byte[0], data[300]);
+        }
+    }
+
+    #[test]
+    fn temp_file_removed_on_drop() {
+        let reader = DecompressingReader::new_temp_file(Trickle::new(vec![1, 2, 3], 7)).unwrap();
+        let path = match &reader.inner {
+            Inner::TempFile(_, path) => path.clone(),
+            Inner::Memory(_) => unreachable!(),
+        };
+        assert!(path.exists());
+        drop(reader);
+        assert!(!path.exists());
+    }
+}
@@ -1,11 +1,11 @@
-use crate::pmt::{Tag, Timestamp};
+use crate::pmt::{Tag, Timestamp, parse, write};
 use num_complex::Complex;
-use std::{any::TypeId, marker::PhantomData, rc::Rc};
+use std::{cmp::Ordering, collections::BTreeMap, ops::Range, rc::Rc};
 use thiserror::Error;
 
 /// Which qualities of the current segment are guaranteed to be preserved after the seek?
 /// When in doubt, use All as most GNU Radio files are a single format and sample rate.
-#[derive(PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum SeekPreserve {
     /// Allow seeking into any type of segment
     None,
@@ -20,15 +20,42 @@ pub enum SeekPreserve {
     /// to the one of the current segment
     SampleRateAndConvertability,
     /// Allow seeking into segments which have the same sample rate and format as the current one
+    #[default]
     All,
     /// Only seek within the current segment. Same guarantees as All, but more restrictive.
     Segment,
 }
 
 impl SeekPreserve {
+    /// The least restrictive variant which guarantees the given qualities. Note that preserving
+    /// the format implies preserving convertability.
+    pub fn new(format: bool, convertability: bool, samplerate: bool) -> SeekPreserve {
+        match (format, convertability, samplerate) {
+            (true, _, true) => SeekPreserve::All,
+            (true, _, false) => SeekPreserve::Format,
+            (false, true, true) => SeekPreserve::SampleRateAndConvertability,
+            (false, true, false) => SeekPreserve::Convertability,
+            (false, false, true) => SeekPreserve::SampleRate,
+            (false, false, false) => SeekPreserve::None,
+        }
+    }
+
+    /// Short human readable description, for example for UIs
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SeekPreserve::None => "any segment",
+            SeekPreserve::Format => "same format",
+            SeekPreserve::Convertability => "convertible format",
+            SeekPreserve::SampleRate => "same sample rate",
+            SeekPreserve::SampleRateAndConvertability => "same sample rate, convertible format",
+            SeekPreserve::All => "same sample rate and format",
+            SeekPreserve::Segment => "current segment only",
+        }
+    }
+
     /// Returns true if the seek guarantees that the resulting segment data type is equal to the
     /// one before the seek
-    fn preserves_format(&self) -> bool {
+    pub fn preserves_format(&self) -> bool {
         *self == SeekPreserve::Format
             || *self == SeekPreserve::All
             || *self == SeekPreserve::Segment
@@ -36,19 +63,84 @@ impl SeekPreserve {
 
     /// Returns true if the seek guarantees that the resulting segment data type can be converted
     /// to the one before the seek
-    fn preserves_convertability(&self) -> bool {
+    pub fn preserves_convertability(&self) -> bool {
         *self != SeekPreserve::None && *self != SeekPreserve::SampleRate
     }
 
     /// Returns true if the seek guarantees that the resulting segment sample rate is equal to the
     /// one before the seek
-    fn preserves_samplerate(&self) -> bool {
+    pub fn preserves_samplerate(&self) -> bool {
         *self != SeekPreserve::None
             && *self != SeekPreserve::Format
             && *self != SeekPreserve::Convertability
     }
 }
 
+/// What seeking to a time which falls in a gap between segments (for example, due to dropped
+/// samples) does.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeGapSeek {
+    /// Seeking errors
+    #[default]
+    Error,
+    /// Seeking lands on the first sample of the segment after the gap
+    NextSegment,
+}
+
+/// How the rx_time of headers is to be interpreted. GNU Radio documents it as relative to the first
+/// sample, but some sources (for example, UHD) write an absolute UNIX time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RxTimeMode {
+    /// rx_time is relative to the first sample of the file
+    #[default]
+    Relative,
+    /// rx_time is seconds since the UNIX epoch
+    AbsoluteUnix,
+}
+
+/// Byte order of the samples in a file. GNU Radio writes samples in the byte order of the host,
+/// while the headers (PMTs) are always big endian.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Endianness {
+    Little,
+    /// Also known as network byte order
+    Big,
+    /// The byte order of the host
+    #[default]
+    Native,
+}
+
+/// How integer samples are converted to floating point
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConversionScaling {
+    /// Values are kept as they are, as GNU Radio does (for example, 32767i16 becomes 32767.0)
+    #[default]
+    Raw,
+    /// Values are divided by the full scale of their type, 2^(bits - 1), so they are in [-1, 1)
+    /// (for example, 32767i16 becomes 0.99997)
+    Normalized,
+}
+
+impl Endianness {
+    pub fn is_native(&self) -> bool {
+        match *self {
+            Endianness::Little => cfg!(target_endian = "little"),
+            Endianness::Big => cfg!(target_endian = "big"),
+            Endianness::Native => true,
+        }
+    }
+
+    /// Converts scalars of scalar_width bytes between this byte order and the native one, in
+    /// place. The conversion is the same in both directions.
+    pub fn convert_in_place(&self, bytes: &mut [u8], scalar_width: usize) {
+        if !self.is_native() && scalar_width > 1 {
+            bytes
+                .chunks_exact_mut(scalar_width)
+                .for_each(<[u8]>::reverse);
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum InvalidHeaderError {
     #[error("Header was not a dictionary")]
@@ -59,11 +151,55 @@ pub enum InvalidHeaderError {
     WrongTypeField(&'static str),
     #[error("Type {0} was present in header, but this represents no known data type")]
     WrongDataType(i32),
+    #[error("Size {0} in header does not match the size of its data type, complex or not")]
+    InconsistentSize(i32),
+    #[error("Field {0} was present in header, but its value is out of range")]
+    OutOfRangeField(&'static str),
+    #[error("Size {0} in header is too large for any data type")]
+    SizeOutOfRange(u64),
+    #[error("Header could not be parsed")]
+    ParseError(#[from] crate::pmt::ParseError),
+    #[error("Trailing bytes after the serialized {0} dict")]
+    TrailingBytes(&'static str),
+}
+
+/// Errors converting a buffer of samples, see DataType::convert_slice
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConvertError {
+    #[error("Buffer of {len} bytes is not a whole number of samples of {item_size} bytes")]
+    Misaligned { len: usize, item_size: usize },
+    #[error("Samples of type {from} can't be converted to {to}")]
+    Inconvertible {
+        from: &'static str,
+        to: &'static str,
+    },
+}
+
+/// Format of the samples of a segment, for example to use as key of maps
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Format {
+    pub dtype: DataType,
+    pub complex: bool,
+}
+
+/// Suspicious, but not necessarily invalid, qualities of a header, see Header::validate
+#[derive(Clone, Debug, PartialEq)]
+pub enum HeaderWarning {
+    /// The sample rate is zero, negative or not a number
+    NonPositiveSampleRate(f64),
+    /// size is not the size of an item of the data type
+    SizeMismatch { size: i32, expected: usize },
+    /// bytes does not contain a whole number of items
+    PartialSample { bytes: u64, size: i32 },
+    /// The segment is larger than any reasonable recording (1 TiB)
+    SuspiciouslyLargeBytes(u64),
+    /// rx_time is more than a day after the current system time
+    RxTimeInFuture(Timestamp),
 }
 
 /// Note all of these can be "complex", which duplicates each entry as a complex number,
 /// and makes them directly convertible to Complex<x>.
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum DataType {
     /// Directly convertible to i8
     Byte,
@@ -81,109 +217,379 @@ pub enum DataType {
 
 impl DataType {
     pub fn is_floating(&self) -> bool {
-        return *self == DataType::Float || *self == DataType::Double;
+        *self == DataType::Float || *self == DataType::Double
     }
 
-    /// Only returns true if the type is directly representable as the target type, including signed-ness
-    /// and number of bits of the type.
-    pub fn reads_directly_to<T: 'static>(&self, complex: bool) -> bool {
-        if complex {
-            match *self {
-                DataType::Byte => TypeId::of::<T>() == TypeId::of::<Complex<i8>>(),
-                DataType::Short => TypeId::of::<T>() == TypeId::of::<Complex<i16>>(),
-                DataType::Int => TypeId::of::<T>() == TypeId::of::<Complex<i32>>(),
-                DataType::Float => TypeId::of::<T>() == TypeId::of::<Complex<f32>>(),
-                DataType::Double => TypeId::of::<T>() == TypeId::of::<Complex<f64>>(),
-            }
-        } else {
-            match *self {
-                DataType::Byte => TypeId::of::<T>() == TypeId::of::<i8>(),
-                DataType::Short => TypeId::of::<T>() == TypeId::of::<i16>(),
-                DataType::Int => TypeId::of::<T>() == TypeId::of::<i32>(),
-                DataType::Float => TypeId::of::<T>() == TypeId::of::<f32>(),
-                DataType::Double => TypeId::of::<T>() == TypeId::of::<f64>(),
-            }
+    pub fn is_integer(&self) -> bool {
+        !self.is_floating()
+    }
+
+    /// Always true, as GNU Radio has no unsigned types (bytes are `signed char`, even though
+    /// byte streams are often read as u8)
+    pub fn is_signed(&self) -> bool {
+        true
+    }
+
+    /// Number of bits of a (real) value of this type
+    pub fn bit_width(&self) -> u32 {
+        match *self {
+            DataType::Byte => 8,
+            DataType::Short => 16,
+            DataType::Int => 32,
+            DataType::Float => 32,
+            DataType::Double => 64,
         }
     }
 
+    /// Only returns true if the type is directly representable as the target type, including signed-ness
+    /// and number of bits of the type, as given by SampleType::reads_directly_from (and so by
+    /// SampleReadSeek::read_samples).
+    pub fn reads_directly_to<T: SampleType>(&self, complex: bool) -> bool {
+        T::reads_directly_from(*self, complex)
+    }
+
     /// No edge cases, we only support up-casting among the basic types from GNU Radio, and
-    /// floating point conversion (lossy conversion from f64 -> f32 is allowed!)
-    pub fn converts_to<T: 'static>(&self, complex: bool) -> bool {
-        if complex {
-            match *self {
-                DataType::Byte => {
-                    TypeId::of::<T>() == TypeId::of::<Complex<f64>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<f32>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i32>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i16>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i8>>()
-                }
-                DataType::Short => {
-                    TypeId::of::<T>() == TypeId::of::<Complex<f64>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<f32>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i32>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i16>>()
-                }
-                DataType::Int => {
-                    TypeId::of::<T>() == TypeId::of::<Complex<f64>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<f32>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<i32>>()
-                }
-                DataType::Float => {
-                    TypeId::of::<T>() == TypeId::of::<Complex<f64>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<f32>>()
-                }
-                DataType::Double => {
-                    TypeId::of::<T>() == TypeId::of::<Complex<f64>>()
-                        || TypeId::of::<T>() == TypeId::of::<Complex<f32>>()
-                }
+    /// floating point conversion (lossy conversion from f64 -> f32 is allowed!), as given by
+    /// converts_to_dtype (and so by SampleReadSeek::read_conv).
+    pub fn converts_to<T: SampleType>(&self, complex: bool) -> bool {
+        T::COMPLEX == complex
+            && self.converts_to_dtype(&T::DTYPE)
+            && std::mem::size_of::<T>() == T::byte_width()
+    }
+
+    /// Size in bytes of a single sample of this type, doubled if complex
+    pub fn item_size_bytes(&self, complex: bool) -> usize {
+        let scalar = self.bit_width() as usize / 8;
+        if complex { 2 * scalar } else { scalar }
+    }
+
+    /// Same rules as converts_to, but with the target given as a DataType
+    pub fn converts_to_dtype(&self, other: &Self) -> bool {
+        match *self {
+            DataType::Byte => true,
+            DataType::Short => *other != DataType::Byte,
+            DataType::Int => *other != DataType::Byte && *other != DataType::Short,
+            DataType::Float | DataType::Double => other.is_floating(),
+        }
+    }
+
+    /// Converts a sample of this type, stored in native endianness at the start of bytes, to T.
+    /// Conversion is performed as with `as` casts, see read_from_bytes_checked to know if
+    /// precision was lost. Panics if bytes is too short.
+    pub fn read_from_bytes<T: SampleType>(&self, bytes: &[u8]) -> T {
+        self.read_from_bytes_checked(bytes, &mut ConversionStats::default())
+    }
+
+    /// Same as read_from_bytes, but counting into stats the values that lost precision
+    pub fn read_from_bytes_checked<T: SampleType>(
+        &self,
+        bytes: &[u8],
+        stats: &mut ConversionStats,
+    ) -> T {
+        self.read_from_bytes_scaled(bytes, ConversionScaling::Raw, stats)
+    }
+
+    /// Same as read_from_bytes_checked, with integers converted to floating point as given by
+    /// scaling
+    pub fn read_from_bytes_scaled<T: SampleType>(
+        &self,
+        bytes: &[u8],
+        scaling: ConversionScaling,
+        stats: &mut ConversionStats,
+    ) -> T {
+        let src_size = self.item_size_bytes(false);
+        let dst_size = T::DTYPE.item_size_bytes(false);
+        let num_scalars = if T::COMPLEX { 2 } else { 1 };
+
+        // SAFETY: SampleType guarantees any bit pattern is a valid T
+        let mut out: T = unsafe { std::mem::zeroed() };
+        // SAFETY: SampleType guarantees T is plain old data without padding
+        let out_bytes = unsafe {
+            std::slice::from_raw_parts_mut(&mut out as *mut T as *mut u8, std::mem::size_of::<T>())
+        };
+
+        for i in 0..num_scalars {
+            let mut src = Scalar::decode(*self, &bytes[i * src_size..(i + 1) * src_size]);
+            if let (Scalar::Int(v), ConversionScaling::Normalized) = (src, scaling)
+                && T::DTYPE.is_floating()
+            {
+                let full_scale = (1u64 << (self.bit_width() - 1)) as f64;
+                src = Scalar::Float(v as f64 / full_scale);
             }
+            src.encode(
+                T::DTYPE,
+                &mut out_bytes[i * dst_size..(i + 1) * dst_size],
+                stats,
+            );
+        }
+
+        out
+    }
+
+    /// Converts a buffer of samples of this type, stored with the given endianness, to T, with
+    /// the same rules as SampleReadSeek::read_conv. Useful for samples from other sources than
+    /// files, for example ZeroMQ streams.
+    pub fn convert_slice<T: SampleType>(
+        &self,
+        complex: bool,
+        bytes: &[u8],
+        endianness: Endianness,
+    ) -> Result<Vec<T>, ConvertError> {
+        if complex != T::COMPLEX
+            || !self.converts_to_dtype(&T::DTYPE)
+            || std::mem::size_of::<T>() != T::byte_width()
+        {
+            return Err(ConvertError::Inconvertible {
+                from: self.type_string(complex),
+                to: std::any::type_name::<T>(),
+            });
+        }
+        let item_size = self.item_size_bytes(complex);
+        if !bytes.len().is_multiple_of(item_size) {
+            return Err(ConvertError::Misaligned {
+                len: bytes.len(),
+                item_size,
+            });
+        }
+
+        let mut native = Vec::new();
+        let bytes = if endianness.is_native() {
+            bytes
         } else {
-            match *self {
-                DataType::Byte => {
-                    TypeId::of::<T>() == TypeId::of::<f64>()
-                        || TypeId::of::<T>() == TypeId::of::<f32>()
-                        || TypeId::of::<T>() == TypeId::of::<i32>()
-                        || TypeId::of::<T>() == TypeId::of::<i16>()
-                        || TypeId::of::<T>() == TypeId::of::<i8>()
-                }
-                DataType::Short => {
-                    TypeId::of::<T>() == TypeId::of::<f64>()
-                        || TypeId::of::<T>() == TypeId::of::<f32>()
-                        || TypeId::of::<T>() == TypeId::of::<i32>()
-                        || TypeId::of::<T>() == TypeId::of::<i16>()
-                }
-                DataType::Int => {
-                    TypeId::of::<T>() == TypeId::of::<f64>()
-                        || TypeId::of::<T>() == TypeId::of::<f32>()
-                        || TypeId::of::<T>() == TypeId::of::<i32>()
-                }
-                DataType::Float => {
-                    TypeId::of::<T>() == TypeId::of::<f64>()
-                        || TypeId::of::<T>() == TypeId::of::<f32>()
-                }
-                DataType::Double => {
-                    TypeId::of::<T>() == TypeId::of::<f64>()
-                        || TypeId::of::<T>() == TypeId::of::<f32>()
-                }
-            }
+            native.extend_from_slice(bytes);
+            endianness.convert_in_place(&mut native, self.bit_width() as usize / 8);
+            &native
+        };
+        Ok(bytes
+            .chunks_exact(item_size)
+            .map(|item| self.read_from_bytes(item))
+            .collect())
+    }
+
+    /// Short type name in the style of numpy, prefixed with "c" if complex (for example "cf32"
+    /// for GNU Radio's `gr_complex`)
+    pub fn type_string(&self, complex: bool) -> &'static str {
+        match (*self, complex) {
+            (DataType::Byte, false) => "i8",
+            (DataType::Short, false) => "i16",
+            (DataType::Int, false) => "i32",
+            (DataType::Float, false) => "f32",
+            (DataType::Double, false) => "f64",
+            (DataType::Byte, true) => "ci8",
+            (DataType::Short, true) => "ci16",
+            (DataType::Int, true) => "ci32",
+            (DataType::Float, true) => "cf32",
+            (DataType::Double, true) => "cf64",
         }
     }
 
-    pub fn converts_to_dtype(&self, other: &Self) -> bool {
-        todo!("Implement");
+    /// Parses the type names used by GNU Radio Companion (for example "complex", "float" or
+    /// "short"), optionally prefixed by "complex", ignoring case. Returns the data type and
+    /// whether it's complex.
+    pub fn from_gnuradio_name(s: &str) -> Option<(DataType, bool)> {
+        let name = s.trim().to_ascii_lowercase();
+        let mut words = name.split_whitespace();
+        let (complex, base) = match (words.next()?, words.next(), words.next()) {
+            ("complex", None, None) => return Some((DataType::Float, true)),
+            ("complex", Some(base), None) => (true, base),
+            (base, None, None) => (false, base),
+            _ => return None,
+        };
+
+        let dtype = match base {
+            "byte" | "char" => DataType::Byte,
+            "short" => DataType::Short,
+            "int" => DataType::Int,
+            "float" => DataType::Float,
+            "double" => DataType::Double,
+            _ => return None,
+        };
+        Some((dtype, complex))
     }
 
-    pub fn read_from_bytes<T>(&self, bytes: &[u8]) -> T {
-        todo!("Implement");
+    /// Name of the type as used by GNU Radio Companion, inverse of from_gnuradio_name
+    pub fn gnuradio_name(&self, complex: bool) -> &'static str {
+        match (*self, complex) {
+            (DataType::Byte, false) => "byte",
+            (DataType::Short, false) => "short",
+            (DataType::Int, false) => "int",
+            (DataType::Float, false) => "float",
+            (DataType::Double, false) => "double",
+            (DataType::Byte, true) => "complex byte",
+            (DataType::Short, true) => "complex short",
+            (DataType::Int, true) => "complex int",
+            (DataType::Float, true) => "complex",
+            (DataType::Double, true) => "complex double",
+        }
     }
 
+    /// Interprets the `type` field of the header, which follows the `gr_file_types` enum
+    /// of GNU Radio. Long and long long (3 and 4) are never generated by GNU Radio.
     pub fn from_int(i: i32) -> Result<Self, InvalidHeaderError> {
         Ok(match i {
             0 => Self::Byte,
+            1 => Self::Short,
+            2 => Self::Int,
+            5 => Self::Float,
+            6 => Self::Double,
             _ => return Err(InvalidHeaderError::WrongDataType(i)),
         })
     }
+
+    /// Inverse of from_int, the value of the `type` field of the header
+    pub fn to_int(&self) -> i32 {
+        match self {
+            Self::Byte => 0,
+            Self::Short => 1,
+            Self::Int => 2,
+            Self::Float => 5,
+            Self::Double => 6,
+        }
+    }
+}
+
+/// Counts of values (each of the two parts of a complex sample counts separately) that lost
+/// information while converting samples
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConversionStats {
+    /// Values out of range of the target type, which were saturated (to infinity for floats)
+    pub clipped: u64,
+    /// Values that were not exactly representable in the target type, and were rounded
+    pub rounded: u64,
+}
+
+/// A single scalar value of any DataType, wide enough to represent all of them
+#[derive(Clone, Copy)]
+enum Scalar {
+    Int(i32),
+    Float(f64),
+}
+
+impl Scalar {
+    fn decode(dtype: DataType, bytes: &[u8]) -> Scalar {
+        match dtype {
+            DataType::Byte => Scalar::Int(i8::from_ne_bytes([bytes[0]]) as i32),
+            DataType::Short => Scalar::Int(i16::from_ne_bytes(bytes.try_into().unwrap()) as i32),
+            DataType::Int => Scalar::Int(i32::from_ne_bytes(bytes.try_into().unwrap())),
+            DataType::Float => Scalar::Float(f32::from_ne_bytes(bytes.try_into().unwrap()) as f64),
+            DataType::Double => Scalar::Float(f64::from_ne_bytes(bytes.try_into().unwrap())),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match *self {
+            Scalar::Int(i) => i as f64,
+            Scalar::Float(f) => f,
+        }
+    }
+
+    fn encode(&self, dtype: DataType, out: &mut [u8], stats: &mut ConversionStats) {
+        match (*self, dtype) {
+            (Scalar::Int(i), DataType::Byte) => {
+                stats.clipped += (i8::try_from(i).is_err()) as u64;
+                out.copy_from_slice(&(i as i8).to_ne_bytes())
+            }
+            (Scalar::Int(i), DataType::Short) => {
+                stats.clipped += (i16::try_from(i).is_err()) as u64;
+                out.copy_from_slice(&(i as i16).to_ne_bytes())
+            }
+            (Scalar::Int(i), DataType::Int) => out.copy_from_slice(&i.to_ne_bytes()),
+            (Scalar::Float(f), DataType::Byte) => out.copy_from_slice(&(f as i8).to_ne_bytes()),
+            (Scalar::Float(f), DataType::Short) => out.copy_from_slice(&(f as i16).to_ne_bytes()),
+            (Scalar::Float(f), DataType::Int) => out.copy_from_slice(&(f as i32).to_ne_bytes()),
+            (v, DataType::Float) => {
+                let f = v.as_f64();
+                // Rounds to nearest, ties to even, to infinity if out of range, and to subnormals
+                // or zero if too small, as IEEE 754 requires
+                let conv = f as f32;
+                if f.is_finite() && conv.is_infinite() {
+                    stats.clipped += 1;
+                } else if !f.is_nan() && conv as f64 != f {
+                    stats.rounded += 1;
+                }
+                out.copy_from_slice(&conv.to_ne_bytes())
+            }
+            (v, DataType::Double) => out.copy_from_slice(&v.as_f64().to_ne_bytes()),
+        }
+    }
+}
+
+/// A type which samples can be read into directly, without any conversion. Implemented for the
+/// scalar types GNU Radio generates and their `Complex` counterparts.
+///
+/// You may implement it for your own types, for example a `#[repr(C)]` complex struct from
+/// a DSP crate, to read directly into them.
+///
+/// # Safety
+/// `Self` must have exactly the memory layout of a sample of `DTYPE` in native endianness (real
+/// part followed by imaginary part if `COMPLEX`), without padding, and any bit pattern must be
+/// a valid value of `Self`.
+pub unsafe trait SampleType: Copy + 'static {
+    const DTYPE: DataType;
+    const COMPLEX: bool;
+
+    /// Size in bytes of a single sample
+    fn byte_width() -> usize {
+        Self::DTYPE.item_size_bytes(Self::COMPLEX)
+    }
+
+    /// Returns true if samples of a segment with the given format can be read directly into Self
+    fn reads_directly_from(dtype: DataType, complex: bool) -> bool {
+        Self::DTYPE == dtype
+            && Self::COMPLEX == complex
+            && std::mem::size_of::<Self>() == Self::byte_width()
+    }
+}
+
+macro_rules! impl_sample_type {
+    ($t:ty, $dtype:expr) => {
+        unsafe impl SampleType for $t {
+            const DTYPE: DataType = $dtype;
+            const COMPLEX: bool = false;
+        }
+
+        unsafe impl SampleType for Complex<$t> {
+            const DTYPE: DataType = $dtype;
+            const COMPLEX: bool = true;
+        }
+    };
+}
+
+impl_sample_type!(i8, DataType::Byte);
+impl_sample_type!(i16, DataType::Short);
+impl_sample_type!(i32, DataType::Int);
+impl_sample_type!(f32, DataType::Float);
+impl_sample_type!(f64, DataType::Double);
+
+/// Floating point sample types, real or complex, whose statistics can be computed, see
+/// SampleReadSeek::stats
+pub trait FloatSample: SampleType {
+    /// The value of real samples, or the magnitude of complex samples
+    fn stat_value(self) -> f64;
+}
+
+impl FloatSample for f32 {
+    fn stat_value(self) -> f64 {
+        self as f64
+    }
+}
+
+impl FloatSample for f64 {
+    fn stat_value(self) -> f64 {
+        self
+    }
+}
+
+impl FloatSample for Complex<f32> {
+    fn stat_value(self) -> f64 {
+        self.norm() as f64
+    }
+}
+
+impl FloatSample for Complex<f64> {
+    fn stat_value(self) -> f64 {
+        self.norm()
+    }
 }
 
 /// Header as read from the GNU radio file
@@ -194,6 +600,9 @@ pub struct Header {
     pub samp_rate: f64,
     /// Duration of a sample, computed from samp_rate
     pub samp_dur: f64,
+    /// samp_rate as an exact fraction (numerator, denominator), if it's one with a small
+    /// denominator, computed from samp_rate. Used to compute sample times without drift.
+    pub samp_rate_ratio: Option<(u64, u64)>,
     /// Reception time of the first sample of the data, relative to first sample
     pub rx_time: Timestamp,
     /// Size of the item in bytes
@@ -202,7 +611,13 @@ pub struct Header {
     pub dtype: DataType,
     /// Is the data complex?
     pub cplx: bool,
-    /// Offset to the first byte of data in this header's segment
+    /// Length in bytes of the header and its extra dict, as stored in the file. For attached
+    /// headers, this is the offset from the start of the header to its data.
+    ///
+    /// As of GNU Radio 3.10, this is METADATA_HEADER_SIZE (149 bytes) plus the length of the
+    /// serialized extra dict (1 byte if empty), and is written both in attached and dettached
+    /// headers. It's never an absolute position in the file. Files older than GNU Radio 3.8 may
+    /// not store it, in which case it's computed from the length of the dicts.
     pub strt: u64,
     /// Size in bytes of the data in this header's segment
     pub bytes: u64,
@@ -210,24 +625,101 @@ pub struct Header {
     pub extra_dict: Rc<Tag>,
 
     /// Absolute position of the first byte of the data from the start of the file,
-    /// computed by ourselves
+    /// computed by ourselves: pos_in_file + strt for attached headers, and the sum of the bytes
+    /// of all previous segments for dettached headers.
     pub abs_pos: u64,
 
     /// Absolute position of the first byte of the HEADER in the file (either attached or dettached),
     /// computed by ourselves
     pub pos_in_file: u64,
+
+    /// Is the header stored inline with the data (attached) or in a separate file (dettached)?
+    pub attached: bool,
+
+    /// Global index of the first sample of the segment, counting from the start of the file,
+    /// computed by ourselves. Only known for headers loaded by a reader, 0 otherwise.
+    pub first_sample: u64,
 }
 
 impl Header {
+    pub fn format(&self) -> Format {
+        Format {
+            dtype: self.dtype,
+            complex: self.cplx,
+        }
+    }
+
+    /// Rebuilds the header dict as GNU Radio writes it, with rx_time as a (seconds, fraction)
+    /// tuple. The extra dict is not included, as it's stored separately. Header::from_tags
+    /// parses it back into the same header, except for the precision of rx_time, which is
+    /// limited by that of a f64 fraction.
+    pub fn as_tag(&self) -> Tag {
+        let secs = self.rx_time.int().saturating_to_num::<u64>();
+        let frac = self.rx_time.frac().to_num::<f64>();
+
+        let mut dict = BTreeMap::new();
+        dict.insert("version".to_string(), Tag::Int32(0));
+        dict.insert("rx_rate".to_string(), Tag::Double(self.samp_rate));
+        dict.insert(
+            "rx_time".to_string(),
+            Tag::Tuple(vec![Tag::UInt64(secs), Tag::Double(frac)]),
+        );
+        dict.insert("size".to_string(), Tag::Int32(self.size));
+        dict.insert("type".to_string(), Tag::Int32(self.dtype.to_int()));
+        dict.insert("cplx".to_string(), Tag::Bool(self.cplx));
+        dict.insert("strt".to_string(), Tag::UInt64(self.strt));
+        dict.insert("bytes".to_string(), Tag::UInt64(self.bytes));
+        Tag::Dict(dict)
+    }
+
+    /// Compares the positions of the headers in the file they are stored in, pos_in_file and
+    /// then abs_pos, for example to sort headers into file order with `sort_by`. This is not
+    /// necessarily their order in time.
+    pub fn cmp_position(&self, other: &Header) -> Ordering {
+        (self.pos_in_file, self.abs_pos).cmp(&(other.pos_in_file, other.abs_pos))
+    }
+
+    /// Number of samples in this header's segment, computed from its actual size in bytes
     pub fn get_num_samples(&self) -> u64 {
-        todo!("Implement");
+        if self.size <= 0 {
+            return 0;
+        }
+        self.bytes / self.size as u64
+    }
+
+    /// Global indices of the samples of the segment, see first_sample
+    pub fn sample_index_range(&self) -> Range<u64> {
+        self.first_sample..self.first_sample + self.get_num_samples()
     }
 
     /// Returns the expected reception time of sample at offset `sample` (which
     /// may be outside the header just fine, or even negative) assuming the sample rate is held
     /// constant until said offset.
     pub fn get_sample_time(&self, sample: i64) -> Timestamp {
-        todo!("Implement");
+        let (num, den) = match self.samp_rate_ratio {
+            Some(v) => v,
+            None => return self.rx_time + Timestamp::from_secs_f64(sample as f64 * self.samp_dur),
+        };
+        // sample * den / num seconds, computed exactly (up to the 64 fractional bits)
+        let ticks = sample.unsigned_abs() as u128 * den as u128;
+        let secs = ticks / num as u128;
+        let frac = ((ticks % num as u128) << 64) / num as u128;
+        let bits = ((secs as i128) << 64) + frac as i128;
+        let offset = Timestamp::from_fixed(fixed::FixedI128::from_bits(bits));
+        if sample < 0 {
+            self.rx_time - offset
+        } else {
+            self.rx_time + offset
+        }
+    }
+
+    /// Returns a copy of the header with rx_time shifted by offset, which may be negative, for
+    /// example to correct a known offset of the clock that timestamped the samples
+    pub fn with_corrected_rx_time(&self, offset: Timestamp) -> Header {
+        Header {
+            rx_time: self.rx_time + offset,
+            ..self.clone()
+        }
     }
 
     /// Gets the duration of a sample at the sample rate of the header
@@ -235,36 +727,179 @@ impl Header {
         self.samp_dur
     }
 
+    /// Bytes of the samples of the segment in the binary file
+    pub fn data_file_range(&self) -> Range<u64> {
+        self.abs_pos..self.abs_pos + self.bytes
+    }
+
+    /// Bytes of the header and extra dict, in the binary file if attached (where they are right
+    /// before the data) or in the header file if dettached
+    pub fn header_byte_range(&self) -> Range<u64> {
+        self.pos_in_file..self.pos_in_file + self.strt
+    }
+
+    /// Size in bytes of the header and extra dict stored inline with the data in attached
+    /// files, or 0 for dettached files, where the data contains no headers.
+    pub fn header_byte_len(&self) -> u64 {
+        if self.attached { self.strt } else { 0 }
+    }
+
+    /// Returns true if both headers have the same extra dict, regardless of everything else.
+    /// Headers cloned from each other share it, so comparing them is cheap.
+    pub fn extra_eq(&self, other: &Header) -> bool {
+        Rc::ptr_eq(&self.extra_dict, &other.extra_dict) || self.extra_dict == other.extra_dict
+    }
+
+    /// Stable hash of the serialized extra dict, cheap to compare in order to detect segments
+    /// where the tags changed. It's the same across runs and platforms.
+    pub fn extra_dict_fingerprint(&self) -> u64 {
+        let mut hasher = Fnv1a(0xcbf29ce484222325);
+        // Writing to the hasher never fails
+        write(&mut hasher, &self.extra_dict).unwrap();
+        hasher.0
+    }
+
+    /// One line description of the segment, for example
+    /// `cf32 @ 2.000 MHz, 1048576 samples, t=+0.524288s, seg@byte 0`
+    pub fn human_summary(&self) -> String {
+        let (rate, unit) = match self.samp_rate.abs() {
+            r if r >= 1e9 => (self.samp_rate / 1e9, "GHz"),
+            r if r >= 1e6 => (self.samp_rate / 1e6, "MHz"),
+            r if r >= 1e3 => (self.samp_rate / 1e3, "kHz"),
+            _ => (self.samp_rate, "Hz"),
+        };
+        format!(
+            "{} @ {:.3} {}, {} samples, t={:+.6}s, seg@byte {}",
+            self.dtype.type_string(self.cplx),
+            rate,
+            unit,
+            self.get_num_samples(),
+            self.rx_time.as_secs_f64(),
+            self.abs_pos
+        )
+    }
+
+    /// Returns true if moving from other's segment to this one keeps the qualities given by
+    /// preserve (i.e. self is the segment after the seek, and other the one before it).
     pub fn is_compatible_with(&self, other: &Header, preserve: SeekPreserve) -> bool {
-        if preserve.preserves_samplerate() && other.samp_rate != self.samp_rate {
+        if preserve.preserves_samplerate() && !self.same_samp_rate(other) {
             return false;
         }
-        if preserve.preserves_format() && other.dtype != self.dtype {
+        if preserve.preserves_format() && (other.dtype != self.dtype || other.cplx != self.cplx) {
             return false;
         }
-        if preserve.preserves_convertability() && !other.dtype.converts_to_dtype(&self.dtype) {
+        if preserve.preserves_convertability()
+            && (other.cplx != self.cplx || !self.dtype.converts_to_dtype(&other.dtype))
+        {
             return false;
         }
 
         true
     }
 
-    /// Returns true if the first sample of this header is received at most with a time error
-    /// of 0.1 * other.get_sample_duration(), to account for floating point errors.
+    /// Returns true if both sample rates are equal within a relative error of 1e-9, as rates
+    /// which are logically the same may differ in their last bits between segments.
+    pub fn same_samp_rate(&self, other: &Header) -> bool {
+        (self.samp_rate - other.samp_rate).abs()
+            <= 1e-9 * self.samp_rate.abs().max(other.samp_rate.abs())
+    }
+
+    /// Returns true if the first sample of this header is received right after the last sample
+    /// of other, at most with a time error of 0.1 * other.get_sample_duration(), to account for
+    /// floating point errors.
     pub fn is_continuation_of(&self, other: &Header) -> bool {
-        let last_sample_t = if other.get_num_samples() == 0 {
-            other.rx_time
-        } else {
-            other.get_sample_time(other.get_num_samples() as i64 - 1)
-        };
-        let diff = self.rx_time.abs_diff(last_sample_t).to_num::<f64>();
-        diff <= 0.1 * other.get_sample_duration()
+        self.is_continuation_of_within(other, 0.1)
+    }
+
+    /// Like is_continuation_of, but allowing a time error of up to tol_samples times
+    /// other.get_sample_duration(), useful if the timing source is noisy.
+    pub fn is_continuation_of_within(&self, other: &Header, tol_samples: f64) -> bool {
+        // For an empty segment, this is simply its rx_time
+        let next_sample_t = other.get_sample_time(other.get_num_samples() as i64);
+        let diff = self.rx_time.abs_diff(next_sample_t).as_secs_f64();
+        diff <= tol_samples * other.get_sample_duration()
     }
 
+    /// Checks the header for suspicious values, returning every one found. Unlike the errors of
+    /// from_tags, these don't prevent reading the file, but hint at a corrupt or misconfigured one.
+    pub fn validate(&self) -> Vec<HeaderWarning> {
+        let mut warnings = Vec::new();
+
+        if self.samp_rate.is_nan() || self.samp_rate <= 0.0 {
+            warnings.push(HeaderWarning::NonPositiveSampleRate(self.samp_rate));
+        }
+
+        let expected = self.dtype.item_size_bytes(self.cplx);
+        if self.size as i64 != expected as i64 {
+            warnings.push(HeaderWarning::SizeMismatch {
+                size: self.size,
+                expected,
+            });
+        }
+
+        if self.size > 0 && !self.bytes.is_multiple_of(self.size as u64) {
+            warnings.push(HeaderWarning::PartialSample {
+                bytes: self.bytes,
+                size: self.size,
+            });
+        }
+
+        if self.bytes > 1 << 40 {
+            warnings.push(HeaderWarning::SuspiciouslyLargeBytes(self.bytes));
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        if self.rx_time > Timestamp::from_parts(now + 86400, 0.0) {
+            warnings.push(HeaderWarning::RxTimeInFuture(self.rx_time));
+        }
+
+        warnings
+    }
+
+    /// Time between the sample after the last one of previous and the first sample of this
+    /// segment: about zero if this segment is a continuation of previous, positive if samples were
+    /// dropped between them, and negative if they overlap.
+    pub fn gap_to(&self, previous: &Header) -> Timestamp {
+        self.rx_time - previous.get_sample_time(previous.get_num_samples() as i64)
+    }
+
+    /// Returns the offset in samples within this segment of the sample containing `byte`,
+    /// which must be a byte of the binary file within the segment's data.
     pub fn get_sample_pos_of_byte(&self, byte: u64) -> u64 {
-        todo!("Implement");
+        if self.size <= 0 || self.bytes == 0 {
+            return 0;
+        }
+        byte.saturating_sub(self.abs_pos) / self.size as u64
     }
 
+    /// Builds the header from the serialized header and extra dicts, with byte_in_file being the
+    /// position of the header in the file it's stored in, as from_tags does. An empty extra is
+    /// taken as no extra dict. Each buffer must hold a single serialized dict, and nothing else.
+    pub fn try_from_bytes(
+        mut main: &[u8],
+        mut extra: &[u8],
+        byte_in_file: u64,
+    ) -> Result<Header, InvalidHeaderError> {
+        let tag = parse(&mut main)?;
+        if !main.is_empty() {
+            return Err(InvalidHeaderError::TrailingBytes("header"));
+        }
+        let extra_tag = if extra.is_empty() {
+            Tag::Null()
+        } else {
+            parse(&mut extra)?
+        };
+        if !extra.is_empty() {
+            return Err(InvalidHeaderError::TrailingBytes("extra"));
+        }
+        Header::from_tags(byte_in_file, tag, extra_tag)
+    }
+
+    /// Builds the header from the parsed header and extra dicts, with byte_in_file being the
+    /// position of the header in the file it's stored in.
     pub fn from_tags(
         byte_in_file: u64,
         tag: Tag,
@@ -276,9 +911,6 @@ impl Header {
             return Err(InvalidHeaderError::HeaderNotDictionary);
         };
 
-        println!("Read header from tag {:?}", tag);
-        println!("Extra: {:?}", extra);
-
         let samp_rate = tag
             .get("rx_rate")
             .ok_or(InvalidHeaderError::MissingField("rx_rate"))?
@@ -286,6 +918,10 @@ impl Header {
             .ok_or(InvalidHeaderError::WrongTypeField("rx_rate"))?;
 
         let samp_dur = 1.0 / samp_rate;
+        // Times of samples are computed from samp_dur, so it must be finite too
+        if !(samp_rate > 0.0 && samp_rate.is_finite() && samp_dur.is_finite()) {
+            return Err(InvalidHeaderError::OutOfRangeField("rx_rate"));
+        }
 
         let (rx_time_a, rx_time_b) = match tag
             .get("rx_time")
@@ -293,30 +929,42 @@ impl Header {
         {
             Tag::Tuple(vec) => {
                 let a = vec
-                    .get(0)
+                    .first()
                     .ok_or(InvalidHeaderError::MissingField("rx_time seconds"))?;
                 let b = vec
                     .get(1)
                     .ok_or(InvalidHeaderError::MissingField("rx_time fractional"))?;
                 (a, b)
             }
+            // As built by pmt.cons(secs, frac), used by some sinks and UHD tags
+            Tag::Pair(a, b) => (a.as_ref(), b.as_ref()),
             _ => return Err(InvalidHeaderError::WrongTypeField("rx_time")),
         };
 
-        let rx_time_secs = rx_time_a
-            .get_u64()
-            .ok_or(InvalidHeaderError::WrongTypeField("rx_time seconds"))?;
+        // Some sinks other than GNU Radio store the seconds as an Int32
+        let rx_time_secs = match *rx_time_a {
+            Tag::UInt64(secs) => secs,
+            Tag::Int32(secs) if secs >= 0 => secs as u64,
+            _ => return Err(InvalidHeaderError::WrongTypeField("rx_time seconds")),
+        };
         let rx_time_frac = rx_time_b
             .get_f64()
             .ok_or(InvalidHeaderError::WrongTypeField("rx_time fraction"))?;
 
-        let rx_time = Timestamp::from_num(rx_time_secs) + Timestamp::from_num(rx_time_frac);
+        let rx_time = Timestamp::checked_from_parts(rx_time_secs, rx_time_frac)
+            .ok_or(InvalidHeaderError::OutOfRangeField("rx_time"))?;
 
-        let size = tag
+        // GNU Radio stores size as Int32, but some tools as UInt64
+        let size = match tag
             .get("size")
             .ok_or(InvalidHeaderError::MissingField("size"))?
-            .get_i32()
-            .ok_or(InvalidHeaderError::WrongTypeField("size"))?;
+        {
+            Tag::Int32(size) => *size,
+            Tag::UInt64(size) => {
+                i32::try_from(*size).map_err(|_| InvalidHeaderError::SizeOutOfRange(*size))?
+            }
+            _ => return Err(InvalidHeaderError::WrongTypeField("size")),
+        };
 
         let dtype = DataType::from_int(
             tag.get("type")
@@ -331,11 +979,31 @@ impl Header {
             .get_bool()
             .ok_or(InvalidHeaderError::WrongTypeField("cplx"))?;
 
-        let strt = tag
-            .get("strt")
-            .ok_or(InvalidHeaderError::MissingField("strt"))?
-            .get_u64()
-            .ok_or(InvalidHeaderError::WrongTypeField("strt"))?;
+        // size already encodes complexity, so a contradicting cplx flag is likely corrupt, in
+        // which case size is trusted as it's what sample indexing is based on
+        let cplx = if size as usize == dtype.item_size_bytes(cplx) {
+            cplx
+        } else if size as usize == dtype.item_size_bytes(!cplx) {
+            !cplx
+        } else {
+            return Err(InvalidHeaderError::InconsistentSize(size));
+        };
+
+        // Very old files (before GNU Radio 3.8) may not store strt, in which case the data
+        // follows the header and extra dict right away, so it's their serialized length. Note
+        // that the length of the extra dict is then unknown if it was skipped (passed as Null).
+        let strt = match tag.get("strt") {
+            Some(v) => v
+                .get_u64()
+                .ok_or(InvalidHeaderError::WrongTypeField("strt"))?,
+            None => {
+                let mut len = ByteCounter(0);
+                // Writing to the counter never fails
+                write(&mut len, &Tag::Dict(tag.clone())).unwrap();
+                write(&mut len, &extra).unwrap();
+                len.0
+            }
+        };
 
         let bytes = tag
             .get("bytes")
@@ -343,9 +1011,19 @@ impl Header {
             .get_u64()
             .ok_or(InvalidHeaderError::WrongTypeField("bytes"))?;
 
+        // Correct for attached headers, where data follows the header. Dettached readers must
+        // override this, as data is stored contiguously in a separate file.
+        let abs_pos = byte_in_file
+            .checked_add(strt)
+            .ok_or(InvalidHeaderError::OutOfRangeField("strt"))?;
+        if abs_pos.checked_add(bytes).is_none() {
+            return Err(InvalidHeaderError::OutOfRangeField("bytes"));
+        }
+
         Ok(Header {
             samp_rate,
             samp_dur,
+            samp_rate_ratio: samp_rate_ratio(samp_rate),
             rx_time,
             size,
             dtype,
@@ -353,85 +1031,841 @@ impl Header {
             strt,
             bytes,
             extra_dict: Rc::new(extra),
-            // TODO: THESE ARE INCORRECT
-            abs_pos: strt,
+            abs_pos,
             pos_in_file: byte_in_file,
+            attached: true,
+            first_sample: 0,
         })
     }
 }
 
+/// Headers are ordered by their position in the file they are stored in (pos_in_file, then
+/// abs_pos), not by time. Headers at the same position are only comparable if they are equal,
+/// so sort them with Header::cmp_position, which ignores everything else.
+impl PartialOrd for Header {
+    fn partial_cmp(&self, other: &Header) -> Option<Ordering> {
+        match self.cmp_position(other) {
+            Ordering::Equal => (self == other).then_some(Ordering::Equal),
+            ord => Some(ord),
+        }
+    }
+}
+
+/// Finds the fraction with the smallest denominator (up to 1000) that is exactly samp_rate, as
+/// sample rates are usually integers, or simple fractions of a clock (for example 1e6 / 3).
+pub(crate) fn samp_rate_ratio(samp_rate: f64) -> Option<(u64, u64)> {
+    if !(samp_rate > 0.0 && samp_rate < (1u64 << 53) as f64) {
+        return None;
+    }
+    (1..=1000u64).find_map(|den| {
+        let num = (samp_rate * den as f64).round();
+        (num / den as f64 == samp_rate).then_some((num as u64, den))
+    })
+}
+
+/// 64 bit FNV-1a hash, which unlike std's hashers is guaranteed to be stable
+struct Fnv1a(u64);
+
+impl std::io::Write for Fnv1a {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for &b in buf {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(0x100000001b3);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Counts the bytes written to it, discarding them
+struct ByteCounter(u64);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds consistent synthetic headers, for example for writing files or for tests.
+/// By default, the header is of an empty `cf32` segment at 1 Hz, with rx_time 0, attached
+/// at the start of the file.
+#[derive(Clone, Debug)]
+pub struct HeaderBuilder {
+    samp_rate: f64,
+    dtype: DataType,
+    cplx: bool,
+    rx_time: Timestamp,
+    num_samples: u64,
+    extra: Tag,
+    strt: u64,
+    pos_in_file: u64,
+    dettached_abs_pos: Option<u64>,
+}
+
+impl Default for HeaderBuilder {
+    fn default() -> Self {
+        HeaderBuilder {
+            samp_rate: 1.0,
+            dtype: DataType::Float,
+            cplx: true,
+            rx_time: Timestamp::ZERO,
+            num_samples: 0,
+            extra: Tag::Null(),
+            strt: 0,
+            pos_in_file: 0,
+            dettached_abs_pos: None,
+        }
+    }
+}
+
+impl HeaderBuilder {
+    pub fn new() -> HeaderBuilder {
+        Default::default()
+    }
+
+    pub fn samp_rate(mut self, samp_rate: f64) -> Self {
+        self.samp_rate = samp_rate;
+        self
+    }
+
+    pub fn dtype(mut self, dtype: DataType) -> Self {
+        self.dtype = dtype;
+        self
+    }
+
+    pub fn cplx(mut self, cplx: bool) -> Self {
+        self.cplx = cplx;
+        self
+    }
+
+    pub fn rx_time(mut self, rx_time: Timestamp) -> Self {
+        self.rx_time = rx_time;
+        self
+    }
+
+    /// Number of samples in the segment, from which its size in bytes is computed
+    pub fn num_samples(mut self, num_samples: u64) -> Self {
+        self.num_samples = num_samples;
+        self
+    }
+
+    pub fn extra(mut self, extra: Tag) -> Self {
+        self.extra = extra;
+        self
+    }
+
+    /// Length of the header and extra dict, as stored in the strt field
+    pub fn strt(mut self, strt: u64) -> Self {
+        self.strt = strt;
+        self
+    }
+
+    /// The header is stored at pos_in_file of the data file, and its data follows it
+    pub fn attached_at(mut self, pos_in_file: u64) -> Self {
+        self.pos_in_file = pos_in_file;
+        self.dettached_abs_pos = None;
+        self
+    }
+
+    /// The header is stored at pos_in_file of the header file, and its data at abs_pos of
+    /// the data file
+    pub fn dettached_at(mut self, pos_in_file: u64, abs_pos: u64) -> Self {
+        self.pos_in_file = pos_in_file;
+        self.dettached_abs_pos = Some(abs_pos);
+        self
+    }
+
+    pub fn build(self) -> Header {
+        let size = self.dtype.item_size_bytes(self.cplx);
+        Header {
+            samp_rate: self.samp_rate,
+            samp_dur: 1.0 / self.samp_rate,
+            samp_rate_ratio: samp_rate_ratio(self.samp_rate),
+            rx_time: self.rx_time,
+            size: size as i32,
+            dtype: self.dtype,
+            cplx: self.cplx,
+            strt: self.strt,
+            bytes: self.num_samples * size as u64,
+            extra_dict: Rc::new(self.extra),
+            abs_pos: self
+                .dettached_abs_pos
+                .unwrap_or(self.pos_in_file + self.strt),
+            pos_in_file: self.pos_in_file,
+            attached: self.dettached_abs_pos.is_none(),
+            first_sample: 0,
+        }
+    }
+}
+
 #[cfg(test)]
 mod header_tests {
     use super::*;
 
+    /// Stands in for a type that can't be read into, so the predicates of DataType can be checked
+    /// for types that aren't SampleType (autoref specialization: the impl for Probe<T> is picked
+    /// if T is SampleType, and that for &Probe<T> otherwise)
+    struct Probe<T>(std::marker::PhantomData<T>);
+
+    trait Predicates {
+        fn reads_directly_to(&self, dtype: DataType, complex: bool) -> bool;
+        fn converts_to(&self, dtype: DataType, complex: bool) -> bool;
+    }
+
+    impl<T: SampleType> Predicates for Probe<T> {
+        fn reads_directly_to(&self, dtype: DataType, complex: bool) -> bool {
+            dtype.reads_directly_to::<T>(complex)
+        }
+        fn converts_to(&self, dtype: DataType, complex: bool) -> bool {
+            dtype.converts_to::<T>(complex)
+        }
+    }
+
+    impl<T> Predicates for &Probe<T> {
+        fn reads_directly_to(&self, _: DataType, _: bool) -> bool {
+            false
+        }
+        fn converts_to(&self, _: DataType, _: bool) -> bool {
+            false
+        }
+    }
+
+    macro_rules! reads_directly_to {
+        ($dtype:expr, $t:ty, $complex:expr) => {
+            (&Probe::<$t>(std::marker::PhantomData)).reads_directly_to($dtype, $complex)
+        };
+    }
+
+    macro_rules! converts_to {
+        ($dtype:expr, $t:ty, $complex:expr) => {
+            (&Probe::<$t>(std::marker::PhantomData)).converts_to($dtype, $complex)
+        };
+    }
+
+    #[test]
+    fn format_as_map_key() {
+        let headers = [
+            HeaderBuilder::new().build(),
+            HeaderBuilder::new().dtype(DataType::Short).build(),
+            HeaderBuilder::new().build(),
+            HeaderBuilder::new().cplx(false).build(),
+            HeaderBuilder::new().build(),
+        ];
+        let mut counts = std::collections::HashMap::new();
+        for header in &headers {
+            *counts.entry(header.format()).or_insert(0) += 1;
+        }
+
+        let cf32 = Format {
+            dtype: DataType::Float,
+            complex: true,
+        };
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&cf32], 3);
+        assert_eq!(counts[&headers[1].format()], 1);
+        assert_eq!(counts[&headers[3].format()], 1);
+    }
+
+    #[test]
+    fn builder_complex_float() {
+        let header = HeaderBuilder::new()
+            .samp_rate(2e6)
+            .dtype(DataType::Float)
+            .cplx(true)
+            .rx_time(Timestamp::from_secs_f64(1.5))
+            .num_samples(1000)
+            .strt(149)
+            .attached_at(4096)
+            .build();
+
+        assert_eq!(header.size, 8);
+        assert_eq!(header.bytes, 8000);
+        assert_eq!(header.get_num_samples(), 1000);
+        assert_eq!(header.samp_dur, 0.5e-6);
+        assert_eq!(header.abs_pos, 4096 + 149);
+        assert_eq!(header.header_byte_len(), 149);
+        assert!(header.dtype.reads_directly_to::<Complex<f32>>(header.cplx));
+
+        let header = HeaderBuilder::new()
+            .dtype(DataType::Short)
+            .cplx(false)
+            .num_samples(10)
+            .strt(149)
+            .dettached_at(149, 20)
+            .build();
+        assert_eq!(header.size, 2);
+        assert_eq!(header.abs_pos, 20);
+        assert_eq!(header.header_byte_len(), 0);
+    }
+
+    #[test]
+    fn rational_sample_time() {
+        assert_eq!(samp_rate_ratio(48e3), Some((48000, 1)));
+        assert_eq!(samp_rate_ratio(1e6 / 3.0), Some((1_000_000, 3)));
+        assert_eq!(samp_rate_ratio(0.5), Some((1, 2)));
+        assert_eq!(samp_rate_ratio(std::f64::consts::PI), None);
+        assert_eq!(samp_rate_ratio(0.0), None);
+
+        let header = HeaderBuilder::new().samp_rate(48e3).build();
+        assert_eq!(header.samp_rate_ratio, Some((48000, 1)));
+        let float_only = Header {
+            samp_rate_ratio: None,
+            ..header.clone()
+        };
+
+        // 10^10 samples at 48 kHz are exactly 208333 + 1/3 seconds
+        let exact = Timestamp::from_parts(208333, 1.0 / 3.0);
+        let rational_err = header.get_sample_time(10_000_000_000).abs_diff(exact);
+        let float_err = float_only.get_sample_time(10_000_000_000).abs_diff(exact);
+        assert!(rational_err.as_secs_f64() < 1e-15);
+        assert!(float_err > rational_err);
+
+        assert_eq!(
+            header.get_sample_time(-48000),
+            Timestamp::ZERO - Timestamp::from_parts(1, 0.0)
+        );
+    }
+
+    #[test]
+    fn samp_rate_tolerance() {
+        let first = HeaderBuilder::new().samp_rate(2e6).build();
+        let ulp = HeaderBuilder::new()
+            .samp_rate(f64::from_bits(2e6f64.to_bits() - 1))
+            .build();
+        let other = HeaderBuilder::new().samp_rate(2.001e6).build();
+
+        assert!(ulp.same_samp_rate(&first));
+        assert!(ulp.is_compatible_with(&first, SeekPreserve::SampleRate));
+        assert!(!other.same_samp_rate(&first));
+        assert!(!other.is_compatible_with(&first, SeekPreserve::SampleRate));
+    }
+
+    #[test]
+    fn continuation_tolerance() {
+        let first = HeaderBuilder::new()
+            .samp_rate(1000.0)
+            .num_samples(100)
+            .build();
+        // Starts half a sample late
+        let second = HeaderBuilder::new()
+            .samp_rate(1000.0)
+            .rx_time(Timestamp::from_secs_f64(0.1005))
+            .num_samples(100)
+            .build();
+
+        assert!(!second.is_continuation_of(&first));
+        assert!(!second.is_continuation_of_within(&first, 0.4));
+        assert!(second.is_continuation_of_within(&first, 0.6));
+        assert!(second.is_continuation_of_within(&first, 1.0));
+    }
+
+    #[test]
+    fn seek_preserve_default_and_describe() {
+        assert_eq!(SeekPreserve::default(), SeekPreserve::All);
+
+        let all = [
+            SeekPreserve::None,
+            SeekPreserve::Format,
+            SeekPreserve::Convertability,
+            SeekPreserve::SampleRate,
+            SeekPreserve::SampleRateAndConvertability,
+            SeekPreserve::All,
+            SeekPreserve::Segment,
+        ];
+        for (i, a) in all.iter().enumerate() {
+            for b in &all[i + 1..] {
+                assert_ne!(a.describe(), b.describe());
+            }
+        }
+
+        // new picks a variant with exactly the requested guarantees, except for Segment
+        for p in &all[..6] {
+            let q = SeekPreserve::new(
+                p.preserves_format(),
+                p.preserves_convertability(),
+                p.preserves_samplerate(),
+            );
+            assert_eq!(q, *p);
+        }
+    }
+
+    fn header_tag(dtype: i32, cplx: bool, size: i32) -> Tag {
+        Tag::Dict(
+            [
+                ("version".to_string(), Tag::Int32(0)),
+                ("rx_rate".to_string(), Tag::Double(1e6)),
+                (
+                    "rx_time".to_string(),
+                    Tag::Tuple(vec![Tag::UInt64(0), Tag::Double(0.0)]),
+                ),
+                ("size".to_string(), Tag::Int32(size)),
+                ("type".to_string(), Tag::Int32(dtype)),
+                ("cplx".to_string(), Tag::Bool(cplx)),
+                ("strt".to_string(), Tag::UInt64(149)),
+                ("bytes".to_string(), Tag::UInt64(800)),
+            ]
+            .into(),
+        )
+    }
+
+    #[test]
+    fn size_cplx_consistent() {
+        let header = Header::from_tags(0, header_tag(5, true, 8), Tag::Null()).unwrap();
+        assert!(header.cplx);
+        assert_eq!(header.get_num_samples(), 100);
+
+        let header = Header::from_tags(0, header_tag(5, false, 4), Tag::Null()).unwrap();
+        assert!(!header.cplx);
+        assert_eq!(header.get_num_samples(), 200);
+    }
+
+    #[test]
+    fn rx_time_pair() {
+        let mut tag = header_tag(5, true, 8);
+        if let Tag::Dict(dict) = &mut tag {
+            dict.insert(
+                "rx_time".to_string(),
+                Tag::Tuple(vec![Tag::UInt64(12), Tag::Double(0.25)]),
+            );
+        }
+        let from_tuple = Header::from_tags(0, tag.clone(), Tag::Null()).unwrap();
+        if let Tag::Dict(dict) = &mut tag {
+            dict.insert(
+                "rx_time".to_string(),
+                Tag::Pair(Box::new(Tag::UInt64(12)), Box::new(Tag::Double(0.25))),
+            );
+        }
+        let from_pair = Header::from_tags(0, tag, Tag::Null()).unwrap();
+        assert_eq!(from_pair.rx_time, Timestamp::from_parts(12, 0.25));
+        assert_eq!(from_pair, from_tuple);
+    }
+
+    #[test]
+    fn as_tag_round_trip() {
+        let extra = Tag::Dict([("gain".to_string(), Tag::Double(3.0))].into());
+        let header = HeaderBuilder::new()
+            .samp_rate(2e6)
+            .dtype(DataType::Short)
+            .cplx(true)
+            .rx_time(Timestamp::from_parts(12, 0.25))
+            .num_samples(1000)
+            .extra(extra.clone())
+            .strt(160)
+            .attached_at(4096)
+            .build();
+
+        let tag = header.as_tag();
+        assert_eq!(tag.get("bytes"), Some(&Tag::UInt64(4000)));
+        assert_eq!(Header::from_tags(4096, tag, extra).unwrap(), header);
+    }
+
+    #[test]
+    fn sort_by_position() {
+        let header = |pos: u64, abs_pos: u64| {
+            HeaderBuilder::new()
+                .dettached_at(pos, abs_pos)
+                .num_samples(10)
+                .build()
+        };
+        // Positions in the header file take precedence over those of the data
+        let ordered = vec![
+            header(0, 0),
+            header(150, 80),
+            header(300, 80),
+            header(450, 160),
+        ];
+        let mut shuffled = vec![
+            ordered[2].clone(),
+            ordered[0].clone(),
+            ordered[3].clone(),
+            ordered[1].clone(),
+        ];
+        shuffled.sort_by(Header::cmp_position);
+        assert_eq!(shuffled, ordered);
+
+        assert!(ordered[0] < ordered[1]);
+        assert!(ordered[3] > ordered[2]);
+        assert!(ordered[1] <= ordered[1].clone());
+        // Same position, but different headers
+        let other = HeaderBuilder::new().dettached_at(0, 0).build();
+        assert_eq!(ordered[0].cmp_position(&other), Ordering::Equal);
+        assert_eq!(ordered[0].partial_cmp(&other), None);
+    }
+
+    #[test]
+    fn convert_slice() {
+        let bytes: Vec<u8> = [1i16, -2, 300, 4]
+            .iter()
+            .flat_map(|v| v.to_be_bytes())
+            .collect();
+
+        let floats: Vec<f32> = DataType::Short
+            .convert_slice(false, &bytes, Endianness::Big)
+            .unwrap();
+        assert_eq!(floats, [1.0, -2.0, 300.0, 4.0]);
+
+        let complex: Vec<Complex<f32>> = DataType::Short
+            .convert_slice(true, &bytes, Endianness::Big)
+            .unwrap();
+        assert_eq!(complex, [Complex::new(1.0, -2.0), Complex::new(300.0, 4.0)]);
+
+        assert_eq!(
+            DataType::Short.convert_slice::<Complex<f32>>(true, &bytes[..6], Endianness::Big),
+            Err(ConvertError::Misaligned {
+                len: 6,
+                item_size: 4
+            })
+        );
+        assert!(matches!(
+            DataType::Short.convert_slice::<f32>(true, &bytes, Endianness::Big),
+            Err(ConvertError::Inconvertible { from: "ci16", .. })
+        ));
+        assert!(matches!(
+            DataType::Float.convert_slice::<i16>(false, &bytes, Endianness::Big),
+            Err(ConvertError::Inconvertible { .. })
+        ));
+    }
+
+    #[test]
+    fn legacy_header_without_strt() {
+        use crate::core::{AttachedHeader, SampleReadSeek};
+
+        let mut tag = header_tag(0, false, 1);
+        if let Tag::Dict(dict) = &mut tag {
+            dict.remove("strt");
+        }
+        let extra = Tag::Dict([("gain".to_string(), Tag::Double(3.0))].into());
+        let mut file = Vec::new();
+        write(&mut file, &tag).unwrap();
+        write(&mut file, &extra).unwrap();
+        let header_len = file.len() as u64;
+        file.extend((0..800).map(|i| i as u8));
+
+        let header = Header::from_tags(0, tag, extra).unwrap();
+        assert_eq!(header.strt, header_len);
+        assert_eq!(header.abs_pos, header_len);
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut buf = [0i8; 1000];
+        assert_eq!(reader.read_samples(&mut buf).unwrap(), 800);
+        assert_eq!(buf[..4], [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn out_of_range_fields() {
+        let with_field = |name: &str, value: Tag| {
+            let mut tag = header_tag(5, false, 4);
+            if let Tag::Dict(dict) = &mut tag {
+                dict.insert(name.to_string(), value);
+            }
+            Header::from_tags(0, tag, Tag::Null())
+        };
+        let rx_time = |secs: u64, frac: f64| Tag::Tuple(vec![Tag::UInt64(secs), Tag::Double(frac)]);
+
+        for value in [
+            rx_time(1 << 63, 0.0),
+            rx_time(0, f64::NAN),
+            rx_time(0, f64::INFINITY),
+        ] {
+            assert!(matches!(
+                with_field("rx_time", value),
+                Err(InvalidHeaderError::OutOfRangeField("rx_time"))
+            ));
+        }
+        for rate in [0.0, -1e6, f64::NAN, f64::INFINITY, 1e-320] {
+            assert!(matches!(
+                with_field("rx_rate", Tag::Double(rate)),
+                Err(InvalidHeaderError::OutOfRangeField("rx_rate"))
+            ));
+        }
+        assert!(matches!(
+            Header::from_tags(u64::MAX, header_tag(5, false, 4), Tag::Null()),
+            Err(InvalidHeaderError::OutOfRangeField("strt"))
+        ));
+        assert!(matches!(
+            with_field("bytes", Tag::UInt64(u64::MAX - 100)),
+            Err(InvalidHeaderError::OutOfRangeField("bytes"))
+        ));
+
+        let header = with_field("rx_time", rx_time((1 << 63) - 1, 0.5)).unwrap();
+        assert_eq!(header.get_sample_time(0), header.rx_time);
+    }
+
+    #[test]
+    fn size_as_uint64() {
+        let with_size = |size: Tag| {
+            let mut tag = header_tag(2, true, 8);
+            if let Tag::Dict(dict) = &mut tag {
+                dict.insert("size".to_string(), size);
+            }
+            Header::from_tags(0, tag, Tag::Null())
+        };
+        let header = with_size(Tag::UInt64(8)).unwrap();
+        assert_eq!(header.size, 8);
+        assert!(header.cplx);
+        assert_eq!(header.get_num_samples(), 100);
+
+        assert!(matches!(
+            with_size(Tag::UInt64(1 << 40)),
+            Err(InvalidHeaderError::SizeOutOfRange(0x100_0000_0000))
+        ));
+        assert!(matches!(
+            with_size(Tag::Double(8.0)),
+            Err(InvalidHeaderError::WrongTypeField("size"))
+        ));
+    }
+
+    #[test]
+    fn complex_integer_sizes() {
+        for (dtype, size) in [(0, 2), (1, 4), (2, 8)] {
+            let header = Header::from_tags(0, header_tag(dtype, true, size), Tag::Null()).unwrap();
+            assert!(header.cplx);
+            assert_eq!(header.size, size);
+            assert_eq!(header.get_num_samples(), 800 / size as u64);
+
+            // The scalar size is taken as a real segment
+            let header =
+                Header::from_tags(0, header_tag(dtype, true, size / 2), Tag::Null()).unwrap();
+            assert!(!header.cplx);
+            assert!(matches!(
+                Header::from_tags(0, header_tag(dtype, true, size + 1), Tag::Null()),
+                Err(InvalidHeaderError::InconsistentSize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rx_time_int32_seconds() {
+        let with_secs = |secs: Tag| {
+            let mut tag = header_tag(5, true, 8);
+            if let Tag::Dict(dict) = &mut tag {
+                dict.insert(
+                    "rx_time".to_string(),
+                    Tag::Tuple(vec![secs, Tag::Double(0.5)]),
+                );
+            }
+            Header::from_tags(0, tag, Tag::Null())
+        };
+
+        let header = with_secs(Tag::Int32(12)).unwrap();
+        assert_eq!(header.rx_time, Timestamp::from_parts(12, 0.5));
+        assert!(matches!(
+            with_secs(Tag::Int32(-1)),
+            Err(InvalidHeaderError::WrongTypeField("rx_time seconds"))
+        ));
+        assert!(matches!(
+            with_secs(Tag::Double(12.0)),
+            Err(InvalidHeaderError::WrongTypeField("rx_time seconds"))
+        ));
+    }
+
+    #[test]
+    fn size_cplx_contradictory() {
+        // size implies real floats, despite the complex flag
+        let header = Header::from_tags(0, header_tag(5, true, 4), Tag::Null()).unwrap();
+        assert!(!header.cplx);
+        assert_eq!(header.dtype, DataType::Float);
+
+        // size implies complex shorts
+        let header = Header::from_tags(0, header_tag(1, false, 4), Tag::Null()).unwrap();
+        assert!(header.cplx);
+
+        assert!(matches!(
+            Header::from_tags(0, header_tag(5, true, 6), Tag::Null()),
+            Err(InvalidHeaderError::InconsistentSize(6))
+        ));
+    }
+
+    #[test]
+    fn validate_warnings() {
+        let valid = HeaderBuilder::new().samp_rate(1e6).num_samples(100).build();
+        assert!(valid.validate().is_empty());
+
+        let header = Header {
+            samp_rate: 0.0,
+            ..valid.clone()
+        };
+        assert_eq!(
+            header.validate(),
+            vec![HeaderWarning::NonPositiveSampleRate(0.0)]
+        );
+        let header = Header {
+            samp_rate: f64::NAN,
+            ..valid.clone()
+        };
+        assert!(matches!(
+            header.validate()[..],
+            [HeaderWarning::NonPositiveSampleRate(_)]
+        ));
+
+        let header = Header {
+            size: 4,
+            bytes: 400,
+            ..valid.clone()
+        };
+        assert_eq!(
+            header.validate(),
+            vec![HeaderWarning::SizeMismatch {
+                size: 4,
+                expected: 8
+            }]
+        );
+
+        let header = Header {
+            bytes: 803,
+            ..valid.clone()
+        };
+        assert_eq!(
+            header.validate(),
+            vec![HeaderWarning::PartialSample {
+                bytes: 803,
+                size: 8
+            }]
+        );
+
+        let header = Header {
+            bytes: 1 << 41,
+            ..valid.clone()
+        };
+        assert_eq!(
+            header.validate(),
+            vec![HeaderWarning::SuspiciouslyLargeBytes(1 << 41)]
+        );
+
+        let future = Timestamp::from_parts(u32::MAX as u64 * 4, 0.0);
+        let header = Header {
+            rx_time: future,
+            ..valid.clone()
+        };
+        assert_eq!(
+            header.validate(),
+            vec![HeaderWarning::RxTimeInFuture(future)]
+        );
+
+        // All at once
+        let header = Header {
+            samp_rate: -1.0,
+            size: 3,
+            bytes: 1 << 41,
+            rx_time: future,
+            ..valid
+        };
+        assert_eq!(header.validate().len(), 5);
+    }
+
+    #[test]
+    fn human_summary() {
+        let header = Header {
+            samp_rate: 2e6,
+            samp_dur: 0.5e-6,
+            samp_rate_ratio: Some((2_000_000, 1)),
+            rx_time: Timestamp::from_secs_f64(0.524288),
+            size: 8,
+            dtype: DataType::Float,
+            cplx: true,
+            strt: 0,
+            bytes: 8 * 1048576,
+            extra_dict: Rc::new(Tag::Null()),
+            abs_pos: 0,
+            pos_in_file: 0,
+            attached: false,
+            first_sample: 0,
+        };
+        assert_eq!(
+            header.human_summary(),
+            "cf32 @ 2.000 MHz, 1048576 samples, t=+0.524288s, seg@byte 0"
+        );
+
+        let header = Header {
+            samp_rate: 48e3,
+            dtype: DataType::Short,
+            cplx: false,
+            size: 2,
+            bytes: 20,
+            abs_pos: 149,
+            ..header
+        };
+        assert_eq!(
+            header.human_summary(),
+            "i16 @ 48.000 kHz, 10 samples, t=+0.524288s, seg@byte 149"
+        );
+    }
+
     // Some very tedious tests ahead...
     #[test]
     fn dtype_byte() {
         assert!(!DataType::Byte.is_floating());
 
         assert!(DataType::Byte.reads_directly_to::<i8>(false));
-        assert!(!DataType::Byte.reads_directly_to::<u8>(false));
+        assert!(!reads_directly_to!(DataType::Byte, u8, false));
         assert!(!DataType::Byte.reads_directly_to::<i16>(false));
-        assert!(!DataType::Byte.reads_directly_to::<u16>(false));
+        assert!(!reads_directly_to!(DataType::Byte, u16, false));
         assert!(!DataType::Byte.reads_directly_to::<i32>(false));
-        assert!(!DataType::Byte.reads_directly_to::<u32>(false));
-        assert!(!DataType::Byte.reads_directly_to::<i64>(false));
-        assert!(!DataType::Byte.reads_directly_to::<u64>(false));
+        assert!(!reads_directly_to!(DataType::Byte, u32, false));
+        assert!(!reads_directly_to!(DataType::Byte, i64, false));
+        assert!(!reads_directly_to!(DataType::Byte, u64, false));
         assert!(!DataType::Byte.reads_directly_to::<f32>(false));
         assert!(!DataType::Byte.reads_directly_to::<f64>(false));
 
         assert!(DataType::Byte.converts_to::<i8>(false));
-        assert!(!DataType::Byte.converts_to::<u8>(false));
+        assert!(!converts_to!(DataType::Byte, u8, false));
         assert!(DataType::Byte.converts_to::<i16>(false));
-        assert!(!DataType::Byte.converts_to::<u16>(false));
+        assert!(!converts_to!(DataType::Byte, u16, false));
         assert!(DataType::Byte.converts_to::<i32>(false));
-        assert!(!DataType::Byte.converts_to::<u32>(false));
-        assert!(!DataType::Byte.converts_to::<i64>(false));
-        assert!(!DataType::Byte.converts_to::<u64>(false));
+        assert!(!converts_to!(DataType::Byte, u32, false));
+        assert!(!converts_to!(DataType::Byte, i64, false));
+        assert!(!converts_to!(DataType::Byte, u64, false));
         assert!(DataType::Byte.converts_to::<f32>(false));
         assert!(DataType::Byte.converts_to::<f64>(false));
 
         assert!(DataType::Byte.reads_directly_to::<Complex<i8>>(true));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u8>>(true));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u8>, true));
         assert!(!DataType::Byte.reads_directly_to::<Complex<i16>>(true));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u16>>(true));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u16>, true));
         assert!(!DataType::Byte.reads_directly_to::<Complex<i32>>(true));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u32>>(true));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<i64>>(true));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u64>>(true));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u32>, true));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<i64>, true));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u64>, true));
         assert!(!DataType::Byte.reads_directly_to::<Complex<f32>>(true));
         assert!(!DataType::Byte.reads_directly_to::<Complex<f64>>(true));
 
         assert!(DataType::Byte.converts_to::<Complex<i8>>(true));
-        assert!(!DataType::Byte.converts_to::<Complex<u8>>(true));
+        assert!(!converts_to!(DataType::Byte, Complex<u8>, true));
         assert!(DataType::Byte.converts_to::<Complex<i16>>(true));
-        assert!(!DataType::Byte.converts_to::<Complex<u16>>(true));
+        assert!(!converts_to!(DataType::Byte, Complex<u16>, true));
         assert!(DataType::Byte.converts_to::<Complex<i32>>(true));
-        assert!(!DataType::Byte.converts_to::<Complex<u32>>(true));
-        assert!(!DataType::Byte.converts_to::<Complex<i64>>(true));
-        assert!(!DataType::Byte.converts_to::<Complex<u64>>(true));
+        assert!(!converts_to!(DataType::Byte, Complex<u32>, true));
+        assert!(!converts_to!(DataType::Byte, Complex<i64>, true));
+        assert!(!converts_to!(DataType::Byte, Complex<u64>, true));
         assert!(DataType::Byte.converts_to::<Complex<f32>>(true));
         assert!(DataType::Byte.converts_to::<Complex<f64>>(true));
 
         assert!(!DataType::Byte.reads_directly_to::<Complex<i8>>(false));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u8>>(false));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u8>, false));
         assert!(!DataType::Byte.reads_directly_to::<Complex<i16>>(false));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u16>>(false));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u16>, false));
         assert!(!DataType::Byte.reads_directly_to::<Complex<i32>>(false));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u32>>(false));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<i64>>(false));
-        assert!(!DataType::Byte.reads_directly_to::<Complex<u64>>(false));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u32>, false));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<i64>, false));
+        assert!(!reads_directly_to!(DataType::Byte, Complex<u64>, false));
         assert!(!DataType::Byte.reads_directly_to::<Complex<f32>>(false));
         assert!(!DataType::Byte.reads_directly_to::<Complex<f64>>(false));
 
         assert!(!DataType::Byte.reads_directly_to::<i8>(true));
-        assert!(!DataType::Byte.reads_directly_to::<u8>(true));
+        assert!(!reads_directly_to!(DataType::Byte, u8, true));
         assert!(!DataType::Byte.reads_directly_to::<i16>(true));
-        assert!(!DataType::Byte.reads_directly_to::<u16>(true));
+        assert!(!reads_directly_to!(DataType::Byte, u16, true));
         assert!(!DataType::Byte.reads_directly_to::<i32>(true));
-        assert!(!DataType::Byte.reads_directly_to::<u32>(true));
-        assert!(!DataType::Byte.reads_directly_to::<i64>(true));
-        assert!(!DataType::Byte.reads_directly_to::<u64>(true));
+        assert!(!reads_directly_to!(DataType::Byte, u32, true));
+        assert!(!reads_directly_to!(DataType::Byte, i64, true));
+        assert!(!reads_directly_to!(DataType::Byte, u64, true));
         assert!(!DataType::Byte.reads_directly_to::<f32>(true));
         assert!(!DataType::Byte.reads_directly_to::<f64>(true));
     }
@@ -441,68 +1875,68 @@ mod header_tests {
         assert!(!DataType::Short.is_floating());
 
         assert!(!DataType::Short.reads_directly_to::<i8>(false));
-        assert!(!DataType::Short.reads_directly_to::<u8>(false));
+        assert!(!reads_directly_to!(DataType::Short, u8, false));
         assert!(DataType::Short.reads_directly_to::<i16>(false));
-        assert!(!DataType::Short.reads_directly_to::<u16>(false));
+        assert!(!reads_directly_to!(DataType::Short, u16, false));
         assert!(!DataType::Short.reads_directly_to::<i32>(false));
-        assert!(!DataType::Short.reads_directly_to::<u32>(false));
-        assert!(!DataType::Short.reads_directly_to::<i64>(false));
-        assert!(!DataType::Short.reads_directly_to::<u64>(false));
+        assert!(!reads_directly_to!(DataType::Short, u32, false));
+        assert!(!reads_directly_to!(DataType::Short, i64, false));
+        assert!(!reads_directly_to!(DataType::Short, u64, false));
         assert!(!DataType::Short.reads_directly_to::<f32>(false));
         assert!(!DataType::Short.reads_directly_to::<f64>(false));
 
         assert!(!DataType::Short.converts_to::<i8>(false));
-        assert!(!DataType::Short.converts_to::<u8>(false));
+        assert!(!converts_to!(DataType::Short, u8, false));
         assert!(DataType::Short.converts_to::<i16>(false));
-        assert!(!DataType::Short.converts_to::<u16>(false));
+        assert!(!converts_to!(DataType::Short, u16, false));
         assert!(DataType::Short.converts_to::<i32>(false));
-        assert!(!DataType::Short.converts_to::<u32>(false));
-        assert!(!DataType::Short.converts_to::<i64>(false));
-        assert!(!DataType::Short.converts_to::<u64>(false));
+        assert!(!converts_to!(DataType::Short, u32, false));
+        assert!(!converts_to!(DataType::Short, i64, false));
+        assert!(!converts_to!(DataType::Short, u64, false));
         assert!(DataType::Short.converts_to::<f32>(false));
         assert!(DataType::Short.converts_to::<f64>(false));
 
         assert!(!DataType::Short.reads_directly_to::<Complex<i8>>(true));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u8>>(true));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u8>, true));
         assert!(DataType::Short.reads_directly_to::<Complex<i16>>(true));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u16>>(true));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u16>, true));
         assert!(!DataType::Short.reads_directly_to::<Complex<i32>>(true));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u32>>(true));
-        assert!(!DataType::Short.reads_directly_to::<Complex<i64>>(true));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u64>>(true));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u32>, true));
+        assert!(!reads_directly_to!(DataType::Short, Complex<i64>, true));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u64>, true));
         assert!(!DataType::Short.reads_directly_to::<Complex<f32>>(true));
         assert!(!DataType::Short.reads_directly_to::<Complex<f64>>(true));
 
         assert!(!DataType::Short.converts_to::<Complex<i8>>(true));
-        assert!(!DataType::Short.converts_to::<Complex<u8>>(true));
+        assert!(!converts_to!(DataType::Short, Complex<u8>, true));
         assert!(DataType::Short.converts_to::<Complex<i16>>(true));
-        assert!(!DataType::Short.converts_to::<Complex<u16>>(true));
+        assert!(!converts_to!(DataType::Short, Complex<u16>, true));
         assert!(DataType::Short.converts_to::<Complex<i32>>(true));
-        assert!(!DataType::Short.converts_to::<Complex<u32>>(true));
-        assert!(!DataType::Short.converts_to::<Complex<i64>>(true));
-        assert!(!DataType::Short.converts_to::<Complex<u64>>(true));
+        assert!(!converts_to!(DataType::Short, Complex<u32>, true));
+        assert!(!converts_to!(DataType::Short, Complex<i64>, true));
+        assert!(!converts_to!(DataType::Short, Complex<u64>, true));
         assert!(DataType::Short.converts_to::<Complex<f32>>(true));
         assert!(DataType::Short.converts_to::<Complex<f64>>(true));
 
         assert!(!DataType::Short.reads_directly_to::<Complex<i8>>(false));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u8>>(false));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u8>, false));
         assert!(!DataType::Short.reads_directly_to::<Complex<i16>>(false));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u16>>(false));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u16>, false));
         assert!(!DataType::Short.reads_directly_to::<Complex<i32>>(false));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u32>>(false));
-        assert!(!DataType::Short.reads_directly_to::<Complex<i64>>(false));
-        assert!(!DataType::Short.reads_directly_to::<Complex<u64>>(false));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u32>, false));
+        assert!(!reads_directly_to!(DataType::Short, Complex<i64>, false));
+        assert!(!reads_directly_to!(DataType::Short, Complex<u64>, false));
         assert!(!DataType::Short.reads_directly_to::<Complex<f32>>(false));
         assert!(!DataType::Short.reads_directly_to::<Complex<f64>>(false));
 
         assert!(!DataType::Short.reads_directly_to::<i8>(true));
-        assert!(!DataType::Short.reads_directly_to::<u8>(true));
+        assert!(!reads_directly_to!(DataType::Short, u8, true));
         assert!(!DataType::Short.reads_directly_to::<i16>(true));
-        assert!(!DataType::Short.reads_directly_to::<u16>(true));
+        assert!(!reads_directly_to!(DataType::Short, u16, true));
         assert!(!DataType::Short.reads_directly_to::<i32>(true));
-        assert!(!DataType::Short.reads_directly_to::<u32>(true));
-        assert!(!DataType::Short.reads_directly_to::<i64>(true));
-        assert!(!DataType::Short.reads_directly_to::<u64>(true));
+        assert!(!reads_directly_to!(DataType::Short, u32, true));
+        assert!(!reads_directly_to!(DataType::Short, i64, true));
+        assert!(!reads_directly_to!(DataType::Short, u64, true));
         assert!(!DataType::Short.reads_directly_to::<f32>(true));
         assert!(!DataType::Short.reads_directly_to::<f64>(true));
     }
@@ -512,68 +1946,68 @@ mod header_tests {
         assert!(!DataType::Int.is_floating());
 
         assert!(!DataType::Int.reads_directly_to::<i8>(false));
-        assert!(!DataType::Int.reads_directly_to::<u8>(false));
+        assert!(!reads_directly_to!(DataType::Int, u8, false));
         assert!(!DataType::Int.reads_directly_to::<i16>(false));
-        assert!(!DataType::Int.reads_directly_to::<u16>(false));
+        assert!(!reads_directly_to!(DataType::Int, u16, false));
         assert!(DataType::Int.reads_directly_to::<i32>(false));
-        assert!(!DataType::Int.reads_directly_to::<u32>(false));
-        assert!(!DataType::Int.reads_directly_to::<i64>(false));
-        assert!(!DataType::Int.reads_directly_to::<u64>(false));
+        assert!(!reads_directly_to!(DataType::Int, u32, false));
+        assert!(!reads_directly_to!(DataType::Int, i64, false));
+        assert!(!reads_directly_to!(DataType::Int, u64, false));
         assert!(!DataType::Int.reads_directly_to::<f32>(false));
         assert!(!DataType::Int.reads_directly_to::<f64>(false));
 
         assert!(!DataType::Int.converts_to::<i8>(false));
-        assert!(!DataType::Int.converts_to::<u8>(false));
+        assert!(!converts_to!(DataType::Int, u8, false));
         assert!(!DataType::Int.converts_to::<i16>(false));
-        assert!(!DataType::Int.converts_to::<u16>(false));
+        assert!(!converts_to!(DataType::Int, u16, false));
         assert!(DataType::Int.converts_to::<i32>(false));
-        assert!(!DataType::Int.converts_to::<u32>(false));
-        assert!(!DataType::Int.converts_to::<i64>(false));
-        assert!(!DataType::Int.converts_to::<u64>(false));
+        assert!(!converts_to!(DataType::Int, u32, false));
+        assert!(!converts_to!(DataType::Int, i64, false));
+        assert!(!converts_to!(DataType::Int, u64, false));
         assert!(DataType::Int.converts_to::<f32>(false));
         assert!(DataType::Int.converts_to::<f64>(false));
 
         assert!(!DataType::Int.reads_directly_to::<Complex<i8>>(true));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u8>>(true));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u8>, true));
         assert!(!DataType::Int.reads_directly_to::<Complex<i16>>(true));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u16>>(true));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u16>, true));
         assert!(DataType::Int.reads_directly_to::<Complex<i32>>(true));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u32>>(true));
-        assert!(!DataType::Int.reads_directly_to::<Complex<i64>>(true));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u64>>(true));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u32>, true));
+        assert!(!reads_directly_to!(DataType::Int, Complex<i64>, true));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u64>, true));
         assert!(!DataType::Int.reads_directly_to::<Complex<f32>>(true));
         assert!(!DataType::Int.reads_directly_to::<Complex<f64>>(true));
 
         assert!(!DataType::Int.converts_to::<Complex<i8>>(true));
-        assert!(!DataType::Int.converts_to::<Complex<u8>>(true));
+        assert!(!converts_to!(DataType::Int, Complex<u8>, true));
         assert!(!DataType::Int.converts_to::<Complex<i16>>(true));
-        assert!(!DataType::Int.converts_to::<Complex<u16>>(true));
+        assert!(!converts_to!(DataType::Int, Complex<u16>, true));
         assert!(DataType::Int.converts_to::<Complex<i32>>(true));
-        assert!(!DataType::Int.converts_to::<Complex<u32>>(true));
-        assert!(!DataType::Int.converts_to::<Complex<i64>>(true));
-        assert!(!DataType::Int.converts_to::<Complex<u64>>(true));
+        assert!(!converts_to!(DataType::Int, Complex<u32>, true));
+        assert!(!converts_to!(DataType::Int, Complex<i64>, true));
+        assert!(!converts_to!(DataType::Int, Complex<u64>, true));
         assert!(DataType::Int.converts_to::<Complex<f32>>(true));
         assert!(DataType::Int.converts_to::<Complex<f64>>(true));
 
         assert!(!DataType::Int.reads_directly_to::<Complex<i8>>(false));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u8>>(false));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u8>, false));
         assert!(!DataType::Int.reads_directly_to::<Complex<i16>>(false));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u16>>(false));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u16>, false));
         assert!(!DataType::Int.reads_directly_to::<Complex<i32>>(false));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u32>>(false));
-        assert!(!DataType::Int.reads_directly_to::<Complex<i64>>(false));
-        assert!(!DataType::Int.reads_directly_to::<Complex<u64>>(false));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u32>, false));
+        assert!(!reads_directly_to!(DataType::Int, Complex<i64>, false));
+        assert!(!reads_directly_to!(DataType::Int, Complex<u64>, false));
         assert!(!DataType::Int.reads_directly_to::<Complex<f32>>(false));
         assert!(!DataType::Int.reads_directly_to::<Complex<f64>>(false));
 
         assert!(!DataType::Int.reads_directly_to::<i8>(true));
-        assert!(!DataType::Int.reads_directly_to::<u8>(true));
+        assert!(!reads_directly_to!(DataType::Int, u8, true));
         assert!(!DataType::Int.reads_directly_to::<i16>(true));
-        assert!(!DataType::Int.reads_directly_to::<u16>(true));
+        assert!(!reads_directly_to!(DataType::Int, u16, true));
         assert!(!DataType::Int.reads_directly_to::<i32>(true));
-        assert!(!DataType::Int.reads_directly_to::<u32>(true));
-        assert!(!DataType::Int.reads_directly_to::<i64>(true));
-        assert!(!DataType::Int.reads_directly_to::<u64>(true));
+        assert!(!reads_directly_to!(DataType::Int, u32, true));
+        assert!(!reads_directly_to!(DataType::Int, i64, true));
+        assert!(!reads_directly_to!(DataType::Int, u64, true));
         assert!(!DataType::Int.reads_directly_to::<f32>(true));
         assert!(!DataType::Int.reads_directly_to::<f64>(true));
     }
@@ -583,140 +2017,269 @@ mod header_tests {
         assert!(DataType::Float.is_floating());
 
         assert!(!DataType::Float.reads_directly_to::<i8>(false));
-        assert!(!DataType::Float.reads_directly_to::<u8>(false));
+        assert!(!reads_directly_to!(DataType::Float, u8, false));
         assert!(!DataType::Float.reads_directly_to::<i16>(false));
-        assert!(!DataType::Float.reads_directly_to::<u16>(false));
+        assert!(!reads_directly_to!(DataType::Float, u16, false));
         assert!(!DataType::Float.reads_directly_to::<i32>(false));
-        assert!(!DataType::Float.reads_directly_to::<u32>(false));
-        assert!(!DataType::Float.reads_directly_to::<i64>(false));
-        assert!(!DataType::Float.reads_directly_to::<u64>(false));
+        assert!(!reads_directly_to!(DataType::Float, u32, false));
+        assert!(!reads_directly_to!(DataType::Float, i64, false));
+        assert!(!reads_directly_to!(DataType::Float, u64, false));
         assert!(DataType::Float.reads_directly_to::<f32>(false));
         assert!(!DataType::Float.reads_directly_to::<f64>(false));
 
         assert!(!DataType::Float.converts_to::<i8>(false));
-        assert!(!DataType::Float.converts_to::<u8>(false));
+        assert!(!converts_to!(DataType::Float, u8, false));
         assert!(!DataType::Float.converts_to::<i16>(false));
-        assert!(!DataType::Float.converts_to::<u16>(false));
+        assert!(!converts_to!(DataType::Float, u16, false));
         assert!(!DataType::Float.converts_to::<i32>(false));
-        assert!(!DataType::Float.converts_to::<u32>(false));
-        assert!(!DataType::Float.converts_to::<i64>(false));
-        assert!(!DataType::Float.converts_to::<u64>(false));
+        assert!(!converts_to!(DataType::Float, u32, false));
+        assert!(!converts_to!(DataType::Float, i64, false));
+        assert!(!converts_to!(DataType::Float, u64, false));
         assert!(DataType::Float.converts_to::<f32>(false));
         assert!(DataType::Float.converts_to::<f64>(false));
 
         assert!(!DataType::Float.reads_directly_to::<Complex<i8>>(true));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u8>>(true));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u8>, true));
         assert!(!DataType::Float.reads_directly_to::<Complex<i16>>(true));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u16>>(true));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u16>, true));
         assert!(!DataType::Float.reads_directly_to::<Complex<i32>>(true));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u32>>(true));
-        assert!(!DataType::Float.reads_directly_to::<Complex<i64>>(true));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u64>>(true));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u32>, true));
+        assert!(!reads_directly_to!(DataType::Float, Complex<i64>, true));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u64>, true));
         assert!(DataType::Float.reads_directly_to::<Complex<f32>>(true));
         assert!(!DataType::Float.reads_directly_to::<Complex<f64>>(true));
 
         assert!(!DataType::Float.converts_to::<Complex<i8>>(true));
-        assert!(!DataType::Float.converts_to::<Complex<u8>>(true));
+        assert!(!converts_to!(DataType::Float, Complex<u8>, true));
         assert!(!DataType::Float.converts_to::<Complex<i16>>(true));
-        assert!(!DataType::Float.converts_to::<Complex<u16>>(true));
+        assert!(!converts_to!(DataType::Float, Complex<u16>, true));
         assert!(!DataType::Float.converts_to::<Complex<i32>>(true));
-        assert!(!DataType::Float.converts_to::<Complex<u32>>(true));
-        assert!(!DataType::Float.converts_to::<Complex<i64>>(true));
-        assert!(!DataType::Float.converts_to::<Complex<u64>>(true));
+        assert!(!converts_to!(DataType::Float, Complex<u32>, true));
+        assert!(!converts_to!(DataType::Float, Complex<i64>, true));
+        assert!(!converts_to!(DataType::Float, Complex<u64>, true));
         assert!(DataType::Float.converts_to::<Complex<f32>>(true));
         assert!(DataType::Float.converts_to::<Complex<f64>>(true));
 
         assert!(!DataType::Float.reads_directly_to::<Complex<i8>>(false));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u8>>(false));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u8>, false));
         assert!(!DataType::Float.reads_directly_to::<Complex<i16>>(false));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u16>>(false));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u16>, false));
         assert!(!DataType::Float.reads_directly_to::<Complex<i32>>(false));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u32>>(false));
-        assert!(!DataType::Float.reads_directly_to::<Complex<i64>>(false));
-        assert!(!DataType::Float.reads_directly_to::<Complex<u64>>(false));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u32>, false));
+        assert!(!reads_directly_to!(DataType::Float, Complex<i64>, false));
+        assert!(!reads_directly_to!(DataType::Float, Complex<u64>, false));
         assert!(!DataType::Float.reads_directly_to::<Complex<f32>>(false));
         assert!(!DataType::Float.reads_directly_to::<Complex<f64>>(false));
 
         assert!(!DataType::Float.reads_directly_to::<i8>(true));
-        assert!(!DataType::Float.reads_directly_to::<u8>(true));
+        assert!(!reads_directly_to!(DataType::Float, u8, true));
         assert!(!DataType::Float.reads_directly_to::<i16>(true));
-        assert!(!DataType::Float.reads_directly_to::<u16>(true));
+        assert!(!reads_directly_to!(DataType::Float, u16, true));
         assert!(!DataType::Float.reads_directly_to::<i32>(true));
-        assert!(!DataType::Float.reads_directly_to::<u32>(true));
-        assert!(!DataType::Float.reads_directly_to::<i64>(true));
-        assert!(!DataType::Float.reads_directly_to::<u64>(true));
+        assert!(!reads_directly_to!(DataType::Float, u32, true));
+        assert!(!reads_directly_to!(DataType::Float, i64, true));
+        assert!(!reads_directly_to!(DataType::Float, u64, true));
         assert!(!DataType::Float.reads_directly_to::<f32>(true));
         assert!(!DataType::Float.reads_directly_to::<f64>(true));
     }
 
+    #[test]
+    fn dtype_introspection() {
+        let all = [
+            DataType::Byte,
+            DataType::Short,
+            DataType::Int,
+            DataType::Float,
+            DataType::Double,
+        ];
+        let widths: Vec<u32> = all.iter().map(DataType::bit_width).collect();
+        assert_eq!(widths, [8, 16, 32, 32, 64]);
+        let integer: Vec<bool> = all.iter().map(DataType::is_integer).collect();
+        assert_eq!(integer, [true, true, true, false, false]);
+        assert!(all.iter().all(DataType::is_signed));
+        for dtype in all {
+            assert_eq!(dtype.item_size_bytes(false) * 8, dtype.bit_width() as usize);
+            assert_eq!(
+                dtype.item_size_bytes(true),
+                2 * dtype.item_size_bytes(false)
+            );
+        }
+    }
+
+    #[test]
+    fn gnuradio_names() {
+        assert_eq!(
+            DataType::from_gnuradio_name("complex"),
+            Some((DataType::Float, true))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name("Complex Float"),
+            Some((DataType::Float, true))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name("float"),
+            Some((DataType::Float, false))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name(" INT "),
+            Some((DataType::Int, false))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name("short"),
+            Some((DataType::Short, false))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name("byte"),
+            Some((DataType::Byte, false))
+        );
+        assert_eq!(
+            DataType::from_gnuradio_name("complex  double"),
+            Some((DataType::Double, true))
+        );
+        assert_eq!(DataType::from_gnuradio_name("complex bogus"), None);
+        assert_eq!(DataType::from_gnuradio_name("half"), None);
+        assert_eq!(DataType::from_gnuradio_name(""), None);
+
+        for dtype in [
+            DataType::Byte,
+            DataType::Short,
+            DataType::Int,
+            DataType::Float,
+            DataType::Double,
+        ] {
+            for complex in [false, true] {
+                let name = dtype.gnuradio_name(complex);
+                assert_eq!(DataType::from_gnuradio_name(name), Some((dtype, complex)));
+            }
+        }
+    }
+
     #[test]
     fn dtype_double() {
         assert!(DataType::Double.is_floating());
 
         assert!(!DataType::Double.reads_directly_to::<i8>(false));
-        assert!(!DataType::Double.reads_directly_to::<u8>(false));
+        assert!(!reads_directly_to!(DataType::Double, u8, false));
         assert!(!DataType::Double.reads_directly_to::<i16>(false));
-        assert!(!DataType::Double.reads_directly_to::<u16>(false));
+        assert!(!reads_directly_to!(DataType::Double, u16, false));
         assert!(!DataType::Double.reads_directly_to::<i32>(false));
-        assert!(!DataType::Double.reads_directly_to::<u32>(false));
-        assert!(!DataType::Double.reads_directly_to::<i64>(false));
-        assert!(!DataType::Double.reads_directly_to::<u64>(false));
+        assert!(!reads_directly_to!(DataType::Double, u32, false));
+        assert!(!reads_directly_to!(DataType::Double, i64, false));
+        assert!(!reads_directly_to!(DataType::Double, u64, false));
         assert!(!DataType::Double.reads_directly_to::<f32>(false));
         assert!(DataType::Double.reads_directly_to::<f64>(false));
 
         assert!(!DataType::Double.converts_to::<i8>(false));
-        assert!(!DataType::Double.converts_to::<u8>(false));
+        assert!(!converts_to!(DataType::Double, u8, false));
         assert!(!DataType::Double.converts_to::<i16>(false));
-        assert!(!DataType::Double.converts_to::<u16>(false));
+        assert!(!converts_to!(DataType::Double, u16, false));
         assert!(!DataType::Double.converts_to::<i32>(false));
-        assert!(!DataType::Double.converts_to::<u32>(false));
-        assert!(!DataType::Double.converts_to::<i64>(false));
-        assert!(!DataType::Double.converts_to::<u64>(false));
+        assert!(!converts_to!(DataType::Double, u32, false));
+        assert!(!converts_to!(DataType::Double, i64, false));
+        assert!(!converts_to!(DataType::Double, u64, false));
         assert!(DataType::Double.converts_to::<f32>(false));
         assert!(DataType::Double.converts_to::<f64>(false));
 
         assert!(!DataType::Double.reads_directly_to::<Complex<i8>>(true));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u8>>(true));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u8>, true));
         assert!(!DataType::Double.reads_directly_to::<Complex<i16>>(true));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u16>>(true));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u16>, true));
         assert!(!DataType::Double.reads_directly_to::<Complex<i32>>(true));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u32>>(true));
-        assert!(!DataType::Double.reads_directly_to::<Complex<i64>>(true));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u64>>(true));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u32>, true));
+        assert!(!reads_directly_to!(DataType::Double, Complex<i64>, true));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u64>, true));
         assert!(!DataType::Double.reads_directly_to::<Complex<f32>>(true));
         assert!(DataType::Double.reads_directly_to::<Complex<f64>>(true));
 
         assert!(!DataType::Double.converts_to::<Complex<i8>>(true));
-        assert!(!DataType::Double.converts_to::<Complex<u8>>(true));
+        assert!(!converts_to!(DataType::Double, Complex<u8>, true));
         assert!(!DataType::Double.converts_to::<Complex<i16>>(true));
-        assert!(!DataType::Double.converts_to::<Complex<u16>>(true));
+        assert!(!converts_to!(DataType::Double, Complex<u16>, true));
         assert!(!DataType::Double.converts_to::<Complex<i32>>(true));
-        assert!(!DataType::Double.converts_to::<Complex<u32>>(true));
-        assert!(!DataType::Double.converts_to::<Complex<i64>>(true));
-        assert!(!DataType::Double.converts_to::<Complex<u64>>(true));
+        assert!(!converts_to!(DataType::Double, Complex<u32>, true));
+        assert!(!converts_to!(DataType::Double, Complex<i64>, true));
+        assert!(!converts_to!(DataType::Double, Complex<u64>, true));
         assert!(DataType::Double.converts_to::<Complex<f32>>(true));
         assert!(DataType::Double.converts_to::<Complex<f64>>(true));
 
         assert!(!DataType::Double.reads_directly_to::<Complex<i8>>(false));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u8>>(false));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u8>, false));
         assert!(!DataType::Double.reads_directly_to::<Complex<i16>>(false));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u16>>(false));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u16>, false));
         assert!(!DataType::Double.reads_directly_to::<Complex<i32>>(false));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u32>>(false));
-        assert!(!DataType::Double.reads_directly_to::<Complex<i64>>(false));
-        assert!(!DataType::Double.reads_directly_to::<Complex<u64>>(false));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u32>, false));
+        assert!(!reads_directly_to!(DataType::Double, Complex<i64>, false));
+        assert!(!reads_directly_to!(DataType::Double, Complex<u64>, false));
         assert!(!DataType::Double.reads_directly_to::<Complex<f32>>(false));
         assert!(!DataType::Double.reads_directly_to::<Complex<f64>>(false));
 
         assert!(!DataType::Double.reads_directly_to::<i8>(true));
-        assert!(!DataType::Double.reads_directly_to::<u8>(true));
+        assert!(!reads_directly_to!(DataType::Double, u8, true));
         assert!(!DataType::Double.reads_directly_to::<i16>(true));
-        assert!(!DataType::Double.reads_directly_to::<u16>(true));
+        assert!(!reads_directly_to!(DataType::Double, u16, true));
         assert!(!DataType::Double.reads_directly_to::<i32>(true));
-        assert!(!DataType::Double.reads_directly_to::<u32>(true));
-        assert!(!DataType::Double.reads_directly_to::<i64>(true));
-        assert!(!DataType::Double.reads_directly_to::<u64>(true));
+        assert!(!reads_directly_to!(DataType::Double, u32, true));
+        assert!(!reads_directly_to!(DataType::Double, i64, true));
+        assert!(!reads_directly_to!(DataType::Double, u64, true));
         assert!(!DataType::Double.reads_directly_to::<f32>(true));
         assert!(!DataType::Double.reads_directly_to::<f64>(true));
     }
+
+    #[test]
+    fn header_from_bytes() {
+        let mut main = Vec::new();
+        write(&mut main, &header_tag(1, false, 2)).unwrap();
+        let mut extra = Vec::new();
+        let dict = Tag::Dict([("rx_freq".to_string(), Tag::Double(1e8))].into());
+        write(&mut extra, &dict).unwrap();
+
+        let header = Header::try_from_bytes(&main, &extra, 1000).unwrap();
+        assert_eq!(header.dtype, DataType::Short);
+        assert_eq!(header.get_num_samples(), 400);
+        assert_eq!(header.abs_pos, 1149);
+        assert_eq!(header.extra_dict.get("rx_freq"), Some(&Tag::Double(1e8)));
+
+        let header = Header::try_from_bytes(&main, &[], 0).unwrap();
+        assert_eq!(*header.extra_dict, Tag::Null());
+        assert!(matches!(
+            Header::try_from_bytes(&main[..10], &[], 0),
+            Err(InvalidHeaderError::ParseError(_))
+        ));
+
+        let mut trailing = main.clone();
+        trailing.push(0);
+        assert!(matches!(
+            Header::try_from_bytes(&trailing, &extra, 0),
+            Err(InvalidHeaderError::TrailingBytes("header"))
+        ));
+        let mut trailing = extra.clone();
+        trailing.extend_from_slice(&extra);
+        assert!(matches!(
+            Header::try_from_bytes(&main, &trailing, 0),
+            Err(InvalidHeaderError::TrailingBytes("extra"))
+        ));
+    }
+
+    #[test]
+    fn extra_dict_equality() {
+        let extra = || Tag::Dict([("rx_freq".to_string(), Tag::Double(1e8))].into());
+        let header = HeaderBuilder::new().extra(extra()).build();
+
+        // Sharing the extra dict
+        let shifted = header.with_corrected_rx_time(Timestamp::from_secs_f64(1.0));
+        assert!(Rc::ptr_eq(&header.extra_dict, &shifted.extra_dict));
+        assert!(header.extra_eq(&shifted));
+        assert_ne!(header, shifted);
+
+        // Distinct but equal extra dicts
+        let other = HeaderBuilder::new()
+            .dtype(DataType::Short)
+            .extra(extra())
+            .build();
+        assert!(!Rc::ptr_eq(&header.extra_dict, &other.extra_dict));
+        assert!(header.extra_eq(&other));
+
+        let different = HeaderBuilder::new().extra(Tag::Null()).build();
+        assert!(!header.extra_eq(&different));
+    }
 }
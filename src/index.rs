@@ -0,0 +1,208 @@
+//! Table of contents of a file, which can be persisted to open the file again without scanning
+//! all of its headers. See HeaderReader::build_index, AttachedHeader::with_index and
+//! DettachedHeader::with_index.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::core::MetaFileError;
+use crate::header::{DataType, Format, Header, samp_rate_ratio};
+use crate::pmt::{Tag, Timestamp, parse, write};
+
+/// Version of the serialized index, increased on incompatible changes
+const INDEX_VERSION: i32 = 1;
+
+/// Everything known about a segment of the file
+#[derive(Clone, Debug, PartialEq)]
+pub struct SegmentInfo {
+    /// Global index of the first sample of the segment, counting from the start of the file
+    pub first_sample: u64,
+    /// Bytes of the samples of the segment in the binary file
+    pub byte_range: Range<u64>,
+    pub rx_time: Timestamp,
+    pub format: Format,
+    pub samp_rate: f64,
+    pub num_samples: u64,
+    /// Position of the header, in the binary file if attached or in the header file if dettached
+    pub header_pos: u64,
+    /// Length of the header and its extra dict, see Header::strt
+    pub header_len: u64,
+    pub extra: Tag,
+}
+
+impl SegmentInfo {
+    pub fn from_header(header: &Header, first_sample: u64) -> SegmentInfo {
+        SegmentInfo {
+            first_sample,
+            byte_range: header.data_file_range(),
+            rx_time: header.rx_time,
+            format: header.format(),
+            samp_rate: header.samp_rate,
+            num_samples: header.get_num_samples(),
+            header_pos: header.pos_in_file,
+            header_len: header.strt,
+            extra: (*header.extra_dict).clone(),
+        }
+    }
+
+    /// Rebuilds the header of the segment
+    pub fn to_header(&self, attached: bool) -> Header {
+        Header {
+            samp_rate: self.samp_rate,
+            samp_dur: 1.0 / self.samp_rate,
+            samp_rate_ratio: samp_rate_ratio(self.samp_rate),
+            rx_time: self.rx_time,
+            size: self.format.dtype.item_size_bytes(self.format.complex) as i32,
+            dtype: self.format.dtype,
+            cplx: self.format.complex,
+            strt: self.header_len,
+            bytes: self.byte_range.end - self.byte_range.start,
+            extra_dict: Rc::new(self.extra.clone()),
+            abs_pos: self.byte_range.start,
+            pos_in_file: self.header_pos,
+            attached,
+            first_sample: self.first_sample,
+        }
+    }
+
+    fn to_tag(&self) -> Tag {
+        let bits = self.rx_time.to_bits();
+        let mut dict = BTreeMap::new();
+        dict.insert("first_sample".to_string(), Tag::UInt64(self.first_sample));
+        dict.insert("start".to_string(), Tag::UInt64(self.byte_range.start));
+        dict.insert("end".to_string(), Tag::UInt64(self.byte_range.end));
+        // Stored exactly, unlike the (seconds, fraction) pair of GNU Radio
+        dict.insert(
+            "rx_time".to_string(),
+            Tag::Tuple(vec![
+                Tag::UInt64((bits >> 64) as u64),
+                Tag::UInt64(bits as u64),
+            ]),
+        );
+        dict.insert("type".to_string(), Tag::Int32(self.format.dtype.to_int()));
+        dict.insert("cplx".to_string(), Tag::Bool(self.format.complex));
+        dict.insert("rx_rate".to_string(), Tag::Double(self.samp_rate));
+        dict.insert("num_samples".to_string(), Tag::UInt64(self.num_samples));
+        dict.insert("header_pos".to_string(), Tag::UInt64(self.header_pos));
+        dict.insert("header_len".to_string(), Tag::UInt64(self.header_len));
+        dict.insert("extra".to_string(), self.extra.clone());
+        Tag::Dict(dict)
+    }
+
+    fn from_tag(tag: &Tag) -> Result<SegmentInfo, MetaFileError> {
+        let field = |name: &'static str| tag.get(name).ok_or(MetaFileError::InvalidIndex(name));
+        let u64_field = |name: &'static str| {
+            field(name)?
+                .get_u64()
+                .ok_or(MetaFileError::InvalidIndex(name))
+        };
+
+        let rx_time = match field("rx_time")? {
+            Tag::Tuple(parts) => match parts.as_slice() {
+                [Tag::UInt64(hi), Tag::UInt64(lo)] => {
+                    let bits = ((*hi as i128) << 64) | *lo as i128;
+                    Timestamp::from_fixed(fixed::FixedI128::from_bits(bits))
+                }
+                _ => return Err(MetaFileError::InvalidIndex("rx_time")),
+            },
+            _ => return Err(MetaFileError::InvalidIndex("rx_time")),
+        };
+        let dtype = field("type")?
+            .get_i32()
+            .and_then(|i| DataType::from_int(i).ok())
+            .ok_or(MetaFileError::InvalidIndex("type"))?;
+        let complex = field("cplx")?
+            .get_bool()
+            .ok_or(MetaFileError::InvalidIndex("cplx"))?;
+
+        let format = Format { dtype, complex };
+        let samp_rate = field("rx_rate")?
+            .get_f64()
+            .filter(|rate| *rate > 0.0)
+            .ok_or(MetaFileError::InvalidIndex("rx_rate"))?;
+        let byte_range = u64_field("start")?..u64_field("end")?;
+        if byte_range.end < byte_range.start {
+            return Err(MetaFileError::InvalidIndex("end"));
+        }
+        let num_samples = u64_field("num_samples")?;
+        let item_size = format.dtype.item_size_bytes(complex) as u64;
+        if num_samples != (byte_range.end - byte_range.start) / item_size {
+            return Err(MetaFileError::InvalidIndex("num_samples"));
+        }
+
+        Ok(SegmentInfo {
+            first_sample: u64_field("first_sample")?,
+            byte_range,
+            rx_time,
+            format,
+            samp_rate,
+            num_samples,
+            header_pos: u64_field("header_pos")?,
+            header_len: u64_field("header_len")?,
+            extra: field("extra")?.clone(),
+        })
+    }
+}
+
+/// Table of contents of a file, with every segment (empty ones included) in file order.
+///
+/// It can be persisted with write and read, which use the PMT serialization format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileIndex {
+    /// Are the headers attached to the data?
+    pub attached: bool,
+    pub segments: Vec<SegmentInfo>,
+}
+
+impl FileIndex {
+    /// Builds the index of the headers of a file, which must be all of them in file order
+    pub fn from_headers(attached: bool, headers: &[Header]) -> FileIndex {
+        let mut first_sample = 0;
+        let segments = headers
+            .iter()
+            .map(|header| {
+                let info = SegmentInfo::from_header(header, first_sample);
+                first_sample += header.get_num_samples();
+                info
+            })
+            .collect();
+        FileIndex { attached, segments }
+    }
+
+    /// Rebuilds the headers of all segments
+    pub fn headers(&self) -> impl Iterator<Item = Header> + '_ {
+        self.segments.iter().map(|s| s.to_header(self.attached))
+    }
+
+    pub fn write<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        let mut dict = BTreeMap::new();
+        dict.insert("version".to_string(), Tag::Int32(INDEX_VERSION));
+        dict.insert("attached".to_string(), Tag::Bool(self.attached));
+        dict.insert(
+            "segments".to_string(),
+            Tag::Tuple(self.segments.iter().map(SegmentInfo::to_tag).collect()),
+        );
+        write(out, &Tag::Dict(dict))
+    }
+
+    pub fn read<R: Read>(input: &mut R) -> Result<FileIndex, MetaFileError> {
+        let tag = parse(input)?;
+        if tag.get("version").and_then(Tag::get_i32) != Some(INDEX_VERSION) {
+            return Err(MetaFileError::InvalidIndex("version"));
+        }
+        let attached = tag
+            .get("attached")
+            .and_then(Tag::get_bool)
+            .ok_or(MetaFileError::InvalidIndex("attached"))?;
+        let segments = match tag.get("segments") {
+            Some(Tag::Tuple(segments)) => segments
+                .iter()
+                .map(SegmentInfo::from_tag)
+                .collect::<Result<_, _>>()?,
+            _ => return Err(MetaFileError::InvalidIndex("segments")),
+        };
+        Ok(FileIndex { attached, segments })
+    }
+}
@@ -2,15 +2,19 @@
 //! This library allows reading from GNU Radio meta file outputs efficiently, including handling of headers in order
 //! to extract stream tags.
 //! Both attached and dettached headers are supported.
-//! In the future, writing data will also be supported.
+//! Files with attached headers can also be written, see [writer].
 //!
 //! ## Streaming data from GNU Radio
 //! This library is not meant to be used while GNU Radio is generating the files. For this purpose it's
 //! better to use some of the other sinks like ZeroMQ or similar.
 //!
+//! ## `no_std` support
+//! The PMT parser in [pmt] only requires `alloc`. Disable the default `std` feature to use it on
+//! targets without `std`. Reading and seeking through files requires `std`.
+//!
 //! ## Crate status
 //! * Currently in development, core API may change greatly.
-//! * Writing data is currently not implemented nor designed into the API.
+//! * Writing is limited to files with attached headers.
 //!
 //! ## Examples
 //!
@@ -40,6 +44,21 @@
 //! which will add this tag to every header generated (by default every 1M samples), including the first one.
 //! You can then read it from each header as a Timestamp value in Rust.
 //!
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+extern crate alloc;
+
+#[cfg(feature = "std")]
 pub mod core;
-mod header;
-mod pmt;
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "std")]
+pub mod decompress;
+#[cfg(feature = "std")]
+pub mod header;
+#[cfg(feature = "std")]
+pub mod index;
+pub mod pmt;
+#[cfg(feature = "std")]
+pub mod spill;
+#[cfg(feature = "std")]
+pub mod writer;
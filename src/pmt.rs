@@ -1,11 +1,14 @@
 //! A most basic PMT parser. It's compatible with the format used as of GNU Radio version 3.10.9.2.
 //! We only support the bare basics to parse the meta headers, see the imhex pattern file in the repo.
 
-use byteorder::{BigEndian, ReadBytesExt};
-use std::{collections::HashMap, io::Read};
+//! Only `alloc` is required, so it may be used without the `std` feature (for example, to parse
+//! headers on an embedded target).
+
+use alloc::{boxed::Box, collections::BTreeMap, string::String, vec, vec::Vec};
+use byteorder::{BigEndian, ByteOrder};
 use thiserror::Error;
 
-type StringToTag = HashMap<String, Tag>;
+type StringToTag = BTreeMap<String, Tag>;
 
 /// A date-time with 64 bits for the second and 64 bits for the fractional part,
 /// allowing accurate time-keeping in seconds regardless of origin point, maintaining
@@ -20,13 +23,134 @@ type StringToTag = HashMap<String, Tag>;
 ///
 /// If you only need timestamps relative to the start of the file, a f32 or f64 is
 /// probably fine, but this is how GNU Radio gives the data.
-pub type Timestamp = fixed::FixedI128<fixed::types::extra::U64>;
+///
+/// The underlying fixed point number can be accessed through Deref for uses not covered here.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(Fixed);
+
+type Fixed = fixed::FixedI128<fixed::types::extra::U64>;
+
+impl Timestamp {
+    pub const ZERO: Timestamp = Timestamp(Fixed::ZERO);
+
+    /// Builds a timestamp from whole seconds and a fractional part, as stored by GNU Radio.
+    /// Panics if out of range (secs of 2^63 or more) or frac is NaN, see checked_from_parts.
+    pub fn from_parts(secs: u64, frac: f64) -> Timestamp {
+        Timestamp(Fixed::from_num(secs) + Fixed::saturating_from_num(frac))
+    }
 
-#[derive(PartialEq, Debug)]
+    /// Same as from_parts, but None if out of range or frac is not finite, as may be the case
+    /// for values read from a file
+    pub fn checked_from_parts(secs: u64, frac: f64) -> Option<Timestamp> {
+        Fixed::checked_from_num(secs)?
+            .checked_add(Fixed::checked_from_num(frac)?)
+            .map(Timestamp)
+    }
+
+    /// Converts seconds to a timestamp, saturating if out of range
+    pub fn from_secs_f64(secs: f64) -> Timestamp {
+        Timestamp(Fixed::saturating_from_num(secs))
+    }
+
+    pub fn from_fixed(value: Fixed) -> Timestamp {
+        Timestamp(value)
+    }
+
+    /// Seconds as a f64, note that precision is lost for large timestamps
+    pub fn as_secs_f64(&self) -> f64 {
+        self.0.to_num()
+    }
+
+    /// Absolute value of the difference between both timestamps
+    pub fn abs_diff(&self, other: Timestamp) -> Timestamp {
+        Timestamp(self.0.abs_diff(other.0).to_num())
+    }
+
+    /// Formats the timestamp as a duration from the start of the file, for example
+    /// `+00:00:01.234567` or `-00:00:00.500000`, rounded to microseconds. Hours are not wrapped
+    /// into days, so durations over a day show the total hours.
+    pub fn format_relative(&self) -> String {
+        let sign = if self.0 < 0 { '-' } else { '+' };
+        let abs = self.0.unsigned_abs();
+        let micros = abs.int().to_num::<u128>() * 1_000_000
+            + (abs.frac() * 1_000_000u128).round().to_num::<u128>();
+        alloc::format!(
+            "{}{:02}:{:02}:{:02}.{:06}",
+            sign,
+            micros / 3_600_000_000,
+            micros / 60_000_000 % 60,
+            micros / 1_000_000 % 60,
+            micros % 1_000_000
+        )
+    }
+}
+
+impl core::ops::Deref for Timestamp {
+    type Target = Fixed;
+
+    fn deref(&self) -> &Fixed {
+        &self.0
+    }
+}
+
+impl core::ops::Add for Timestamp {
+    type Output = Timestamp;
+
+    fn add(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0 + rhs.0)
+    }
+}
+
+impl core::ops::Sub for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Timestamp) -> Timestamp {
+        Timestamp(self.0 - rhs.0)
+    }
+}
+
+/// Formats as an UTC date-time in ISO 8601 style with nanoseconds, taking the timestamp as
+/// seconds since the UNIX epoch, for example `2025-05-19T12:00:00.250000000Z`.
+impl core::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        // int() rounds towards negative infinity, so frac() is always positive
+        let secs = self.0.int().to_num::<i128>();
+        let nanos = (self.0.frac() * 1_000_000_000).to_num::<u64>();
+        let days = secs.div_euclid(86400);
+        let secs_of_day = secs.rem_euclid(86400);
+
+        // Days to civil date, from Howard Hinnant's date algorithms
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day / 60 % 60,
+            secs_of_day % 60,
+            nanos
+        )
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum Tag {
     Bool(bool),
     Symbol(String),
     Int32(i32),
+    /// GNU Radio's serializer has no single precision real, so f32 PMTs (from pmt::from_float)
+    /// are also stored as doubles and parsed here.
     Double(f64),
     Null(),
     Pair(Box<Tag>, Box<Tag>),
@@ -68,6 +192,36 @@ impl Tag {
         }
     }
 
+    /// Gets the value of key, if self is a dictionary containing it
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        if let Tag::Dict(d) = self {
+            d.get(key)
+        } else {
+            None
+        }
+    }
+
+    /// Walks nested tags, each element of path being a key of a dict, or an index into a tuple
+    /// or pair (0 for the first element, 1 for the second). An empty path returns self.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Tag> {
+        path.iter().try_fold(self, |tag, key| match tag {
+            Tag::Dict(d) => d.get(*key),
+            Tag::Tuple(v) => v.get(key.parse::<usize>().ok()?),
+            Tag::Pair(a, b) => match *key {
+                "0" => Some(a.as_ref()),
+                "1" => Some(b.as_ref()),
+                _ => None,
+            },
+            _ => None,
+        })
+    }
+
+    /// Same as get_path, with the path given as dot separated elements, such as "rx_time.0"
+    pub fn get_path_str(&self, path: &str) -> Option<&Tag> {
+        let path: Vec<&str> = path.split('.').collect();
+        self.get_path(&path)
+    }
+
     pub fn get_u64(&self) -> Option<u64> {
         if let Tag::UInt64(u) = self {
             Some(*u)
@@ -77,118 +231,303 @@ impl Tag {
     }
 }
 
+/// Formats the tag in the notation of GNU Radio's pmt::write_string, for example
+/// `((rx_rate . 1000000))` for a dict, `(a . b)` for a pair and `{a b}` for a tuple. Numbers are
+/// formatted as Rust does, so doubles print `1000000` where GNU Radio prints `1e+06`.
+impl core::fmt::Display for Tag {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Tag::Bool(true) => write!(f, "#t"),
+            Tag::Bool(false) => write!(f, "#f"),
+            Tag::Symbol(s) => write!(f, "{}", s),
+            Tag::Int32(i) => write!(f, "{}", i),
+            Tag::Double(d) => write!(f, "{}", d),
+            Tag::Null() => write!(f, "()"),
+            Tag::Pair(a, b) => write!(f, "({} . {})", a, b),
+            Tag::Dict(dict) => {
+                write!(f, "(")?;
+                for (i, (key, value)) in dict.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { " " };
+                    write!(f, "{}({} . {})", sep, key, value)?;
+                }
+                write!(f, ")")
+            }
+            Tag::UInt64(u) => write!(f, "{}", u),
+            Tag::Tuple(vec) => {
+                write!(f, "{{")?;
+                for (i, t) in vec.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { " " };
+                    write!(f, "{}{}", sep, t)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum ParseError {
     #[error("Unexpected EOF while parsing")]
     UnexpectedEOF(),
     #[error("Dict entry didn't follow dict(pair(name_a, a), ...) structure")]
     MalformedDict(),
+    #[cfg(feature = "std")]
     #[error("Reader I/O error while parsing")]
     IoError(#[from] std::io::Error),
     #[error("Symbol was not UTF-8 encoded, likely corrupt file")]
-    Utf8Error(#[from] std::string::FromUtf8Error),
+    Utf8Error(#[from] alloc::string::FromUtf8Error),
+    #[error("Unknown or unsupported PMT type {0:#x}")]
+    UnknownTag(u8),
+    #[error("PMT nested deeper than {MAX_DEPTH} levels, likely corrupt file")]
+    TooDeeplyNested(),
+}
+
+/// Maximum nesting of pairs, dicts and tuples, as the parser is recursive and a corrupt file
+/// could otherwise overflow the stack
+pub const MAX_DEPTH: u32 = 64;
+
+/// The minimal reading interface the parser needs, so that it doesn't depend on `std`.
+/// Implemented for any `std::io::Read` if the `std` feature is enabled, and for byte
+/// slices otherwise.
+pub trait PmtRead {
+    /// Fills buf completely, returning ParseError::UnexpectedEOF if not enough bytes remain
+    fn fill_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError>;
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> PmtRead for R {
+    fn fill_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError> {
+        self.read_exact(buf).map_err(|e| match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => ParseError::UnexpectedEOF(),
+            _ => ParseError::IoError(e),
+        })
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl PmtRead for &[u8] {
+    fn fill_exact(&mut self, buf: &mut [u8]) -> Result<(), ParseError> {
+        if self.len() < buf.len() {
+            return Err(ParseError::UnexpectedEOF());
+        }
+        let (head, tail) = self.split_at(buf.len());
+        buf.copy_from_slice(head);
+        *self = tail;
+        Ok(())
+    }
+}
+
+fn read_array<const N: usize, T: PmtRead>(reader: &mut T) -> Result<[u8; N], ParseError> {
+    let mut buf = [0; N];
+    reader.fill_exact(&mut buf)?;
+    Ok(buf)
 }
 
-fn parse_symbol<T: Read>(reader: &mut T) -> Result<Tag, ParseError> {
-    let len = reader.read_u16::<BigEndian>()?;
+/// Reads a number of N bytes in the byte order of opts, returning it in big endian
+fn read_number<const N: usize, T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+) -> Result<[u8; N], ParseError> {
+    Ok(opts.byte_order.reorder(read_array(reader)?))
+}
+
+fn parse_symbol<T: PmtRead>(reader: &mut T, opts: &ParseOptions) -> Result<Tag, ParseError> {
+    let len = BigEndian::read_u16(&read_number::<2, _>(reader, opts)?);
     // TODO: This could be a target for an easy DOS attack! Limit len to prevent massive allocation.
     let mut bytes = vec![0; len as usize];
+    reader.fill_exact(bytes.as_mut_slice())?;
 
-    let bytes_read = reader.read(bytes.as_mut_slice())?;
-    if bytes_read != len as usize {
-        return Err(ParseError::UnexpectedEOF());
+    match opts.symbol_encoding {
+        SymbolEncoding::Strict => Ok(Tag::Symbol(String::from_utf8(bytes)?)),
+        SymbolEncoding::Lossy => Ok(Tag::Symbol(String::from_utf8_lossy(&bytes).into_owned())),
     }
-
-    Ok(Tag::Symbol(String::from_utf8(bytes)?))
 }
 
-fn parse_pair_inner<T: Read>(reader: &mut T) -> Result<(Tag, Tag), ParseError> {
-    let first = parse(reader)?;
-    let second = parse(reader)?;
+fn parse_pair_inner<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<(Tag, Tag), ParseError> {
+    let first = parse_nested(reader, opts, depth)?;
+    let second = parse_nested(reader, opts, depth)?;
     Ok((first, second))
 }
 
-fn parse_pair<T: Read>(reader: &mut T) -> Result<Tag, ParseError> {
-    let ab = parse_pair_inner(reader)?;
+fn parse_pair<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<Tag, ParseError> {
+    let ab = parse_pair_inner(reader, opts, depth)?;
     Ok(Tag::Pair(Box::new(ab.0), Box::new(ab.1)))
 }
 
-fn expect_byte<T: Read>(reader: &mut T) -> Result<u8, ParseError> {
-    let mut byte_buf: [u8; 1] = Default::default();
-    let num_read = reader.read(&mut byte_buf)?;
-    if num_read != 1 {
-        // EOF or similar
-        return Err(ParseError::UnexpectedEOF());
-    }
-
+fn expect_byte<T: PmtRead>(reader: &mut T) -> Result<u8, ParseError> {
+    let byte_buf: [u8; 1] = read_array(reader)?;
     Ok(byte_buf[0])
 }
 
-fn parse_dict_inner<T: Read>(rdr: &mut T, tgt: &mut StringToTag) -> Result<(), ParseError> {
+fn parse_dict_inner<T: PmtRead>(
+    rdr: &mut T,
+    tgt: &mut StringToTag,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<(), ParseError> {
     // The "pair" byte
-    if expect_byte(rdr)? != 0x7 {
-        return Err(ParseError::MalformedDict());
-    }
+    let mut pair_byte = expect_byte(rdr)?;
 
-    let pair = parse_pair_inner(rdr)?;
+    loop {
+        if pair_byte != 0x7 {
+            return Err(ParseError::MalformedDict());
+        }
 
-    if let Tag::Symbol(name) = pair.0 {
-        tgt.insert(name, pair.1);
-    } else {
-        return Err(ParseError::MalformedDict());
-    }
+        let pair = parse_pair_inner(rdr, opts, depth)?;
 
-    let next_byte = expect_byte(rdr)?;
+        if let Tag::Symbol(name) = pair.0 {
+            tgt.insert(name, pair.1);
+        } else {
+            return Err(ParseError::MalformedDict());
+        }
 
-    match next_byte {
-        0x6 => Ok(()),                     // null byte, dict is over
-        0x9 => parse_dict_inner(rdr, tgt), // dict byte, continue parsing
-        _ => Err(ParseError::MalformedDict()),
+        pair_byte = match expect_byte(rdr)? {
+            0x6 => return Ok(()),        // null byte, dict is over
+            0x9 => expect_byte(rdr)?,    // dict byte, continue parsing
+            0x7 if opts.tolerant => 0x7, // pair-list form, continue parsing
+            _ => return Err(ParseError::MalformedDict()),
+        };
     }
 }
 
-fn parse_dict<T: Read>(reader: &mut T) -> Result<Tag, ParseError> {
-    // A dict is formed as dict(pair(name_a, a), dict(pair(name_b, b), ...))
-    let mut dict = HashMap::new();
-    parse_dict_inner(reader, &mut dict)?;
+fn parse_dict<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<Tag, ParseError> {
+    // A dict is formed as dict(pair(name_a, a), dict(pair(name_b, b), ...)), but some tools
+    // emit it as dict(pair(name_a, a), pair(name_b, b), ..., null)
+    let mut dict = BTreeMap::new();
+    parse_dict_inner(reader, &mut dict, opts, depth)?;
     Ok(Tag::Dict(dict))
 }
 
-fn parse_tuple<T: Read>(reader: &mut T) -> Result<Tag, ParseError> {
-    let num = reader.read_u32::<BigEndian>()?;
-    let mut vec = Vec::with_capacity(num as usize);
+fn parse_tuple<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<Tag, ParseError> {
+    let num = BigEndian::read_u32(&read_number::<4, _>(reader, opts)?);
+    // num comes from the file, so it can't be trusted for a large allocation
+    let mut vec = Vec::with_capacity(num.min(64) as usize);
     for _ in 0..num {
-        vec.push(parse(reader)?)
+        vec.push(parse_nested(reader, opts, depth)?)
     }
     Ok(Tag::Tuple(vec))
 }
 
-fn parse_tag<T: Read>(reader: &mut T, kind: u8) -> Result<Tag, ParseError> {
+/// Parses the tag of type kind, nested in depth other tags
+fn parse_tag<T: PmtRead>(
+    reader: &mut T,
+    kind: u8,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<Tag, ParseError> {
+    if depth >= MAX_DEPTH {
+        return Err(ParseError::TooDeeplyNested());
+    }
+    let depth = depth + 1;
     match kind {
         0x0 => Ok(Tag::Bool(true)),
         0x1 => Ok(Tag::Bool(false)),
-        0x2 => parse_symbol(reader),
-        0x3 => Ok(Tag::Int32(reader.read_i32::<BigEndian>()?)),
-        0x4 => Ok(Tag::Double(reader.read_f64::<BigEndian>()?)),
+        0x2 => parse_symbol(reader, opts),
+        0x3 => Ok(Tag::Int32(BigEndian::read_i32(&read_number::<4, _>(
+            reader, opts,
+        )?))),
+        0x4 => Ok(Tag::Double(BigEndian::read_f64(&read_number::<8, _>(
+            reader, opts,
+        )?))),
         0x6 => Ok(Tag::Null()),
-        0x7 => parse_pair(reader),
-        0x9 => parse_dict(reader),
-        0xb => Ok(Tag::UInt64(reader.read_u64::<BigEndian>()?)),
-        0xc => parse_tuple(reader),
-        _x => todo!("Unimplemented"),
+        0x7 => parse_pair(reader, opts, depth),
+        0x9 => parse_dict(reader, opts, depth),
+        0xb => Ok(Tag::UInt64(BigEndian::read_u64(&read_number::<8, _>(
+            reader, opts,
+        )?))),
+        0xc => parse_tuple(reader, opts, depth),
+        x => Err(ParseError::UnknownTag(x)),
+    }
+}
+
+/// Options to parse PMTs that don't exactly follow the format GNU Radio uses
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Accept structures that GNU Radio never generates, but other tools do:
+    /// - Dicts given as a list of pairs terminated by null, instead of nested dicts
+    pub tolerant: bool,
+    /// How to decode symbols which are not valid UTF-8
+    pub symbol_encoding: SymbolEncoding,
+    /// Byte order of numbers, big endian as in GNU Radio unless the file was written by a tool
+    /// that uses little endian
+    pub byte_order: PmtByteOrder,
+}
+
+/// Byte order of the numbers (and lengths) in serialized PMTs
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PmtByteOrder {
+    #[default]
+    Big,
+    Little,
+}
+
+impl PmtByteOrder {
+    /// Converts a number between big endian and this byte order, in either direction
+    fn reorder<const N: usize>(self, mut bytes: [u8; N]) -> [u8; N] {
+        if self == PmtByteOrder::Little {
+            bytes.reverse();
+        }
+        bytes
     }
 }
 
+/// Decoding of the bytes of a symbol into a String
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SymbolEncoding {
+    /// Invalid UTF-8 is a ParseError::Utf8Error
+    #[default]
+    Strict,
+    /// Invalid UTF-8 sequences are replaced by U+FFFD (the replacement character)
+    Lossy,
+}
+
 /// The reader must be positioned at the start of a Tag
-pub fn parse<T: Read>(reader: &mut T) -> Result<Tag, ParseError> {
+pub fn parse<T: PmtRead>(reader: &mut T) -> Result<Tag, ParseError> {
+    parse_with(reader, &ParseOptions::default())
+}
+
+/// Same as parse, but with the given options
+pub fn parse_with<T: PmtRead>(reader: &mut T, opts: &ParseOptions) -> Result<Tag, ParseError> {
+    parse_nested(reader, opts, 0)
+}
+
+fn parse_nested<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+    depth: u32,
+) -> Result<Tag, ParseError> {
     let byte = expect_byte(reader)?;
-    parse_tag(reader, byte)
+    parse_tag(reader, byte, opts, depth)
 }
 
 /// Tries to read a tag, but if EOF is found on the first read, None is returned
 /// instead of an error.
 /// The reader must be positioned at the start of a Tag
-pub fn parse_maybe_eof<T: Read>(reader: &mut T) -> Result<Option<Tag>, ParseError> {
+pub fn parse_maybe_eof<T: PmtRead>(reader: &mut T) -> Result<Option<Tag>, ParseError> {
+    parse_maybe_eof_with(reader, &ParseOptions::default())
+}
+
+/// Same as parse_maybe_eof, but with the given options
+pub fn parse_maybe_eof_with<T: PmtRead>(
+    reader: &mut T,
+    opts: &ParseOptions,
+) -> Result<Option<Tag>, ParseError> {
     let byte = match expect_byte(reader) {
         Err(e) => match e {
             ParseError::UnexpectedEOF() => return Ok(None),
@@ -196,8 +535,416 @@ pub fn parse_maybe_eof<T: Read>(reader: &mut T) -> Result<Option<Tag>, ParseErro
         },
         Ok(v) => v,
     };
-    match parse_tag(reader, byte) {
+    match parse_tag(reader, byte, opts, 0) {
         Err(e) => Err(e),
         Ok(v) => Ok(Some(v)),
     }
 }
+
+/// Iterates the raw (main dict, extra dict) tag pairs of every segment in a dettached header
+/// file, without interpreting them as headers. Stops at EOF, or after the first error.
+pub struct HeaderFileIter<T: PmtRead> {
+    reader: T,
+    done: bool,
+}
+
+impl<T: PmtRead> HeaderFileIter<T> {
+    /// The reader must be positioned at the start of a header
+    pub fn new(reader: T) -> HeaderFileIter<T> {
+        HeaderFileIter {
+            reader,
+            done: false,
+        }
+    }
+
+    fn parse_next(&mut self) -> Result<Option<(Tag, Tag)>, ParseError> {
+        let main = match parse_maybe_eof(&mut self.reader)? {
+            None => return Ok(None),
+            Some(v) => v,
+        };
+        let extra = parse(&mut self.reader)?;
+        Ok(Some((main, extra)))
+    }
+}
+
+impl<T: PmtRead> Iterator for HeaderFileIter<T> {
+    type Item = Result<(Tag, Tag), ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let out = self.parse_next().transpose();
+        self.done = !matches!(out, Some(Ok(_)));
+        out
+    }
+}
+
+/// Serializes tag exactly as GNU Radio does, such that parse reads it back. Note that empty
+/// dictionaries are serialized as Null, as in GNU Radio.
+#[cfg(any(feature = "std", test))]
+pub fn write<T: std::io::Write>(writer: &mut T, tag: &Tag) -> Result<(), std::io::Error> {
+    write_ordered(writer, tag, PmtByteOrder::Big)
+}
+
+/// Same as write, but with numbers in the given byte order
+#[cfg(any(feature = "std", test))]
+fn write_ordered<T: std::io::Write>(
+    writer: &mut T,
+    tag: &Tag,
+    order: PmtByteOrder,
+) -> Result<(), std::io::Error> {
+    match tag {
+        Tag::Bool(true) => writer.write_all(&[0x0])?,
+        Tag::Bool(false) => writer.write_all(&[0x1])?,
+        Tag::Symbol(s) => {
+            let len = u16::try_from(s.len()).map_err(|_| too_long("symbol"))?;
+            writer.write_all(&[0x2])?;
+            writer.write_all(&order.reorder(len.to_be_bytes()))?;
+            writer.write_all(s.as_bytes())?;
+        }
+        Tag::Int32(i) => {
+            writer.write_all(&[0x3])?;
+            writer.write_all(&order.reorder(i.to_be_bytes()))?;
+        }
+        Tag::Double(f) => {
+            writer.write_all(&[0x4])?;
+            writer.write_all(&order.reorder(f.to_be_bytes()))?;
+        }
+        Tag::Null() => writer.write_all(&[0x6])?,
+        Tag::Pair(a, b) => {
+            writer.write_all(&[0x7])?;
+            write_ordered(writer, a, order)?;
+            write_ordered(writer, b, order)?;
+        }
+        Tag::Dict(dict) => {
+            // dict(pair(name_a, a), dict(pair(name_b, b), ... null))
+            for (name, value) in dict.iter() {
+                writer.write_all(&[0x9, 0x7])?;
+                write_ordered(writer, &Tag::Symbol(name.clone()), order)?;
+                write_ordered(writer, value, order)?;
+            }
+            writer.write_all(&[0x6])?;
+        }
+        Tag::UInt64(u) => {
+            writer.write_all(&[0xb])?;
+            writer.write_all(&order.reorder(u.to_be_bytes()))?;
+        }
+        Tag::Tuple(vec) => {
+            let len = u32::try_from(vec.len()).map_err(|_| too_long("tuple"))?;
+            writer.write_all(&[0xc])?;
+            writer.write_all(&order.reorder(len.to_be_bytes()))?;
+            for t in vec {
+                write_ordered(writer, t, order)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Error for a value whose length doesn't fit its length field
+#[cfg(any(feature = "std", test))]
+fn too_long(what: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("{} too long to serialize as a PMT", what),
+    )
+}
+
+#[cfg(test)]
+mod pmt_tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_format_relative() {
+        let fmt = |secs: f64| Timestamp::from_secs_f64(secs).format_relative();
+        assert_eq!(fmt(1.234567), "+00:00:01.234567");
+        assert_eq!(fmt(3661.0), "+01:01:01.000000");
+        assert_eq!(fmt(-0.5), "-00:00:00.500000");
+        assert_eq!(fmt(0.0), "+00:00:00.000000");
+        // Rounding carries into the seconds
+        assert_eq!(fmt(59.9999999), "+00:01:00.000000");
+        assert_eq!(fmt(90000.0 + 0.000001), "+25:00:00.000001");
+    }
+
+    #[test]
+    fn tag_display() {
+        let mut dict = BTreeMap::new();
+        dict.insert("a".to_string(), Tag::Int32(-3));
+        dict.insert(
+            "b".to_string(),
+            Tag::Tuple(vec![Tag::UInt64(1), Tag::Double(0.5)]),
+        );
+        let tag = Tag::Pair(Box::new(Tag::Dict(dict)), Box::new(Tag::Bool(true)));
+        assert_eq!(tag.to_string(), "(((a . -3) (b . {1 0.5})) . #t)");
+        assert_eq!(Tag::Null().to_string(), "()");
+        assert_eq!(Tag::Symbol("x".into()).to_string(), "x");
+
+        // Doubles are formatted as Rust does
+        let mut dict = BTreeMap::new();
+        dict.insert("rx_rate".to_string(), Tag::Double(1e6));
+        assert_eq!(Tag::Dict(dict).to_string(), "((rx_rate . 1000000))");
+    }
+
+    #[test]
+    fn get_nested_path() {
+        let mut info = BTreeMap::new();
+        info.insert("serial".to_string(), Tag::Symbol("abc".into()));
+        let mut uhd = BTreeMap::new();
+        uhd.insert("info".to_string(), Tag::Dict(info));
+        let mut extra = BTreeMap::new();
+        extra.insert("uhd".to_string(), Tag::Dict(uhd));
+        extra.insert(
+            "rx_time".to_string(),
+            Tag::Tuple(vec![Tag::UInt64(3), Tag::Double(0.5)]),
+        );
+        extra.insert(
+            "pair".to_string(),
+            Tag::Pair(Box::new(Tag::Int32(1)), Box::new(Tag::Int32(2))),
+        );
+        let extra = Tag::Dict(extra);
+
+        assert_eq!(
+            extra.get_path(&["uhd", "info", "serial"]),
+            Some(&Tag::Symbol("abc".into()))
+        );
+        assert_eq!(
+            extra.get_path_str("uhd.info.serial"),
+            extra.get_path(&["uhd", "info", "serial"])
+        );
+        assert_eq!(extra.get_path_str("rx_time.0"), Some(&Tag::UInt64(3)));
+        assert_eq!(extra.get_path_str("rx_time.1"), Some(&Tag::Double(0.5)));
+        assert_eq!(extra.get_path_str("pair.1"), Some(&Tag::Int32(2)));
+        assert_eq!(extra.get_path(&[]), Some(&extra));
+
+        assert_eq!(extra.get_path_str("rx_time.2"), None);
+        assert_eq!(extra.get_path_str("rx_time.x"), None);
+        assert_eq!(extra.get_path_str("uhd.missing"), None);
+        assert_eq!(extra.get_path_str("uhd.info.serial.0"), None);
+    }
+
+    #[test]
+    fn timestamp_constructors() {
+        let t = Timestamp::from_parts(1_700_000_000, 0.25);
+        assert_eq!(t.int(), 1_700_000_000);
+        assert_eq!(t.frac(), 0.25);
+        assert_eq!(t.as_secs_f64(), 1_700_000_000.25);
+        assert_eq!(Timestamp::from_secs_f64(1.5), Timestamp::from_parts(1, 0.5));
+        assert_eq!(
+            Timestamp::from_secs_f64(f64::INFINITY),
+            Timestamp::from_fixed(Fixed::MAX)
+        );
+        assert_eq!(Timestamp::default(), Timestamp::ZERO);
+    }
+
+    #[test]
+    fn timestamp_arithmetic() {
+        let a = Timestamp::from_parts(10, 0.75);
+        let b = Timestamp::from_parts(3, 0.5);
+        assert_eq!(a + b, Timestamp::from_parts(14, 0.25));
+        assert_eq!(a - b, Timestamp::from_parts(7, 0.25));
+        assert_eq!((b - a).as_secs_f64(), -7.25);
+        assert_eq!(a.abs_diff(b), b.abs_diff(a));
+        assert_eq!(a.abs_diff(b), Timestamp::from_parts(7, 0.25));
+        assert!(b < a);
+    }
+
+    #[test]
+    fn timestamp_display() {
+        assert_eq!(
+            Timestamp::ZERO.to_string(),
+            "1970-01-01T00:00:00.000000000Z"
+        );
+        assert_eq!(
+            Timestamp::from_parts(1_747_656_000, 0.25).to_string(),
+            "2025-05-19T12:00:00.250000000Z"
+        );
+        assert_eq!(
+            Timestamp::from_secs_f64(-0.5).to_string(),
+            "1969-12-31T23:59:59.500000000Z"
+        );
+    }
+
+    fn sample_dict() -> Tag {
+        let mut dict = BTreeMap::new();
+        dict.insert("rx_rate".into(), Tag::Double(2e6));
+        dict.insert("size".into(), Tag::Int32(8));
+        dict.insert("cplx".into(), Tag::Bool(true));
+        dict.insert(
+            "rx_time".into(),
+            Tag::Tuple(vec![Tag::UInt64(12), Tag::Double(0.5)]),
+        );
+        dict.insert(
+            "pair".into(),
+            Tag::Pair(Box::new(Tag::Symbol("a".into())), Box::new(Tag::Null())),
+        );
+        Tag::Dict(dict)
+    }
+
+    // Parsing from a plain byte slice works with and without the std feature
+    #[test]
+    fn parse_from_slice() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &sample_dict()).unwrap();
+
+        let mut slice = bytes.as_slice();
+        assert_eq!(parse(&mut slice).unwrap(), sample_dict());
+        assert!(slice.is_empty());
+        assert!(parse_maybe_eof(&mut slice).unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_truncated() {
+        let mut bytes = Vec::new();
+        write(&mut bytes, &sample_dict()).unwrap();
+
+        let mut slice = &bytes[..bytes.len() - 3];
+        assert!(matches!(
+            parse(&mut slice),
+            Err(ParseError::UnexpectedEOF())
+        ));
+    }
+
+    #[test]
+    fn malformed_inputs_dont_panic() {
+        // Complex and vectors are not supported
+        assert!(matches!(
+            parse(&mut [0x5u8].as_slice()),
+            Err(ParseError::UnknownTag(0x5))
+        ));
+        assert!(matches!(
+            parse(&mut [0x8u8, 0x0, 0x0].as_slice()),
+            Err(ParseError::UnknownTag(0x8))
+        ));
+        // A tuple claiming u32::MAX items used to attempt a huge allocation
+        assert!(matches!(
+            parse(&mut [0xcu8, 0xff, 0xff, 0xff, 0xff, 0x6].as_slice()),
+            Err(ParseError::UnexpectedEOF())
+        ));
+        // Deeply nested pairs used to overflow the stack
+        let nested = vec![0x7u8; 100_000];
+        assert!(matches!(
+            parse(&mut nested.as_slice()),
+            Err(ParseError::TooDeeplyNested())
+        ));
+    }
+
+    #[test]
+    fn widened_float() {
+        // pmt.serialize_str(pmt.from_float(0.1)), stored as a double
+        let mut bytes = vec![0x4];
+        bytes.extend((0.1f32 as f64).to_be_bytes());
+        let tag = parse(&mut bytes.as_slice()).unwrap();
+        assert_eq!(tag.get_f64(), Some(0.1f32 as f64));
+    }
+
+    #[test]
+    fn symbol_encoding() {
+        let bytes = [0x2, 0x0, 0x3, b'a', 0xff, b'b'];
+        assert!(matches!(
+            parse(&mut bytes.as_slice()),
+            Err(ParseError::Utf8Error(_))
+        ));
+
+        let lossy = ParseOptions {
+            symbol_encoding: SymbolEncoding::Lossy,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with(&mut bytes.as_slice(), &lossy).unwrap(),
+            Tag::Symbol("a\u{FFFD}b".into())
+        );
+    }
+
+    #[test]
+    fn write_oversized_symbol() {
+        let longest = Tag::Symbol("x".repeat(u16::MAX as usize));
+        let mut out = Vec::new();
+        write(&mut out, &longest).unwrap();
+        assert_eq!(parse(&mut out.as_slice()).unwrap(), longest);
+
+        // Nothing is written, instead of a wrapped length
+        let mut out = Vec::new();
+        let err = write(&mut out, &Tag::Symbol("x".repeat(1 << 16))).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn parse_pair_list_dict() {
+        // dict(pair(rx_rate, 2e6), pair(size, 8), pair(cplx, true), null)
+        let mut bytes = vec![0x9];
+        for (name, value) in [
+            ("cplx", Tag::Bool(true)),
+            ("rx_rate", Tag::Double(2e6)),
+            ("size", Tag::Int32(8)),
+        ] {
+            write(
+                &mut bytes,
+                &Tag::Pair(Box::new(Tag::Symbol(name.into())), Box::new(value)),
+            )
+            .unwrap();
+        }
+        bytes.push(0x6);
+
+        let mut expected = BTreeMap::new();
+        expected.insert("rx_rate".into(), Tag::Double(2e6));
+        expected.insert("size".into(), Tag::Int32(8));
+        expected.insert("cplx".into(), Tag::Bool(true));
+        let expected = Tag::Dict(expected);
+
+        let tolerant = ParseOptions {
+            tolerant: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with(&mut bytes.as_slice(), &tolerant).unwrap(),
+            expected
+        );
+        assert!(matches!(
+            parse(&mut bytes.as_slice()),
+            Err(ParseError::MalformedDict())
+        ));
+
+        // The nested form is still parsed the same in tolerant mode
+        let mut nested = Vec::new();
+        write(&mut nested, &expected).unwrap();
+        assert_eq!(
+            parse_with(&mut nested.as_slice(), &tolerant).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn parse_little_endian() {
+        let little = ParseOptions {
+            byte_order: PmtByteOrder::Little,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with(&mut [0x3, 0x78, 0x56, 0x34, 0x12].as_slice(), &little).unwrap(),
+            Tag::Int32(0x12345678)
+        );
+
+        let mut dict = BTreeMap::new();
+        dict.insert("version".to_string(), Tag::Int32(0));
+        dict.insert("rx_rate".to_string(), Tag::Double(32000.0));
+        dict.insert(
+            "rx_time".to_string(),
+            Tag::Tuple(vec![Tag::UInt64(1234), Tag::Double(0.25)]),
+        );
+        dict.insert("bytes".to_string(), Tag::UInt64(256));
+        dict.insert("cplx".to_string(), Tag::Bool(true));
+        let header = Tag::Dict(dict);
+
+        let mut big = Vec::new();
+        write(&mut big, &header).unwrap();
+        let mut le = Vec::new();
+        write_ordered(&mut le, &header, PmtByteOrder::Little).unwrap();
+        assert_ne!(big, le);
+
+        let from_big = parse(&mut big.as_slice()).unwrap();
+        assert_eq!(parse_with(&mut le.as_slice(), &little).unwrap(), from_big);
+        assert_eq!(from_big, header);
+    }
+}
@@ -0,0 +1,219 @@
+//! Reading of files from streams that can't seek, such as stdin or a pipe, which the readers
+//! otherwise require. Unlike DecompressingReader, the stream is only read as far as needed.
+//!
+//! For example, to read a file with attached headers piped to the program:
+//! ```no_run
+//! # use gnuradio_meta::core::AttachedHeader;
+//! # use gnuradio_meta::spill::SpillReader;
+//! let reader = AttachedHeader::new(SpillReader::new(std::io::stdin().lock()));
+//! ```
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use crate::decompress::create_temp_file;
+
+/// Bytes kept in memory by default before spilling to a temporary file
+pub const DEFAULT_MEMORY_CAP: usize = 64 * 1024 * 1024;
+
+/// Size of the chunks in which the stream is read
+const CHUNK_LEN: usize = 64 * 1024;
+
+/// Seekable wrapper of a stream, which keeps every byte read from it so far. These are kept in
+/// memory up to a cap, past which all of them are moved to a temporary file (in
+/// std::env::temp_dir(), deleted on drop).
+///
+/// Seeking forward reads the stream up to the new position only once it is read from, but
+/// seeking relative to the end requires reading the whole stream.
+pub struct SpillReader<R: Read> {
+    inner: R,
+    buffer: Buffer,
+    /// Number of bytes read from inner
+    len: u64,
+    pos: u64,
+    memory_cap: usize,
+    /// Has inner reached its end?
+    eof: bool,
+}
+
+enum Buffer {
+    Memory(Vec<u8>),
+    TempFile(File, PathBuf),
+}
+
+impl<R: Read> SpillReader<R> {
+    /// Keeps up to DEFAULT_MEMORY_CAP bytes in memory
+    pub fn new(inner: R) -> SpillReader<R> {
+        SpillReader {
+            inner,
+            buffer: Buffer::Memory(Vec::new()),
+            len: 0,
+            pos: 0,
+            memory_cap: DEFAULT_MEMORY_CAP,
+            eof: false,
+        }
+    }
+
+    /// Number of bytes kept in memory before spilling all of them to a temporary file
+    pub fn with_memory_cap(mut self, memory_cap: usize) -> Self {
+        self.memory_cap = memory_cap;
+        self
+    }
+
+    /// Returns true if the bytes read have been moved to a temporary file
+    pub fn spilled(&self) -> bool {
+        matches!(self.buffer, Buffer::TempFile(..))
+    }
+
+    /// Reads the stream until at least target bytes have been read from it, or it ends
+    fn fill_to(&mut self, target: u64) -> std::io::Result<()> {
+        let mut chunk = vec![0; CHUNK_LEN];
+        while self.len < target && !self.eof {
+            let n = match self.inner.read(&mut chunk) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+
+            match &mut self.buffer {
+                Buffer::Memory(data) if data.len() + n > self.memory_cap => {
+                    let (file, path) = create_temp_file()?;
+                    // Switch first, so the file is removed on drop even if writing fails
+                    let data = std::mem::replace(&mut self.buffer, Buffer::TempFile(file, path));
+                    let Buffer::TempFile(file, _) = &mut self.buffer else {
+                        unreachable!()
+                    };
+                    if let Buffer::Memory(data) = data {
+                        file.write_all(&data)?;
+                    }
+                    file.write_all(&chunk[..n])?;
+                }
+                Buffer::Memory(data) => data.extend_from_slice(&chunk[..n]),
+                Buffer::TempFile(file, _) => {
+                    file.seek(SeekFrom::End(0))?;
+                    file.write_all(&chunk[..n])?;
+                }
+            }
+            self.len += n as u64;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for SpillReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.fill_to(self.pos.saturating_add(buf.len() as u64))?;
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+
+        let n = buf.len().min((self.len - self.pos) as usize);
+        match &mut self.buffer {
+            Buffer::Memory(data) => {
+                let start = self.pos as usize;
+                buf[..n].copy_from_slice(&data[start..start + n]);
+            }
+            Buffer::TempFile(file, _) => {
+                file.seek(SeekFrom::Start(self.pos))?;
+                file.read_exact(&mut buf[..n])?;
+            }
+        }
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Seek for SpillReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(v) => Some(v),
+            SeekFrom::Current(v) => self.pos.checked_add_signed(v),
+            SeekFrom::End(v) => {
+                self.fill_to(u64::MAX)?;
+                self.len.checked_add_signed(v)
+            }
+        };
+        self.pos = target.ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+impl<R: Read> Drop for SpillReader<R> {
+    fn drop(&mut self) {
+        if let Buffer::TempFile(_, path) = &self.buffer {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod spill_tests {
+    use super::*;
+    use crate::core::{AttachedHeader, SampleReadSeek};
+    use crate::decompress::Trickle;
+    use crate::header::{DataType, HeaderBuilder, SeekPreserve};
+    use crate::writer::write_from_iter;
+
+    #[test]
+    fn read_attached_from_pipe() {
+        let template = HeaderBuilder::new()
+            .dtype(DataType::Short)
+            .cplx(false)
+            .num_samples(1000)
+            .build();
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &template, 0..5000i16).unwrap();
+
+        for memory_cap in [DEFAULT_MEMORY_CAP, 4000] {
+            let spill =
+                SpillReader::new(Trickle::new(file.clone(), 1000)).with_memory_cap(memory_cap);
+            let mut reader = AttachedHeader::new(spill);
+            let mut buf = vec![0i16; 2500];
+            assert_eq!(reader.read_samples(&mut buf).unwrap(), 2500);
+            assert!(buf.iter().enumerate().all(|(i, &s)| s == i as i16));
+
+            // Seeking back is served from the buffered bytes
+            reader
+                .seek(SeekFrom::Start(100), SeekPreserve::All)
+                .unwrap();
+            assert_eq!(reader.read_samples(&mut buf[..1]).unwrap(), 1);
+            assert_eq!(buf[0], 100);
+
+            let spill = reader.into_inner();
+            assert_eq!(spill.spilled(), memory_cap < file.len());
+        }
+    }
+
+    #[test]
+    fn seek_past_end_and_from_end() {
+        let data: Vec<u8> = (0..=255).cycle().take(10000).collect();
+        let mut reader = SpillReader::new(Trickle::new(data.clone(), 1000)).with_memory_cap(3000);
+        let mut byte = [0];
+
+        assert_eq!(reader.seek(SeekFrom::Start(20000)).unwrap(), 20000);
+        assert_eq!(reader.read(&mut byte).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::Start(u64::MAX)).unwrap(), u64::MAX);
+        assert_eq!(reader.read(&mut byte).unwrap(), 0);
+        assert_eq!(reader.seek(SeekFrom::End(-1)).unwrap(), 9999);
+        reader.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], data[9999]);
+        assert!(reader.seek(SeekFrom::Current(-20000)).is_err());
+
+        let path = match &reader.buffer {
+            Buffer::TempFile(_, path) => path.clone(),
+            Buffer::Memory(_) => unreachable!(),
+        };
+        drop(reader);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,336 @@
+//! Writing of GNU Radio meta files, readable both by GNU Radio and by the readers of this crate.
+
+use std::io::Write;
+
+use crate::core::MetaFileError;
+use crate::header::{Endianness, Header, SampleType};
+use crate::pmt::{Tag, Timestamp, write};
+
+/// Number of samples per segment GNU Radio's File Meta Sink uses by default
+pub const DEFAULT_SEGMENT_LEN: u64 = 1_000_000;
+
+/// Builds the header dict of a segment as GNU Radio does
+fn header_tag(header: &Header, rx_time: Timestamp, strt: u64, bytes: u64) -> Tag {
+    Header {
+        rx_time,
+        strt,
+        bytes,
+        ..header.clone()
+    }
+    .as_tag()
+}
+
+/// Writes a file with attached headers: each segment is written as its header, its extra dict
+/// and then its samples.
+///
+/// Samples are buffered until a segment is complete, so the file needs not be seekable. The
+/// last (partial) segment is written by finish, or on drop if finish wasn't called, but errors
+/// can then only be printed to stderr. Once writing a segment fails partway, the file ends in a
+/// partial segment, so nothing more is written (not even on drop) and further writes error.
+pub struct AttachedHeaderWriter<W: Write> {
+    file: W,
+    /// Format, sample rate, initial rx_time and extra dict of all segments
+    template: Header,
+    segment_len: u64,
+    /// Byte order of the samples (headers are always big endian)
+    endianness: Endianness,
+    /// Samples of the current segment, not yet written
+    pending: Vec<u8>,
+    /// Samples written to the file, used to compute rx_time of each segment
+    samples_written: u64,
+    /// Set while a segment is being written to the file, so it stays set if writing it failed
+    poisoned: bool,
+}
+
+impl<W: Write> AttachedHeaderWriter<W> {
+    /// Segments take their format, sample rate and extra dict from template, and their rx_time
+    /// from the rx_time of template plus the time of the samples before them
+    pub fn new(file: W, template: Header) -> AttachedHeaderWriter<W> {
+        AttachedHeaderWriter {
+            file,
+            template,
+            segment_len: DEFAULT_SEGMENT_LEN,
+            endianness: Endianness::Native,
+            pending: Vec::new(),
+            samples_written: 0,
+            poisoned: false,
+        }
+    }
+
+    /// Number of samples after which a new header is written. Must not be zero.
+    pub fn with_segment_len(mut self, segment_len: u64) -> Self {
+        assert!(segment_len > 0, "segments must contain samples");
+        self.segment_len = segment_len;
+        self
+    }
+
+    /// Byte order in which samples are written, native by default as GNU Radio does. Note that
+    /// readers must then be told the endianness, as it's not stored in the file.
+    pub fn with_endianness(mut self, endianness: Endianness) -> Self {
+        self.endianness = endianness;
+        self
+    }
+
+    /// Writes samples, which must be exactly of the format of the template
+    pub fn write_samples<T: SampleType>(&mut self, samples: &[T]) -> Result<(), MetaFileError> {
+        if !T::reads_directly_from(self.template.dtype, self.template.cplx) {
+            return Err(MetaFileError::WrongSampleType {
+                expected: self.template.dtype.type_string(self.template.cplx),
+                found: T::DTYPE.type_string(T::COMPLEX),
+            });
+        }
+
+        // SAFETY: SampleType guarantees T is plain old data with no padding
+        let mut bytes = unsafe {
+            std::slice::from_raw_parts(
+                samples.as_ptr() as *const u8,
+                std::mem::size_of_val(samples),
+            )
+        };
+        let segment_bytes = self.segment_len as usize * self.template.size as usize;
+        while !bytes.is_empty() {
+            let n = bytes.len().min(segment_bytes - self.pending.len());
+            let start = self.pending.len();
+            self.pending.extend_from_slice(&bytes[..n]);
+            let scalar_width = self.template.dtype.bit_width() as usize / 8;
+            self.endianness
+                .convert_in_place(&mut self.pending[start..], scalar_width);
+            bytes = &bytes[n..];
+            if self.pending.len() == segment_bytes {
+                self.write_segment()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the last segment, if any samples are pending, and flushes the file. More samples
+    /// may still be written afterwards, starting a new segment.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.check_poisoned()?;
+        if !self.pending.is_empty() {
+            self.write_segment()?;
+        }
+        self.file.flush()
+    }
+
+    fn check_poisoned(&self) -> std::io::Result<()> {
+        if self.poisoned {
+            return Err(std::io::Error::other(
+                "a previous segment failed to be written",
+            ));
+        }
+        Ok(())
+    }
+
+    fn write_segment(&mut self) -> std::io::Result<()> {
+        self.check_poisoned()?;
+        let num_samples = self.pending.len() as u64 / self.template.size as u64;
+        let rx_time = self.template.rx_time
+            + Timestamp::from_secs_f64(self.samples_written as f64 / self.template.samp_rate);
+
+        let mut extra = Vec::new();
+        write(&mut extra, &self.template.extra_dict)?;
+        // All fields of the header are of fixed length, so strt doesn't change its length
+        let mut probe = Vec::new();
+        write(&mut probe, &header_tag(&self.template, rx_time, 0, 0))?;
+        let strt = (probe.len() + extra.len()) as u64;
+
+        let tag = header_tag(&self.template, rx_time, strt, self.pending.len() as u64);
+        self.poisoned = true;
+        write(&mut self.file, &tag)?;
+        self.file.write_all(&extra)?;
+        self.file.write_all(&self.pending)?;
+        self.poisoned = false;
+
+        self.pending.clear();
+        self.samples_written += num_samples;
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for AttachedHeaderWriter<W> {
+    fn drop(&mut self) {
+        // The error of the failed segment was already returned
+        if self.poisoned {
+            return;
+        }
+        if let Err(e) = self.finish() {
+            eprintln!(
+                "gnuradio-meta: error writing the last segment on drop: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Writes all samples of an iterator to a file with attached headers, as AttachedHeaderWriter
+/// does, and finishes it.
+///
+/// The number of samples of header_template (if not zero) is used as the number of samples per
+/// segment, so `HeaderBuilder::num_samples` configures the header interval.
+pub fn write_from_iter<T: SampleType, I: IntoIterator<Item = T>, W: Write>(
+    file: W,
+    header_template: &Header,
+    samples: I,
+) -> Result<(), MetaFileError> {
+    let mut writer = AttachedHeaderWriter::new(file, header_template.clone());
+    if header_template.get_num_samples() > 0 {
+        writer = writer.with_segment_len(header_template.get_num_samples());
+    }
+
+    let mut chunk = Vec::with_capacity(4096);
+    for sample in samples {
+        chunk.push(sample);
+        if chunk.len() == chunk.capacity() {
+            writer.write_samples(&chunk)?;
+            chunk.clear();
+        }
+    }
+    writer.write_samples(&chunk)?;
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod writer_tests {
+    use super::*;
+    use crate::core::{AttachedHeader, HeaderReader, SampleReadSeek};
+    use crate::header::{DataType, HeaderBuilder, SeekPreserve};
+    use std::io::SeekFrom;
+
+    #[test]
+    fn write_ramp_from_iter() {
+        let template = HeaderBuilder::new()
+            .samp_rate(1000.0)
+            .dtype(DataType::Float)
+            .cplx(false)
+            .rx_time(Timestamp::from_parts(10, 0.5))
+            .num_samples(300)
+            .build();
+
+        let mut file = Vec::new();
+        write_from_iter(&mut file, &template, (0..1000).map(|i| i as f32)).unwrap();
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = vec![0.0f32; 1200];
+        assert_eq!(reader.read_samples(&mut out).unwrap(), 1000);
+        assert!(out[..1000].iter().enumerate().all(|(i, &s)| s == i as f32));
+
+        // 3 full segments of 300 samples and a partial one
+        let headers: Vec<_> = (0..4)
+            .map(|i| reader.get_header_at_index(i).unwrap().unwrap())
+            .collect();
+        assert!(reader.get_header_at_index(4).unwrap().is_none());
+        for (i, header) in headers.iter().enumerate() {
+            assert_eq!(header.strt, 150);
+            assert_eq!(header.get_num_samples(), if i < 3 { 300 } else { 100 });
+            assert_eq!(header.samp_rate, 1000.0);
+            let expected = Timestamp::from_parts(10, 0.5 + i as f64 * 0.3);
+            assert!(header.rx_time.abs_diff(expected).as_secs_f64() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn write_big_endian() {
+        let template = HeaderBuilder::new()
+            .dtype(DataType::Short)
+            .cplx(false)
+            .build();
+        let samples: Vec<i16> = (0..100).map(|i| i * 300 - 10000).collect();
+
+        let mut file = Vec::new();
+        let mut writer =
+            AttachedHeaderWriter::new(&mut file, template).with_endianness(Endianness::Big);
+        writer.write_samples(&samples).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        assert_eq!(file[150..152], samples[0].to_be_bytes());
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        reader.set_endianness(Endianness::Big);
+        let mut out = vec![0i16; 100];
+        assert_eq!(reader.read_samples(&mut out).unwrap(), 100);
+        assert_eq!(out, samples);
+
+        reader.seek(SeekFrom::Start(0), SeekPreserve::None).unwrap();
+        let mut out = vec![0i32; 100];
+        assert_eq!(reader.read_conv(&mut out).unwrap(), 100);
+        assert!(out.iter().zip(&samples).all(|(&a, &b)| a == b as i32));
+    }
+
+    #[test]
+    fn write_wrong_sample_type() {
+        let template = HeaderBuilder::new().build();
+        let mut writer = AttachedHeaderWriter::new(Vec::new(), template);
+        assert!(matches!(
+            writer.write_samples(&[0i16]),
+            Err(MetaFileError::WrongSampleType { .. })
+        ));
+    }
+
+    #[test]
+    fn write_on_drop() {
+        let template = HeaderBuilder::new()
+            .dtype(DataType::Float)
+            .cplx(false)
+            .build();
+        let samples: Vec<f32> = (0..250).map(|i| i as f32).collect();
+
+        let mut file = Vec::new();
+        {
+            let mut writer = AttachedHeaderWriter::new(&mut file, template).with_segment_len(100);
+            writer.write_samples(&samples).unwrap();
+        }
+
+        let mut reader = AttachedHeader::from_bytes(file);
+        let mut out = vec![0.0f32; 300];
+        assert_eq!(reader.read_samples(&mut out).unwrap(), 250);
+        assert_eq!(out[..250], samples);
+        let last = reader.get_header_at_index(2).unwrap().unwrap();
+        assert_eq!(last.get_num_samples(), 50);
+    }
+
+    /// Accepts up to the given number of bytes, then errors
+    struct FailAfter(Vec<u8>, usize);
+
+    impl Write for FailAfter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0.len() == self.1 {
+                return Err(std::io::Error::other("disk full"));
+            }
+            let n = buf.len().min(self.1 - self.0.len());
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn failed_segment_not_retried() {
+        let template = HeaderBuilder::new()
+            .dtype(DataType::Float)
+            .cplx(false)
+            .build();
+        let samples: Vec<f32> = (0..250).map(|i| i as f32).collect();
+
+        // Fails in the data of the second segment, after its header
+        let mut file = FailAfter(Vec::new(), 2 * 150 + 400 + 100);
+        {
+            let mut writer = AttachedHeaderWriter::new(&mut file, template).with_segment_len(100);
+            assert!(writer.write_samples(&samples).is_err());
+            assert!(writer.write_samples(&samples).is_err());
+            assert!(writer.finish().is_err());
+        }
+        // Dropping the writer didn't write another header after the partial segment
+        assert_eq!(file.0.len(), 2 * 150 + 400 + 100);
+
+        // The first segment can still be recovered
+        let mut reader = AttachedHeader::from_bytes(file.0);
+        let mut out = vec![0.0f32; 100];
+        assert_eq!(reader.read_samples(&mut out).unwrap(), 100);
+        assert_eq!(out, samples[..100]);
+    }
+}